@@ -0,0 +1,75 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+use mxl_2_solo::{convert_reader, Options};
+
+/// The number of measures in the generated score
+const MEASURES: usize = 500;
+
+/// Builds a piano part with a treble and bass staff and a second part with one staff, which GJM
+/// writes as three tracks. Each measure of 4/4 has a run of eighths in the right hand, chords in
+/// the left hand and quarters in the second part, with a dynamic and a key change now and then.
+fn large_score() -> String {
+    let mut xml = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>
+<score-partwise version="3.1">
+  <work><work-title>Benchmark</work-title></work>
+  <part-list>
+    <score-part id="P1"><part-name>Piano</part-name></score-part>
+    <score-part id="P2"><part-name>Violin</part-name></score-part>
+  </part-list>
+  <part id="P1">
+"#);
+    let steps = ["C", "D", "E", "F", "G", "A", "B", "C"];
+    for m in 0..MEASURES {
+        xml.push_str(&format!("    <measure number=\"{}\">\n", m + 1));
+        if m == 0 {
+            xml.push_str("      <attributes><divisions>2</divisions><key><fifths>0</fifths></key><time><beats>4</beats><beat-type>4</beat-type></time><staves>2</staves><clef number=\"1\"><sign>G</sign><line>2</line></clef><clef number=\"2\"><sign>F</sign><line>4</line></clef></attributes>\n");
+        } else if m % 50 == 0 {
+            xml.push_str(&format!("      <attributes><key><fifths>{}</fifths></key></attributes>\n", (m / 50) % 5));
+        }
+        if m % 8 == 0 {
+            xml.push_str("      <direction><direction-type><dynamics><mf/></dynamics></direction-type></direction>\n");
+        }
+        for (i, step) in steps.iter().enumerate() {
+            let octave = if i == 7 { 6 } else { 5 };
+            let beam = match i % 2 { 0 => "begin", _ => "end" };
+            xml.push_str(&format!("      <note><pitch><step>{}</step><octave>{}</octave></pitch><duration>1</duration><voice>1</voice><type>eighth</type><staff>1</staff><beam number=\"1\">{}</beam></note>\n", step, octave, beam));
+        }
+        xml.push_str("      <backup><duration>8</duration></backup>\n");
+        for step in ["C", "F", "G", "C"].iter() {
+            xml.push_str(&format!("      <note><pitch><step>{}</step><octave>3</octave></pitch><duration>2</duration><voice>2</voice><type>quarter</type><staff>2</staff></note>\n", step));
+            xml.push_str("      <note><chord/><pitch><step>E</step><octave>3</octave></pitch><duration>2</duration><voice>2</voice><type>quarter</type><staff>2</staff></note>\n");
+        }
+        xml.push_str("    </measure>\n");
+    }
+    xml.push_str("  </part>\n  <part id=\"P2\">\n");
+    for m in 0..MEASURES {
+        xml.push_str(&format!("    <measure number=\"{}\">\n", m + 1));
+        if m == 0 {
+            xml.push_str("      <attributes><divisions>2</divisions><time><beats>4</beats><beat-type>4</beat-type></time><clef><sign>G</sign><line>2</line></clef></attributes>\n");
+        }
+        for step in ["A", "B", "C", "D"].iter() {
+            xml.push_str(&format!("      <note><pitch><step>{}</step><octave>4</octave></pitch><duration>2</duration><type>quarter</type></note>\n", step));
+        }
+        xml.push_str("    </measure>\n");
+    }
+    xml.push_str("  </part>\n</score-partwise>\n");
+    xml
+}
+
+fn conversion(c: &mut Criterion) {
+    let xml = large_score();
+    let mut group = c.benchmark_group("conversion");
+    group.throughput(Throughput::Bytes(xml.len() as u64));
+    group.sample_size(20);
+    group.bench_function("500 measures on 3 staves", |b| {
+        b.iter(|| {
+            let mut output = Vec::new();
+            convert_reader(xml.as_bytes(), &mut output, &Options::default()).unwrap();
+            output
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, conversion);
+criterion_main!(benches);
@@ -0,0 +1,78 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::error::Error;
+use crate::log;
+use crate::options::Options;
+use crate::partwise;
+
+/// Converts every MusicXml file in a directory to a GJM file next to it, spreading the files over a
+/// number of threads. The result of each file is printed once all of them are done, along with the
+/// warnings given while converting it, so the output of different files doesn't get mixed together.
+///
+/// # Arguments
+///
+/// * 'dir'     - The directory holding the MusicXml files
+/// * 'options' - The conversion options in use, including how many jobs to run at once
+///
+/// Returns the number of files that failed to convert
+///
+pub fn convert_dir(dir: &Path, options: &Options) -> std::io::Result<usize> {
+    let mut inputs = Vec::<PathBuf>::new();
+    for entry in dir.read_dir()? {
+        let path = entry?.path();
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("xml") | Some("musicxml") | Some("mxl") => {
+                inputs.push(path);
+            }
+            _ => {}
+        }
+    }
+    inputs.sort();
+
+    let jobs = match options.jobs {
+        Some(jobs) => jobs,
+        None => thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+    };
+
+    // Each thread takes the next unconverted file off the end of the queue until it is empty
+    let queue = Mutex::new(inputs.clone());
+    let results = Mutex::new(Vec::<(PathBuf, Result<(), Error>, Vec<String>)>::new());
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(inputs.len()) {
+            scope.spawn(|| {
+                loop {
+                    let input = match queue.lock().unwrap().pop() {
+                        Some(input) => input,
+                        None => break,
+                    };
+                    log::hold();
+                    let result = partwise::convert_file(&input, &input.with_extension("gjm"), options);
+                    let messages = log::release();
+                    results.lock().unwrap().push((input, result, messages));
+                }
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut failures = 0;
+    for (input, result, messages) in results {
+        for message in messages {
+            eprintln!("{}: {}", input.display(), message);
+        }
+        match result {
+            Ok(()) => {
+                println!("{}: Converted", input.display());
+            }
+            Err(e) => {
+                eprintln!("{}: Error: {}", input.display(), e);
+                failures += 1;
+            }
+        }
+    }
+    println!("Converted {} of {} files", inputs.len() - failures, inputs.len());
+    Ok(failures)
+}
@@ -0,0 +1,99 @@
+use std::fmt;
+
+use crate::report::Issue;
+
+/// The reasons a score can fail to convert
+#[derive(Debug)]
+pub enum Error {
+    /// A clef sign that GJM can't represent, found while unsupported clefs are treated as errors
+    UnsupportedClef(String),
+    /// The part at the given index never declares its divisions, found while in strict mode
+    MissingDivisions(usize),
+    /// The document has no "score-partwise" or "score-timewise" element
+    MissingScore,
+    /// The score has no "part" elements to convert
+    NoParts,
+    /// The score can't be converted without losing the details described by each issue, found
+    /// while lossless conversion is required
+    Lossy(Vec<Issue>),
+    /// A tag or attribute holds a value that can't be read as the number it should be, with the
+    /// number of the measure it was found in when known
+    InvalidValue { tag: String, value: String, measure: Option<usize> },
+    /// An element was found inside a tag that should only hold a value, with the number of the
+    /// measure it was found in when known
+    UnexpectedElement { tag: String, parent: String, measure: Option<usize> },
+    /// The document is not valid XML
+    Xml(xml::reader::Error),
+    /// The input looks like compressed MusicXml but can't be unzipped
+    Zip(zip::result::ZipError),
+    /// Reading the input or writing the output failed
+    Io(std::io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnsupportedClef(sign) => write!(f, "Unsupported clef sign '{}'", sign),
+            Error::MissingDivisions(part) => write!(f, "Part {} does not declare its divisions", part),
+            Error::MissingScore => write!(f, "No MusicXml score found"),
+            Error::NoParts => write!(f, "No parts found"),
+            Error::Lossy(issues) => {
+                write!(f, "The conversion would lose {} details", issues.len())?;
+                for issue in issues {
+                    write!(f, "\n\t{}", issue)?;
+                }
+                Ok(())
+            }
+            Error::InvalidValue { tag, value, measure } => {
+                write!(f, "Invalid {} value '{}'", tag, value)?;
+                write_measure(f, *measure)
+            }
+            Error::UnexpectedElement { tag, parent, measure } => {
+                write!(f, "Unexpected <{}> element inside <{}>", tag, parent)?;
+                write_measure(f, *measure)
+            }
+            Error::Xml(e) => write!(f, "{}", e),
+            Error::Zip(e) => write!(f, "{}", e),
+            Error::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Error {
+    /// Records the measure an error was found in, if the error doesn't already know it
+    ///
+    /// # Arguments
+    ///
+    /// * 'number' - The number of the measure being parsed when the error was found, as written
+    ///   in the score or counted from one when it has none
+    ///
+    pub fn in_measure(self, number: usize) -> Self {
+        match self {
+            Error::InvalidValue { tag, value, measure: None } => Error::InvalidValue { tag, value, measure: Some(number) },
+            Error::UnexpectedElement { tag, parent, measure: None } => Error::UnexpectedElement { tag, parent, measure: Some(number) },
+            e => e,
+        }
+    }
+}
+
+/// Writes where in the score an error was found, if known
+fn write_measure(f: &mut fmt::Formatter, measure: Option<usize>) -> fmt::Result {
+    match measure {
+        Some(number) => write!(f, " in measure {}", number),
+        None => Ok(()),
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<zip::result::ZipError> for Error {
+    fn from(e: zip::result::ZipError) -> Self {
+        Error::Zip(e)
+    }
+}
@@ -0,0 +1,77 @@
+/// The instrument GJM plays a track with when nothing better is known
+pub const DEFAULT: &str = "Piano";
+
+/// The GJM instrument for each range of General MIDI programs, counted from one as MusicXml does.
+/// Programs in no range have nothing close enough in GJM and are played on the default.
+const MIDI_PROGRAMS: [(u32, u32, &str); 15] = [
+    (1, 8, "Piano"),
+    (25, 32, "Pipa"),
+    (41, 45, "Erhu"),
+    (46, 46, "Pipa"),
+    (47, 47, "Guzheng"),
+    (49, 52, "Erhu"),
+    (57, 72, "Suona"),
+    (73, 75, "Dizi"),
+    (76, 78, "Xiao"),
+    (79, 80, "Dizi"),
+    (105, 107, "Pipa"),
+    (108, 108, "Guzheng"),
+    (110, 110, "Suona"),
+    (111, 111, "Erhu"),
+    (112, 112, "Suona"),
+];
+
+/// The starts of words found in instrument and part names along with the GJM instrument they
+/// stand for, used when a part has no MIDI program
+const NAMES: [(&str, &str); 23] = [
+    ("piano", "Piano"),
+    ("keyboard", "Piano"),
+    ("guzheng", "Guzheng"),
+    ("koto", "Guzheng"),
+    ("harp", "Guzheng"),
+    ("pipa", "Pipa"),
+    ("guitar", "Pipa"),
+    ("lute", "Pipa"),
+    ("mandolin", "Pipa"),
+    ("erhu", "Erhu"),
+    ("viol", "Erhu"),
+    ("cello", "Erhu"),
+    ("fiddle", "Erhu"),
+    ("dizi", "Dizi"),
+    ("flute", "Dizi"),
+    ("piccolo", "Dizi"),
+    ("recorder", "Dizi"),
+    ("xiao", "Xiao"),
+    ("shakuhachi", "Xiao"),
+    ("suona", "Suona"),
+    ("oboe", "Suona"),
+    ("clarinet", "Suona"),
+    ("trumpet", "Suona"),
+];
+
+/// Returns the GJM instrument closest to a General MIDI program, if GJM has one
+///
+/// # Arguments
+///
+/// * 'program' - The MIDI program, from 1 to 128
+///
+pub fn from_midi_program(program: u32) -> Option<&'static str> {
+    MIDI_PROGRAMS.iter()
+        .find(|(first, last, _)| (*first..=*last).contains(&program))
+        .map(|(_, _, instrument)| *instrument)
+}
+
+/// Returns the GJM instrument named by an instrument or part name, if any
+///
+/// # Arguments
+///
+/// * 'name' - The name to look for instrument words in, such as "Violin 1"
+///
+pub fn from_name(name: &str) -> Option<&'static str> {
+    // Match the starts of words so that a flute isn't taken for a lute
+    let name = name.to_lowercase();
+    let words: Vec<&str> = name.split(|c: char| !c.is_alphanumeric()).collect();
+    NAMES.iter()
+        .find(|(start, _)| words.iter().any(|word| word.starts_with(start)))
+        .map(|(_, instrument)| *instrument)
+}
@@ -0,0 +1,30 @@
+use std::io::{BufReader, Read, Write};
+
+pub mod batch;
+pub mod error;
+mod instrument;
+mod log;
+mod mxl;
+pub mod options;
+pub mod partwise;
+pub mod report;
+
+pub use error::Error;
+pub use options::Options;
+
+/// Converts a plain or compressed MusicXml document into GJM, writing the result to any output.
+/// This is the whole conversion without any of the files, dialogs or reports of the program, so
+/// that it can be run on documents held in memory.
+///
+/// # Arguments
+///
+/// * 'input'   - The source of the MusicXml document
+/// * 'output'  - Where to write the GJM document to
+/// * 'options' - The conversion options in use
+///
+pub fn convert_reader<R: Read, W: Write>(input: R, output: &mut W, options: &Options) -> Result<(), Error> {
+    let score = partwise::parse_for_writing(mxl::open(BufReader::new(input), options.input_format)?, options)?;
+    score.check_lossless(options)?;
+    score.write_gjm(output, options)?;
+    Ok(())
+}
@@ -0,0 +1,143 @@
+use std::cell::{Cell, RefCell};
+use std::fmt::Arguments;
+
+use crate::options::Verbosity;
+
+thread_local! {
+    /// How much is printed on this thread, so that conversions run at once with different
+    /// options each print as much as they were asked to
+    static VERBOSITY: Cell<Verbosity> = const { Cell::new(Verbosity::Normal) };
+    /// The index of the part and of the measure being read on this thread, if any, so messages
+    /// can say where in the score they come from. Each batch job runs on its own thread, so the
+    /// files being converted at once don't mix up each other's places.
+    static CONTEXT: Cell<(Option<usize>, Option<usize>)> = const { Cell::new((None, None)) };
+    /// Whether the messages printed on this thread are dropped, for work whose messages would
+    /// only repeat the ones given when it is done again
+    static SILENCED: Cell<bool> = const { Cell::new(false) };
+    /// The lines held back on this thread instead of being printed, so that a batch job's
+    /// messages can be printed together under the name of its file
+    static HELD: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+    /// The warnings given on this thread while recording for a report, whether or not they are
+    /// printed
+    static RECORDED: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+/// Prints a warning to stderr unless the program was asked to be quiet, labelled with the part
+/// and measure being read
+macro_rules! warning {
+    ($($arg:tt)*) => {
+        $crate::log::print($crate::options::Verbosity::Normal, "Warning! ", format_args!($($arg)*))
+    };
+}
+
+/// Prints a message to stderr only when the program was asked to be verbose, labelled with the
+/// part and measure being read
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        $crate::log::print($crate::options::Verbosity::Verbose, "", format_args!($($arg)*))
+    };
+}
+
+pub(crate) use {trace, warning};
+
+/// Sets how much is printed on this thread from now on
+///
+/// # Arguments
+///
+/// * 'verbosity' - The least verbosity a message needs to be printed at
+///
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.with(|current| current.set(verbosity));
+}
+
+/// Sets the part being read on this thread, which also leaves any measure
+///
+/// # Arguments
+///
+/// * 'index' - The index of the part in the score, or None once the parts are read
+///
+pub fn set_part(index: Option<usize>) {
+    CONTEXT.with(|context| context.set((index, None)));
+}
+
+/// Sets the measure being read on this thread within the current part
+///
+/// # Arguments
+///
+/// * 'index' - The index of the measure in its part, or None once it is read
+///
+pub fn set_measure(index: Option<usize>) {
+    CONTEXT.with(|context| context.set((context.get().0, index)));
+}
+
+/// Starts or stops dropping the messages printed on this thread, which are then neither printed,
+/// held nor recorded
+///
+/// # Arguments
+///
+/// * 'on' - Whether to drop the messages from now on
+///
+pub fn silence(on: bool) {
+    SILENCED.with(|silenced| silenced.set(on));
+}
+
+/// Holds back the messages printed on this thread from now on instead of printing them
+pub fn hold() {
+    HELD.with(|held| *held.borrow_mut() = Some(Vec::new()));
+}
+
+/// Stops holding back the messages printed on this thread and returns the ones held, each a line
+/// with its label
+pub fn release() -> Vec<String> {
+    HELD.with(|held| held.borrow_mut().take()).unwrap_or_default()
+}
+
+/// Starts or stops recording the warnings given on this thread, dropping any recorded before
+///
+/// # Arguments
+///
+/// * 'on' - Whether to record the warnings from now on
+///
+pub fn record(on: bool) {
+    RECORDED.with(|recorded| *recorded.borrow_mut() = if on { Some(Vec::new()) } else { None });
+}
+
+/// Stops recording the warnings given on this thread and returns the ones recorded
+pub fn take_recorded() -> Vec<String> {
+    RECORDED.with(|recorded| recorded.borrow_mut().take()).unwrap_or_default()
+}
+
+/// Prints a message to stderr if the program is at least as verbose as the message needs,
+/// putting the current part and measure between the label and the message. Messages being held
+/// back are kept instead of printed, and warnings being recorded are recorded without their label.
+///
+/// # Arguments
+///
+/// * 'verbosity' - The least verbosity the message is printed at
+/// * 'label'     - The text to start the line with
+/// * 'message'   - The message itself
+///
+pub fn print(verbosity: Verbosity, label: &str, message: Arguments) {
+    if SILENCED.with(Cell::get) {
+        return;
+    }
+    let line = match CONTEXT.with(Cell::get) {
+        (Some(part), Some(measure)) => format!("Part {}, measure {}: {}", part, measure, message),
+        (Some(part), None) => format!("Part {}: {}", part, message),
+        _ => message.to_string(),
+    };
+    if verbosity == Verbosity::Normal {
+        RECORDED.with(|recorded| {
+            if let Some(recorded) = recorded.borrow_mut().as_mut() {
+                recorded.push(line.clone());
+            }
+        });
+    }
+    if VERBOSITY.with(Cell::get) as u8 >= verbosity as u8 {
+        let line = format!("{}{}", label, line);
+        HELD.with(|held| match held.borrow_mut().as_mut() {
+            Some(held) => held.push(line),
+            None => eprintln!("{}", line),
+        });
+    }
+}
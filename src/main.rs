@@ -1,75 +1,101 @@
-use std::fs::File;
-use std::io::{BufReader, Write};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
 
-use xml::reader::{EventReader, XmlEvent};
-
-mod partwise;
+use mxl_2_solo::report::Issue;
+use mxl_2_solo::{batch, options, partwise};
 
 fn main() -> std::io::Result<()> {
-    let dialog_result = wfd::open_dialog(Default::default()).unwrap();
-    let file = File::open(dialog_result.selected_file_path).unwrap();
-    let file = BufReader::new(file);
-    let mut parser = EventReader::new(file);
-    let mut score = partwise::Score::new();
-
-    loop{
-        match parser.next() {
-            Ok(XmlEvent::StartElement {name, ..}) => {
-                match name.local_name.as_str() {
-                    "score-partwise" => {
-                        score = partwise::Score::parse_score(&mut parser);
-                    }
-                    _ => {}
-                }
-            }
-            Ok(XmlEvent::EndElement {..}) => {
-            }
-            Ok(XmlEvent::EndDocument) => {
-                let mut outfile = File::create("output.gjm").unwrap();
-                // File Version
-                let line = "Version ='1.1.0.0'\n";
-                outfile.write_all(line.as_bytes())?;
-
-                // Overall Notation info
-                let line = "Notation = {\n";
-                outfile.write_all(line.as_bytes())?;
-                //      Version and author info
-                let line = "\tVersion ='1.1.0.0',\n\tNotationName = 'Unnamed',\n\tNotationAuther = 'UnknownAuthor',\n\tNotationTranslater = 'UnknownTranslator',\n\tNotationCreator = 'Dwarfed',\n\tVolume = 1,\n";
-                outfile.write_all(line.as_bytes())?;
-                //      Time signature info
-                let line = format!("\tBeatsPerMeasure = {},\n", score.get_beats_per_measure());
-                outfile.write_all(line.as_bytes())?;
-                let line = format!("\tBeatDurationType = '{}',\n", score.get_beat_duration_type());
-                outfile.write_all(line.as_bytes())?;
-                let line = "\tNumberedKeySignature = 'C',\n";
-                outfile.write_all(line.as_bytes())?;
-
-                //      BPM
-                let line = "\tMeasureBeatsPerMinuteMap = {\n";
-                outfile.write_all(line.as_bytes())?;
-                let line = score.get_bpm_map();
-                outfile.write_all(line.as_bytes())?;
-                let line = "\t},\n";
-                outfile.write_all(line.as_bytes())?;
-
-                //      Number of Measures
-                let line = format!("\tMeasureAlignedCount = {},\n", score.get_measure_count());
-                outfile.write_all(line.as_bytes())?;
-
-                // Close notation info
-                let line = "}\n";
-                outfile.write_all(line.as_bytes())?;
+    let options = options::Options::from_args(std::env::args().skip(1));
+    if options.usage_error {
+        eprintln!("{}", options::USAGE);
+        std::process::exit(1);
+    }
+    if options.show_usage {
+        println!("{}", options::USAGE);
+        return Ok(());
+    }
+    if let Some(dir) = &options.batch {
+        if options.check {
+            eprintln!("Error: --check can only check a single file");
+            std::process::exit(1);
+        }
+        if batch::convert_dir(dir, &options)? > 0 {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
 
-                // Track/measure/note info
-                score.write_score_gjn(&mut outfile)?;
-                break;
+    let input = match &options.input {
+        Some(input) => input.clone(),
+        None => match choose_input() {
+            Some(input) => input,
+            None => {
+                eprintln!("Error: No input file was chosen");
+                std::process::exit(1);
             }
+        },
+    };
+    if options.check {
+        let result = if input == Path::new("-") {
+            partwise::check_reader(BufReader::new(std::io::stdin()), &options)
+        } else {
+            partwise::check_file(&input, &options)
+        };
+        match result {
+            Ok(issues) => print_issues(&issues),
             Err(e) => {
-                println!("Error: {}", e);
-                break;
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
             }
-            _ => {}
         }
+        return Ok(());
+    }
+    if input == Path::new("-") {
+        let output = options.output.clone().unwrap_or_else(|| PathBuf::from("output.gjm"));
+        let stdin = BufReader::new(std::io::stdin());
+        if let Err(e) = partwise::convert_reader_to_path(stdin, "stdin", &output, &options) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if !input.is_file() {
+        eprintln!("Error: '{}' is not a file", input.display());
+        std::process::exit(1);
+    }
+    let output = options.output.clone().unwrap_or_else(|| input.with_extension("gjm"));
+    if let Err(e) = partwise::convert_file(&input, &output, &options) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
     }
     Ok(())
 }
+
+/// Asks for the file to convert with a file dialog, returning None when none is chosen
+#[cfg(windows)]
+fn choose_input() -> Option<PathBuf> {
+    wfd::open_dialog(Default::default()).ok().map(|result| result.selected_file_path)
+}
+
+/// Other platforms have no file dialog, so the input has to be given as an argument
+#[cfg(not(windows))]
+fn choose_input() -> Option<PathBuf> {
+    None
+}
+
+/// Prints the problems found by a check, one per line
+///
+/// # Arguments
+///
+/// * 'issues' - The problems found
+///
+fn print_issues(issues: &[Issue]) {
+    for issue in issues {
+        println!("{}", issue);
+    }
+    match issues.len() {
+        0 => println!("Nothing was found that GJM can't represent"),
+        1 => println!("1 problem found"),
+        count => println!("{} problems found", count),
+    }
+}
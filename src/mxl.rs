@@ -0,0 +1,76 @@
+use std::io::{BufRead, Cursor, Read};
+
+use xml::reader::{EventReader, XmlEvent};
+use zip::ZipArchive;
+
+use crate::error::Error;
+use crate::log::warning;
+use crate::options::InputFormat;
+
+/// The bytes every ZIP archive, and so every compressed MusicXml file, starts with
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+/// Returns a reader over the uncompressed MusicXml document of an input. Plain MusicXml is read
+/// as it comes in, while compressed MusicXml has to be read into memory first to be unzipped.
+///
+/// # Arguments
+///
+/// * 'input'  - The source of the MusicXml document
+/// * 'format' - The format of the input, worked out from its first bytes when Auto
+///
+pub fn open<'a, R: BufRead + 'a>(mut input: R, format: InputFormat) -> Result<Box<dyn Read + 'a>, Error> {
+    let compressed = match format {
+        InputFormat::Xml => false,
+        InputFormat::Mxl => true,
+        InputFormat::Auto => input.fill_buf()?.starts_with(ZIP_MAGIC),
+    };
+    if !compressed {
+        return Ok(Box::new(input));
+    }
+    let mut data = Vec::<u8>::new();
+    input.read_to_end(&mut data)?;
+    Ok(Box::new(Cursor::new(read_root_file(data)?)))
+}
+
+/// Unzips a compressed MusicXml file and returns the score document it holds
+///
+/// # Arguments
+///
+/// * 'data' - The whole compressed file
+///
+fn read_root_file(data: Vec<u8>) -> Result<Vec<u8>, Error> {
+    let mut archive = ZipArchive::new(Cursor::new(data))?;
+
+    // The container lists the score as its first root file
+    let mut root_path = None;
+    if let Ok(container) = archive.by_name("META-INF/container.xml") {
+        let mut parser = EventReader::new(container);
+        loop {
+            match parser.next() {
+                Ok(XmlEvent::StartElement {name, attributes, ..}) if name.local_name.as_str() == "rootfile" => {
+                    root_path = attributes.into_iter()
+                        .find(|attr| attr.name.local_name.as_str() == "full-path")
+                        .map(|attr| attr.value);
+                    break;
+                }
+                Ok(XmlEvent::EndDocument) | Err(_) => {
+                    break;
+                }
+                _ => {}
+            }
+        }
+    }
+    // Without a container fall back on the first MusicXml file outside of META-INF
+    if root_path.is_none() {
+        warning!("Compressed file has no container listing its score, using the first MusicXml file");
+        root_path = archive.file_names()
+            .filter(|name| !name.starts_with("META-INF/"))
+            .find(|name| name.ends_with(".xml") || name.ends_with(".musicxml"))
+            .map(|name| name.to_string());
+    }
+
+    let root_path = root_path.ok_or(Error::MissingScore)?;
+    let mut root = Vec::<u8>::new();
+    archive.by_name(&root_path)?.read_to_end(&mut root)?;
+    Ok(root)
+}
@@ -0,0 +1,567 @@
+use std::path::PathBuf;
+
+use crate::partwise::{pitch_index_from_name, Clef};
+
+/// How to run the program, printed when the arguments don't make sense
+pub const USAGE: &str = "Usage: mxl_2_solo [OPTIONS] [INPUT [OUTPUT]]
+
+Converts the MusicXml file INPUT, or stdin when INPUT is '-', into the GJM file OUTPUT, or stdout
+when OUTPUT is '-' or --stdout is given. OUTPUT defaults to INPUT with a .gjm extension, and on
+Windows a file dialog asks for INPUT when it isn't given. The output is UTF-8 with Unix line
+endings, or Windows line endings with --crlf. Warnings and errors are printed to stderr, where
+--quiet leaves only the errors and --verbose adds what is read from each part and measure.
+
+With --stream each part is written to a temporary file as soon as it is read, so only one part of
+a large score is held in memory at once.
+
+GJM has no lyrics, so with --lyrics the words are written to OUTPUT with a .txt extension, one line
+for each measure of each verse.
+
+With --conductor-track an extra first track with no notes carries the key and meter of every
+measure. It counts towards --max-tracks, three tracks by default, so one fewer staff is written.
+
+With --check nothing is written, and everything in INPUT that GJM can't represent is listed
+instead.";
+
+/// What to do when a clef sign that GJM can't represent is found
+#[derive(Clone, Copy, Debug, Default)]
+pub enum UnsupportedClef {
+    /// Print a warning and keep the clef the staff already had
+    #[default]
+    Warn,
+    /// Print a warning and use the given clef instead
+    Fallback(Clef),
+    /// Stop the conversion with an error
+    Error,
+}
+
+/// Which of a part's names to label its tracks with
+#[derive(Clone, Copy, Debug, Default)]
+pub enum TrackLabel {
+    /// The full part name, falling back on the abbreviation
+    #[default]
+    Name,
+    /// The abbreviated part name, falling back on the full name
+    Abbreviation,
+}
+
+/// When pairs of eighths are played long-short
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Swing {
+    /// Always play eighths evenly
+    #[default]
+    Off,
+    /// Swing the measures that are marked to be swung
+    Detect,
+    /// Swing every measure
+    Force,
+}
+
+/// How long a chord whose notes have different durations lasts
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ChordDuration {
+    /// As long as its shortest note, so the next note is struck on time
+    #[default]
+    Shortest,
+    /// As long as its longest note, so every note is held for its full length
+    Longest,
+    /// As long as its shortest note, with the longer notes tied on into the chords that follow
+    Split,
+}
+
+/// The order tracks are written in
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum TrackOrder {
+    /// The order the parts and staves appear in the score
+    #[default]
+    Parsed,
+    /// The given order, listing the index each track has in the parsed order
+    Permutation(Vec<usize>),
+    /// Sorted by the label of each track's part
+    Name,
+    /// Sorted by the average pitch of each track, highest first
+    Pitch,
+}
+
+/// How numbered (jianpu) signs are counted
+#[derive(Clone, Copy, Debug, Default)]
+pub enum NumberedNotation {
+    /// Every pitch keeps the same number whatever the key
+    #[default]
+    Fixed,
+    /// Numbers are counted from the tonic of the key, so the tonic is always 1
+    Movable,
+}
+
+/// How glissandi and slides are played
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Glissando {
+    /// Keep the marking on the notes, which GJM plays as just the end notes
+    #[default]
+    Keep,
+    /// Replace the first note with a chromatic run to the second
+    Fill,
+}
+
+/// The format of the input file
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum InputFormat {
+    /// Work out the format from the first bytes of the input
+    #[default]
+    Auto,
+    /// Plain MusicXml
+    Xml,
+    /// Compressed MusicXml, read into memory to be unzipped
+    Mxl,
+}
+
+/// How much is printed to stderr while converting
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Verbosity {
+    /// Only errors
+    Quiet,
+    /// Errors and warnings about what can't be converted as it is
+    #[default]
+    Normal,
+    /// Also trace what is read from each part and measure
+    Verbose,
+}
+
+/// How positions are rounded when converting from MusicXml divisions to GJM stamps
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Rounding {
+    /// Round to the closest stamp
+    #[default]
+    Nearest,
+    /// Round down to the stamp before
+    Floor,
+    /// Round up to the stamp after
+    Ceil,
+}
+
+impl Rounding {
+    /// Rounds a value to a whole number using this rounding mode
+    ///
+    /// # Arguments
+    ///
+    /// * 'value' - The value to round
+    ///
+    pub fn apply(&self, value: f64) -> f64 {
+        // Allow a little floating point error so exact stamps don't snap to the next one
+        match self {
+            Rounding::Nearest => value.round(),
+            Rounding::Floor => (value + 1e-9).floor(),
+            Rounding::Ceil => (value - 1e-9).ceil(),
+        }
+    }
+}
+
+/// A collection of settings that control how a score is converted
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// Whether to emit an extra leading track with no notes that carries the key and meter
+    /// timeline, taking up one of the tracks that can be written
+    pub conductor_track: bool,
+    /// Whether to guess triplets from beamed groups that are missing explicit tuplets. This is a
+    /// heuristic for broken exports and is off by default.
+    pub infer_beamed_tuplets: bool,
+    /// Whether to write a JSON report describing the conversion next to the output
+    pub report: bool,
+    /// Whether to write the words of the score to a text file next to the output, since GJM has
+    /// nowhere to put them
+    pub lyrics: bool,
+    /// How to handle clef signs that GJM can't represent
+    pub unsupported_clef: UnsupportedClef,
+    /// Whether to stop with an error instead of working around invalid MusicXml
+    pub strict: bool,
+    /// Which of a part's names to label its tracks with
+    pub track_label: TrackLabel,
+    /// The GJM instrument to play every track with, instead of matching each part's instrument
+    pub instrument: Option<String>,
+    /// The order tracks are written in
+    pub track_order: TrackOrder,
+    /// How numbered signs are counted
+    pub numbered_notation: NumberedNotation,
+    /// A tuning offset in cents added to every pitch, rounded to the nearest half step
+    pub tuning_offset: i32,
+    /// How chord positions and measure lengths are rounded to GJM stamps
+    pub rounding: Rounding,
+    /// The lowest and highest pitch indexes notes may sound at, with notes outside moved by
+    /// octaves until they fit. Notes are left as they are when not given.
+    pub pitch_range: Option<(u32, u32)>,
+    /// How long a chord whose notes have different durations lasts
+    pub chord_duration: ChordDuration,
+    /// How glissandi and slides are played
+    pub glissando: Glissando,
+    /// When pairs of eighths are played long-short
+    pub swing: Swing,
+    /// How much longer the first eighth of a swung pair is than the second, defaulting to the
+    /// triplet feel of 2
+    pub swing_ratio: Option<f64>,
+    /// The largest number of stamps to randomly move each chord by, zero to leave chords in place.
+    /// Only the timing is humanized as GJM has no velocity for each note.
+    pub humanize: u32,
+    /// The seed used when humanizing, so the same seed always gives the same output
+    pub seed: u64,
+    /// Whether to write the meter of each measure as a MeasureTimeSignatureMap. GJM doesn't
+    /// document this map and takes the meter from each measure's DurationStampMax, so it is only
+    /// written when asked for.
+    pub meter_map: bool,
+    /// Whether to refuse to write any output when the conversion would lose musical details
+    pub lossless: bool,
+    /// Whether to only list what the conversion can't represent, without writing any output
+    pub check: bool,
+    /// Whether to write transposing instruments at the pitch they are written at instead of the
+    /// pitch they sound at
+    pub written_pitch: bool,
+    /// Whether to write repeated sections out as many times as they are played
+    pub unfold: bool,
+    /// Whether to write each part to a temporary file as soon as it is parsed, which bounds the
+    /// memory a large score takes at the cost of a round trip through the disk
+    pub stream: bool,
+    /// Whether to end lines with "\r\n" instead of "\n", as Windows programs may expect
+    pub crlf: bool,
+    /// Whether to wait for each output file to reach the disk before reporting it as converted
+    pub sync: bool,
+    /// Whether to replace measures that fail to parse with rests instead of stopping
+    pub keep_going: bool,
+    /// The MusicXml file to convert, asked for with a dialog on Windows when not given, or "-" for stdin
+    pub input: Option<PathBuf>,
+    /// The GJM file to write, defaulting to the input with a .gjm extension, or "-" for stdout
+    pub output: Option<PathBuf>,
+    /// Whether the arguments were wrong or asked for help, so the usage should be printed instead
+    pub show_usage: bool,
+    /// Whether the usage is shown because the arguments were wrong rather than asked for
+    pub usage_error: bool,
+    /// How much is printed to stderr while converting
+    pub verbosity: Verbosity,
+    /// The format of the input
+    pub input_format: InputFormat,
+    /// A directory whose MusicXml files should all be converted, instead of asking for one file
+    pub batch: Option<PathBuf>,
+    /// How many files to convert at once in batch mode, defaulting to the number of cores
+    pub jobs: Option<usize>,
+    /// The most tracks to write, defaulting to the three GJM supports, with any tracks past it
+    /// dropped
+    pub max_tracks: Option<usize>,
+}
+
+impl Options {
+    /// Builds a set of Options from command line flags, warning about any flag it doesn't know
+    ///
+    /// # Arguments
+    ///
+    /// * 'args' - An iterator over the command line arguments, not including the program name
+    ///
+    pub fn from_args<I: Iterator<Item = String>>(args: I) -> Self {
+        let mut options = Options::default();
+        let mut args = args;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--chord-duration=shortest" => {
+                    options.chord_duration = ChordDuration::Shortest;
+                }
+                "--chord-duration=longest" => {
+                    options.chord_duration = ChordDuration::Longest;
+                }
+                "--chord-duration=split" => {
+                    options.chord_duration = ChordDuration::Split;
+                }
+                "--meter-map" => {
+                    options.meter_map = true;
+                }
+                "--conductor-track" => {
+                    options.conductor_track = true;
+                }
+                "--infer-beamed-tuplets" => {
+                    options.infer_beamed_tuplets = true;
+                }
+                "--report" => {
+                    options.report = true;
+                }
+                "--lyrics" => {
+                    options.lyrics = true;
+                }
+                "--keep-going" => {
+                    options.keep_going = true;
+                }
+                "--lossless" => {
+                    options.lossless = true;
+                }
+                "--check" => {
+                    options.check = true;
+                }
+                "--written-pitch" => {
+                    options.written_pitch = true;
+                }
+                "-q" | "--quiet" => {
+                    options.verbosity = Verbosity::Quiet;
+                }
+                "-v" | "--verbose" => {
+                    options.verbosity = Verbosity::Verbose;
+                }
+                "--stdout" => {
+                    options.output = Some(PathBuf::from("-"));
+                }
+                "--stream" => {
+                    options.stream = true;
+                }
+                "--unfold" => {
+                    options.unfold = true;
+                }
+                "--strict" => {
+                    options.strict = true;
+                }
+                "--crlf" => {
+                    options.crlf = true;
+                }
+                "--sync" => {
+                    options.sync = true;
+                }
+                "--glissando=keep" => {
+                    options.glissando = Glissando::Keep;
+                }
+                "--glissando=fill" => {
+                    options.glissando = Glissando::Fill;
+                }
+                "--numbered=fixed" => {
+                    options.numbered_notation = NumberedNotation::Fixed;
+                }
+                "--numbered=movable" => {
+                    options.numbered_notation = NumberedNotation::Movable;
+                }
+                "--rounding=nearest" => {
+                    options.rounding = Rounding::Nearest;
+                }
+                "--rounding=floor" => {
+                    options.rounding = Rounding::Floor;
+                }
+                "--rounding=ceil" => {
+                    options.rounding = Rounding::Ceil;
+                }
+                "--track-label=name" => {
+                    options.track_label = TrackLabel::Name;
+                }
+                "--track-label=abbreviation" => {
+                    options.track_label = TrackLabel::Abbreviation;
+                }
+                "--swing=off" => {
+                    options.swing = Swing::Off;
+                }
+                "--swing=detect" => {
+                    options.swing = Swing::Detect;
+                }
+                "--swing=force" => {
+                    options.swing = Swing::Force;
+                }
+                "--track-order=name" => {
+                    options.track_order = TrackOrder::Name;
+                }
+                "--track-order=pitch" => {
+                    options.track_order = TrackOrder::Pitch;
+                }
+                "--unsupported-clef=warn" => {
+                    options.unsupported_clef = UnsupportedClef::Warn;
+                }
+                "--unsupported-clef=G" => {
+                    options.unsupported_clef = UnsupportedClef::Fallback(Clef::G);
+                }
+                "--unsupported-clef=F" => {
+                    options.unsupported_clef = UnsupportedClef::Fallback(Clef::F);
+                }
+                "--unsupported-clef=error" => {
+                    options.unsupported_clef = UnsupportedClef::Error;
+                }
+                _ if arg.starts_with("--batch=") => {
+                    options.batch = Some(expand_path(&arg["--batch=".len()..]));
+                }
+                _ if arg == "--humanize" || arg.starts_with("--humanize=") => {
+                    // The amount can be given after an = or as the next argument
+                    let amount = match arg.strip_prefix("--humanize=") {
+                        Some(amount) => Some(amount.to_string()),
+                        None => args.next(),
+                    };
+                    match amount.as_deref().map(str::parse::<u32>) {
+                        Some(Ok(amount)) => {
+                            options.humanize = amount;
+                        }
+                        _ => {eprintln!("Warning! Invalid humanize amount for '{}'", arg);}
+                    }
+                }
+                _ if arg == "--jobs" || arg.starts_with("--jobs=") => {
+                    // The count can be given after an = or as the next argument
+                    let jobs = match arg.strip_prefix("--jobs=") {
+                        Some(jobs) => Some(jobs.to_string()),
+                        None => args.next(),
+                    };
+                    match jobs.as_deref().map(str::parse::<usize>) {
+                        Some(Ok(jobs)) if jobs > 0 => {
+                            options.jobs = Some(jobs);
+                        }
+                        _ => {eprintln!("Warning! Invalid job count for '{}'", arg);}
+                    }
+                }
+                _ if arg == "--instrument" || arg.starts_with("--instrument=") => {
+                    // The name can be given after an = or as the next argument
+                    let name = match arg.strip_prefix("--instrument=") {
+                        Some(name) => Some(name.to_string()),
+                        None => args.next(),
+                    };
+                    match name {
+                        Some(name) if !name.is_empty() => {
+                            options.instrument = Some(name);
+                        }
+                        _ => {eprintln!("Warning! Missing instrument name for '{}'", arg);}
+                    }
+                }
+                _ if arg == "--input-format" || arg.starts_with("--input-format=") => {
+                    // The format can be given after an = or as the next argument
+                    let format = match arg.strip_prefix("--input-format=") {
+                        Some(format) => Some(format.to_string()),
+                        None => args.next(),
+                    };
+                    match format.as_deref() {
+                        Some("auto") => {
+                            options.input_format = InputFormat::Auto;
+                        }
+                        Some("xml") => {
+                            options.input_format = InputFormat::Xml;
+                        }
+                        Some("mxl") => {
+                            options.input_format = InputFormat::Mxl;
+                        }
+                        _ => {eprintln!("Warning! Invalid input format for '{}', expected auto, xml or mxl", arg);}
+                    }
+                }
+                _ if arg == "--max-tracks" || arg.starts_with("--max-tracks=") => {
+                    // The count can be given after an = or as the next argument
+                    let count = match arg.strip_prefix("--max-tracks=") {
+                        Some(count) => Some(count.to_string()),
+                        None => args.next(),
+                    };
+                    match count.as_deref().map(str::parse::<usize>) {
+                        Some(Ok(count)) if count > 0 => {
+                            options.max_tracks = Some(count);
+                        }
+                        _ => {eprintln!("Warning! Invalid track count for '{}'", arg);}
+                    }
+                }
+                _ if arg.starts_with("--pitch-range=") => {
+                    let range = arg["--pitch-range=".len()..].split_once(',');
+                    match range.map(|(low, high)| (pitch_index_from_name(low), pitch_index_from_name(high))) {
+                        Some((Some(low), Some(high))) if low <= high => {
+                            options.pitch_range = Some((low, high));
+                        }
+                        _ => {eprintln!("Warning! Invalid pitch range in '{}', expected something like C3,C6", arg);}
+                    }
+                }
+                _ if arg.starts_with("--seed=") => {
+                    match arg["--seed=".len()..].parse::<u64>() {
+                        Ok(seed) => {
+                            options.seed = seed;
+                        }
+                        Err(_) => {eprintln!("Warning! Invalid seed in '{}'", arg);}
+                    }
+                }
+                _ if arg.starts_with("--swing-ratio=") => {
+                    match arg["--swing-ratio=".len()..].parse::<f64>() {
+                        Ok(ratio) if ratio > 0.0 => {
+                            options.swing_ratio = Some(ratio);
+                        }
+                        _ => {eprintln!("Warning! Invalid swing ratio in '{}'", arg);}
+                    }
+                }
+                _ if arg.starts_with("--track-order=") => {
+                    let order: Result<Vec<usize>, _> = arg["--track-order=".len()..].split(',').map(|i| i.trim().parse::<usize>()).collect();
+                    match order {
+                        Ok(order) => {
+                            options.track_order = TrackOrder::Permutation(order);
+                        }
+                        Err(_) => {eprintln!("Warning! Invalid track order in '{}'", arg);}
+                    }
+                }
+                _ if arg.starts_with("--tuning-offset=") => {
+                    match arg["--tuning-offset=".len()..].parse::<i32>() {
+                        Ok(cents) => {
+                            options.tuning_offset = cents;
+                        }
+                        Err(_) => {eprintln!("Warning! Invalid tuning offset in '{}'", arg);}
+                    }
+                }
+                "-h" | "--help" => {
+                    options.show_usage = true;
+                }
+                _ if arg == "-" || !arg.starts_with("--") => {
+                    // The first path is the input and the second the output
+                    let path = match arg.as_str() {
+                        "-" => PathBuf::from("-"),
+                        _ => expand_path(&arg),
+                    };
+                    if options.input.is_none() {
+                        options.input = Some(path);
+                    } else if options.output.is_none() {
+                        options.output = Some(path);
+                    } else {
+                        eprintln!("Error: Too many arguments, '{}' is neither the input nor the output", arg);
+                        options.show_usage = true;
+                        options.usage_error = true;
+                    }
+                }
+                _ => {eprintln!("Warning! Unrecognized argument '{}'", arg);}
+            }
+        }
+        options
+    }
+}
+
+/// Turns a path given on the command line into a path on disk, so that paths copied from a shell
+/// or file manager work as given. A leading "~" is replaced with the home directory and a
+/// "file://" URL is replaced with the path it points to.
+///
+/// # Arguments
+///
+/// * 'arg' - The path or URL as given on the command line
+///
+pub fn expand_path(arg: &str) -> PathBuf {
+    if let Some(url_path) = arg.strip_prefix("file://") {
+        // The host is normally empty, but "localhost" means the same thing
+        let url_path = url_path.strip_prefix("localhost").unwrap_or(url_path);
+        let mut path = decode_percent(url_path);
+        // Windows URLs put a slash before the drive letter, as in "file:///C:/Music"
+        if path.len() > 2 && path.starts_with('/') && path.as_bytes()[2] == b':' {
+            path.remove(0);
+        }
+        return PathBuf::from(path);
+    }
+    if arg == "~" || arg.starts_with("~/") || arg.starts_with("~\\") {
+        if let Some(home) = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE")) {
+            let mut path = PathBuf::from(home);
+            if arg.len() > 2 {
+                path.push(&arg[2..]);
+            }
+            return path;
+        }
+        eprintln!("Warning! Could not find the home directory to expand '{}'", arg);
+    }
+    PathBuf::from(arg)
+}
+
+/// Decodes the "%XX" escapes in a URL, leaving any invalid escape as it is
+fn decode_percent(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::<u8>::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let digits = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+            if let Ok(byte) = u8::from_str_radix(digits, 16) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
@@ -1,11 +1,28 @@
-use std::fs::File;
 use std::fmt::Write;
-use std::io::{BufReader, Write as OtherWrite};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write as OtherWrite};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::str::FromStr;
 use std::collections::BTreeMap;
+use xml::attribute::OwnedAttribute;
+use xml::common::Position;
 use xml::reader::{EventReader, XmlEvent};
 
+use crate::error::Error;
+use crate::instrument;
+use crate::log::{self, trace, warning};
+use crate::mxl;
+use crate::options::{ChordDuration, Glissando, NumberedNotation, Options, Rounding, Swing, TrackLabel, TrackOrder, UnsupportedClef};
+use crate::report::{Issue, Location, Report, Statistics, TrackReport};
+
 const MAX_PART_COUNT: usize = 3;
 
+/// Get the most tracks that will be written, including the conductor track
+fn get_max_tracks(options: &Options) -> usize {
+    options.max_tracks.unwrap_or(MAX_PART_COUNT)
+}
+
 fn indent(cnt: usize) -> String {
     let mut ind = "".to_string();
     for _ in 0..cnt {
@@ -14,38 +31,429 @@ fn indent(cnt: usize) -> String {
     ind
 }
 
-fn calc_measure_maps(measures: &Vec<Measure>) -> (Vec<(usize, i32)>, Vec<(usize, Clef)>, Vec<(usize, u32)>) {
+/// The key signature, clef and volume maps of a staff, each a list of (measure, value)
+type MeasureMaps = (Vec<(usize, i32)>, Vec<(usize, Clef)>, Vec<(usize, u32)>);
+
+/// Builds the key signature, clef and volume maps of a staff as lists of (measure, value)
+fn calc_measure_maps(measures: &[Measure]) -> MeasureMaps {
     let mut key_sigs = Vec::<(usize, i32)>::new();
     let mut clefs = Vec::<(usize, Clef)>::new();
     let mut volumes = Vec::<(usize, u32)>::new();
 
-    if let Some(measure) = measures.first() {
-        let mut last_key_sig = measure.attributes.key;
-        key_sigs.push((0, last_key_sig));
+    // Each map starts at the first measure and then only holds the measures that change it
+    for (i, measure) in measures.iter().enumerate() {
+        if key_sigs.last().map(|(_, key)| *key) != Some(measure.attributes.key) {
+            key_sigs.push((i, measure.attributes.key));
+        }
+        if clefs.last().map(|(_, clef)| *clef) != Some(measure.get_clef()) {
+            clefs.push((i, measure.get_clef()));
+        }
+        let volume = measure.get_start_volume();
+        if volumes.last().map(|(_, last)| *last) != Some(volume) {
+            volumes.push((i, volume));
+        }
+    }
+
+    (key_sigs, clefs, volumes)
+}
+
+/// Writes the key signature, clef, instrument, volume curve and volume maps every GJM track starts
+/// with
+///
+/// # Arguments
+///
+/// * 'file'       - The output to write to
+/// * 'measures'   - The measures of the staff the track is written from
+/// * 'instrument' - The GJM instrument the track is played with
+///
+fn write_track_maps<W: OtherWrite>(file: &mut W, measures: &[Measure], instrument: &str) -> std::io::Result<()> {
+    let (keys, clefs, volumes) = calc_measure_maps(measures);
+
+    // Key Signature Map
+    let line = format!("{}MeasureKeySignatureMap = {{\n", indent(2));
+    file.write_all(line.as_bytes())?;
+    for (i, key) in keys {
+        let line = format!("{}{{ {}, {} }},\n", indent(3), i, key);
+        file.write_all(line.as_bytes())?;
+    }
+    let line = format!("{}}},\n", indent(2));
+    file.write_all(line.as_bytes())?;
 
-        let mut last_clef = measure.attributes.clef;
-        clefs.push((0, last_clef));
+    // Clef Type Map
+    let line = format!("{}MeasureClefTypeMap = {{\n", indent(2));
+    file.write_all(line.as_bytes())?;
+    for (i, clef) in clefs {
+        let line = format!("{}{{ {}, '{}' }},\n", indent(3), i, clef.gjm_string());
+        file.write_all(line.as_bytes())?;
+    }
+    let line = format!("{}}},\n", indent(2));
+    file.write_all(line.as_bytes())?;
+    
+    // Instrument Map
+    let line = format!("{}MeasureInstrumentTypeMap = {{\n", indent(2));
+    file.write_all(line.as_bytes())?;
+    let line = format!("{}{{ 0, '{}' }},\n", indent(3), instrument);
+    file.write_all(line.as_bytes())?;
+    let line = format!("{}}},\n", indent(2));
+    file.write_all(line.as_bytes())?;
 
-        let mut last_volume = measure.attributes.volume;
-        volumes.push((0, last_volume));
+    // Hardcoded Maps
+        // Volume Curve
+    let line = format!("{}MeasureVolumeCurveMap = {{\n", indent(2));
+    file.write_all(line.as_bytes())?;
+    let line = format!("{}{{ 0, {{0.8, 0.7, 0.5, 0.5, 0.7, 0.6, 0.5, 0.4}} }},\n", indent(3));
+    file.write_all(line.as_bytes())?;
+    let line = format!("{}}},\n", indent(2));
+    file.write_all(line.as_bytes())?;
 
-        for (i, measure) in measures.iter().enumerate() {
-            if measure.attributes.key != last_key_sig {
-                last_key_sig = measure.attributes.key;
-                key_sigs.push((i, last_key_sig));
+    // Volume Map
+    let line = format!("{}MeasureVolumeMap = {{\n", indent(2));
+    file.write_all(line.as_bytes())?;
+    for (i, mut volume) in volumes {
+        if volume == 0 {
+            volume = 10
+        }
+        let line = format!("{}{{ {}, {:.2} }},\n", indent(3), i, volume as f64 / 100f64);
+        file.write_all(line.as_bytes())?;
+    }
+    let line = format!("{}}},\n", indent(2));
+    file.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+/// Merges the tempo changes of every part and staff into one map. Tempo directions are often
+/// repeated in every part at the same measure, or only given in a part or staff other than the
+/// first, so each measure takes the first tempo any staff declares in it and repeats of the
+/// current tempo are dropped.
+fn calc_tempo_map(parts: &[Part]) -> Vec<(usize, u32)> {
+    let mut tempos = Vec::<(usize, u32)>::new();
+    let measure_count = parts.first().map_or(0, |part| part.measures[0].len());
+
+    let mut last_tempo = 0;
+    for i in 0..measure_count {
+        let mut tempo = None;
+        for (part_idx, part) in parts.iter().enumerate() {
+            let declared = part.measures.iter()
+                .filter_map(|staff| staff.get(i))
+                .find(|measure| measure.tempo_declared);
+            let measure = match declared {
+                Some(measure) => measure,
+                None => continue,
+            };
+            match tempo {
+                None => tempo = Some(measure.attributes.tempo),
+                Some(first) if first != measure.attributes.tempo => {
+                    warning!("Part {} sets a tempo of {} in measure {} but an earlier part sets {}", part_idx, measure.attributes.tempo, i, first);
+                }
+                _ => {}
+            }
+        }
+        // The first measure always needs a tempo, so use the default if no part gives one
+        if i == 0 && tempo.is_none() {
+            tempo = Some(parts[0].measures[0][0].attributes.tempo);
+        }
+        if let Some(tempo) = tempo {
+            if tempo != last_tempo {
+                last_tempo = tempo;
+                tempos.push((i, last_tempo));
             }
-            if measure.attributes.clef != last_clef {
-                last_clef = measure.attributes.clef;
-                clefs.push((i, last_clef));
+        }
+    }
+
+    tempos
+}
+
+fn calc_meter_map(measures: &[Measure]) -> Vec<(usize, u8, u8)> {
+    let mut meters = Vec::<(usize, u8, u8)>::new();
+
+    let mut last_meter = (0, 0);
+    for (i, measure) in measures.iter().enumerate() {
+        let meter = (measure.attributes.beats, measure.attributes.beat_type);
+        if meter != last_meter {
+            last_meter = meter;
+            meters.push((i, meter.0, meter.1));
+        }
+    }
+
+    meters
+}
+
+/// Returns how many times longer than its note type a note with the given number of dots is,
+/// since each dot adds half the length the last one added
+///
+/// # Arguments
+///
+/// * 'dots' - The number of dots after the note
+///
+fn dot_factor(dots: u8) -> f64 {
+    2.0 - 0.5f64.powi(dots as i32)
+}
+
+/// Returns the order the measures are played in once their repeats are unfolded, as indexes
+/// into the measures. Each backward repeat jumps back to the last forward repeat, or the start of
+/// the score, until its section has been played as many times as it asks for, and endings are only
+/// played on the passes they are numbered for.
+///
+/// # Arguments
+///
+/// * 'measures' - The measures of the staff holding the repeat structure of the score
+///
+fn calc_repeat_order(measures: &[Measure]) -> Vec<usize> {
+    // Which passes each measure is played on when it is part of an ending
+    let mut endings = Vec::<Option<Vec<u32>>>::new();
+    let mut current = None;
+    for measure in measures {
+        if let Some(numbers) = &measure.repeats.ending_start {
+            current = Some(numbers.clone());
+        }
+        endings.push(current.clone());
+        if measure.repeats.ending_stop {
+            current = None;
+        }
+    }
+
+    let mut order = Vec::<usize>::new();
+    let mut section_start = 0;
+    let mut pass = 1;
+    let mut i = 0;
+    while i < measures.len() {
+        let repeats = &measures[i].repeats;
+        if repeats.start && i != section_start {
+            section_start = i;
+            pass = 1;
+        }
+        if let Some(numbers) = &endings[i] {
+            if !numbers.contains(&pass) {
+                i += 1;
+                continue;
             }
-            if measure.attributes.volume != last_volume {
-                last_volume = measure.attributes.volume;
-                volumes.push((i, last_volume));
+        }
+        order.push(i);
+        if repeats.end_times > 0 {
+            if pass < repeats.end_times {
+                pass += 1;
+                i = section_start;
+                continue;
             }
+            section_start = i + 1;
+            pass = 1;
+        } else if repeats.ending_stop && endings[i].is_some() {
+            // The last ending of a section finishes it without a backward repeat
+            section_start = i + 1;
+            pass = 1;
         }
+        i += 1;
     }
 
-    (key_sigs, clefs, volumes)
+    order
+}
+
+/// Parses a MusicXml document into a Score without rendering it, so the model can be inspected
+/// before (or instead of) being written out
+///
+/// # Arguments
+///
+/// * 'input'   - The source of the MusicXml document
+/// * 'options' - The conversion options in use
+///
+pub fn parse<R: Read>(input: R, options: &Options) -> Result<Score, Error> {
+    parse_document(input, options, false)
+}
+
+/// Returns whether a score should be written one part at a time with the given options. Writing a
+/// part as soon as it is parsed means only one part's notes are held at once, which bounds the
+/// memory a large score takes, but the written parts wait in a temporary file so it is only done
+/// when asked for. Anything that looks at the notes of every part together has to keep the whole
+/// score: reports, lyrics, lossless checks, and ordering tracks by name or pitch.
+fn can_stream(options: &Options) -> bool {
+    options.stream && !options.report && !options.lyrics && !options.lossless && options.track_order == TrackOrder::Parsed
+}
+
+/// Parses a MusicXml document into a score
+///
+/// # Arguments
+///
+/// * 'input'   - The source of the MusicXml document
+/// * 'options' - The conversion options in use
+/// * 'stream'  - Whether to write each part of a partwise score as soon as it is parsed, keeping
+///   only the measures' attributes, which leaves a score that can only be written
+///
+fn parse_document<R: Read>(input: R, options: &Options, stream: bool) -> Result<Score, Error> {
+    log::set_verbosity(options.verbosity);
+    log::set_part(None);
+    let mut parser = EventReader::new(input);
+    loop {
+        match parser.next() {
+            Ok(XmlEvent::StartElement {name, ..}) => {
+                match name.local_name.as_str() {
+                    "score-partwise" => return Score::parse_score(&mut parser, options, stream),
+                    "score-timewise" => return Score::parse_timewise_score(&mut parser, options),
+                    _ => {}
+                }
+            }
+            Ok(XmlEvent::EndDocument) => {
+                return Err(Error::MissingScore);
+            }
+            Err(e) => {
+                return Err(Error::Xml(e));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Returns the pitch index of a note name such as "C4", "F#3" or "Bb5"
+///
+/// # Arguments
+///
+/// * 'name' - The letter, an optional sharp or flat, and the octave of the note
+///
+pub fn pitch_index_from_name(name: &str) -> Option<u32> {
+    let step = name.get(..1)?.to_uppercase();
+    if !"ABCDEFG".contains(step.as_str()) {
+        return None;
+    }
+    let mut rest = &name[1..];
+    let mut alter = 0;
+    if let Some(stripped) = rest.strip_prefix('#') {
+        alter = 1;
+        rest = stripped;
+    } else if let Some(stripped) = rest.strip_prefix('b') {
+        alter = -1;
+        rest = stripped;
+    }
+    let octave = rest.parse::<u32>().ok()?;
+    let pitch = Note::convert_pitch_index(step.as_str(), octave) as i32 + alter;
+    if pitch < 0 {
+        return None;
+    }
+    Some(pitch as u32)
+}
+
+/// Converts a MusicXml file into a GJM file, along with a report next to it if one is requested
+///
+/// # Arguments
+///
+/// * 'input'   - The path of the MusicXml file to read
+/// * 'output'  - The path of the GJM file to write
+/// * 'options' - The conversion options in use
+///
+pub fn convert_file(input: &Path, output: &Path, options: &Options) -> Result<(), Error> {
+    let file = BufReader::new(File::open(input)?);
+    convert_reader_to_path(file, &input.display().to_string(), output, options)
+}
+
+/// Converts a plain or compressed MusicXml document into a GJM file, along with a report and the
+/// lyrics next to it if they are requested
+///
+/// # Arguments
+///
+/// * 'input'   - The source of the MusicXml document
+/// * 'source'  - A name for the source to use in the report
+/// * 'output'  - The path of the GJM file to write, or "-" for stdout
+/// * 'options' - The conversion options in use
+///
+pub fn convert_reader_to_path<R: BufRead>(input: R, source: &str, output: &Path, options: &Options) -> Result<(), Error> {
+    // Warnings are recorded from the start so the report holds every one of them
+    log::record(options.report);
+    let score = parse_for_writing(mxl::open(input, options.input_format)?, options)?;
+    score.check_lossless(options)?;
+
+    if output == Path::new("-") {
+        let mut stdout = BufWriter::new(std::io::stdout().lock());
+        score.write_gjm(&mut stdout, options)?;
+        stdout.flush()?;
+        // There's no file for the report or lyrics to go next to
+        if options.report {
+            warning!("Writing to stdout, so no report is written");
+        }
+        if options.lyrics {
+            warning!("Writing to stdout, so no lyrics are written");
+        }
+        return Ok(());
+    }
+
+    let mut outfile = BufWriter::new(File::create(output)?);
+    score.write_gjm(&mut outfile, options)?;
+    finish_file(outfile, options)?;
+
+    // Conversion report
+    if options.report {
+        let mut report_file = BufWriter::new(File::create(output.with_extension("json"))?);
+        score.build_report(source, options, log::take_recorded()).write_json(&mut report_file)?;
+        finish_file(report_file, options)?;
+    }
+
+    if options.lyrics {
+        let mut lyrics_file = BufWriter::new(File::create(output.with_extension("txt"))?);
+        if options.crlf {
+            score.write_lyrics(&mut CrlfWriter {inner: &mut lyrics_file}, options)?;
+        } else {
+            score.write_lyrics(&mut lyrics_file, options)?;
+        }
+        finish_file(lyrics_file, options)?;
+    }
+    Ok(())
+}
+
+/// Checks a MusicXml file for everything GJM can't represent, without writing anything
+///
+/// # Arguments
+///
+/// * 'input'   - The path of the MusicXml file to read
+/// * 'options' - The conversion options in use
+///
+pub fn check_file(input: &Path, options: &Options) -> Result<Vec<Issue>, Error> {
+    let file = BufReader::new(File::open(input)?);
+    check_reader(file, options)
+}
+
+/// Checks a plain or compressed MusicXml document for everything GJM can't represent, without
+/// writing anything
+///
+/// # Arguments
+///
+/// * 'input'   - The source of the MusicXml document
+/// * 'options' - The conversion options in use
+///
+pub fn check_reader<R: BufRead>(input: R, options: &Options) -> Result<Vec<Issue>, Error> {
+    let score = parse(mxl::open(input, options.input_format)?, options)?;
+    Ok(score.validate(options))
+}
+
+/// Parses a MusicXml document into a score that is only going to be written, which lets large
+/// scores be written one part at a time when the options ask for it
+///
+/// # Arguments
+///
+/// * 'input'   - The source of the MusicXml document
+/// * 'options' - The conversion options in use
+///
+pub fn parse_for_writing<R: Read>(input: R, options: &Options) -> Result<Score, Error> {
+    parse_document(input, options, can_stream(options))
+}
+
+/// Flushes a finished output file so write errors are reported instead of lost when it is dropped,
+/// and when asked to, waits for the file to reach the disk so a crash can't leave it half written
+///
+/// # Arguments
+///
+/// * 'file'    - The buffered output file
+/// * 'options' - The conversion options in use
+///
+fn finish_file(file: BufWriter<File>, options: &Options) -> std::io::Result<()> {
+    let file = file.into_inner().map_err(|e| e.into_error())?;
+    if options.sync {
+        file.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Keeps only the attributes that belong to MusicXml itself. MusicXml attributes never have a
+/// namespace, so attributes that other tools add in their own namespace, such as "x:type", can't be
+/// mistaken for the MusicXml attribute with the same local name.
+fn musicxml_attributes(attributes: Vec<OwnedAttribute>) -> Vec<OwnedAttribute> {
+    attributes.into_iter().filter(|attr| attr.name.namespace.is_none()).collect()
 }
 
 /// Parses the internal value of a tag. This function expects that the provided parser is already
@@ -57,1129 +465,4617 @@ fn calc_measure_maps(measures: &Vec<Measure>) -> (Vec<(usize, i32)>, Vec<(usize,
 /// * 'label' - A string slice holding the label of the tag to parse
 /// * 'parser' - A mutable reference to the parser located inside the referenced tag
 ///
-fn parse_tag_value(label: &str, parser: &mut EventReader<BufReader<File>>) -> String {
+fn parse_tag_value<R: Read>(label: &str, parser: &mut EventReader<R>) -> Result<String, Error> {
     let mut value: String = "".to_string();
-    match parser.next(){
-        Ok(XmlEvent::Characters(chars)) => {
-            value = chars;
-        }
-        _ => {println!("Warning! Non-Characters Element inside <{}>", label);}
-    }
     loop {
         match parser.next(){
+            Ok(XmlEvent::Characters(chars)) => {
+                value.push_str(&chars);
+            }
+            Ok(XmlEvent::StartElement{name, ..}) => {
+                return Err(Error::UnexpectedElement { tag: name.local_name, parent: label.to_string(), measure: None });
+            }
             Ok(XmlEvent::EndElement{name}) => {
                 if name.local_name.as_str() == label {
                     break;
                 }
             }
-            _ => {println!("Warning! Extra Elements inside <{}>", label);}
+            Ok(XmlEvent::EndDocument) => {
+                break;
+            }
+            Err(e) => {
+                return Err(Error::Xml(e));
+            }
+            _ => {}
         }
     }
-    value
+    Ok(value)
 }
 
-/// An enum to hold the duration value of a single note
-#[derive(Clone, Copy, Debug)]
-enum NoteType {
-    TenTwentyFourth,
-    FiveTwelfth,
-    TwoFiftySixth,
-    OneTwentyEighth,
-    SixtyFourth,
-    // Nothing shorter than ThirtySecond is supported by GJM
-    ThirtySecond,
-    Sixteenth,
-    Eighth,
-    Quarter,
-    Half,
-    Whole,
-    // Nothing longer than Whole is supported by GJM
-    Breve,
-    Long,
-    Maxima,
+/// Returns whether the attributes of a measure mark it as implicit, meaning it doesn't count
+/// towards the meter, as is the case for pickups
+///
+/// # Arguments
+///
+/// * 'attributes' - The attributes of the "measure" tag
+///
+fn is_implicit(attributes: &[OwnedAttribute]) -> bool {
+    attributes.iter().any(|attr| attr.name.local_name.as_str() == "implicit" && attr.value.trim() == "yes")
 }
 
-/// A Representation of a single note
-#[derive(Clone, Debug)]
-struct Note {
-    /// The numeric note value with index zero being A1 and increasing by one each half step
-    pitch_index: u32,
-    /// Note alteration in half steps, i.e. a flat note has alter = -1
-    alter: i32,
-    /// Duration of the note in divisions
-    duration: u32,
-    /// Note duration type as an enum
-    note_type: NoteType,
-    /// In multi-staff parts staff is used to track which staff each note sits on
-    staff: u8,
-    /// Whether the note is a rest or not
-    is_rest: bool,
-    /// Whether the note is dotted
-    dotted: bool,
-    /// Whether the note is arpeggiated
-    arpeggiate: bool,
-    /// Whether the note is the start of a triplet
-    triplet: bool,
-    /// Whether a slur/tie starts on this note
-    slur_start: bool,
-    /// Whether a slur/tie stops on this note
-    slur_stop: bool,
+/// Returns the least common multiple of two numbers
+fn lcm(a: u32, b: u32) -> u32 {
+    let (mut x, mut y) = (a, b);
+    while y != 0 {
+        let rest = x % y;
+        x = y;
+        y = rest;
+    }
+    a / x * b
 }
 
-impl Note {
-    /// Returns a default instantiation of a Note
-    fn new() -> Self {
-        Note {
-            pitch_index: 0,
-            alter: 0,
-            duration: 0,
-            note_type: NoteType::Quarter,
-            staff: 1,
-            is_rest: false,
-            dotted: false,
-            arpeggiate: false,
-            triplet: false,
-            slur_start: false,
-            slur_stop: false,
-        }
-    }
+/// Escapes a value for use inside a single quoted GJM string, putting line breaks on one line
+///
+/// # Arguments
+///
+/// * 'value' - The text to escape
+///
+fn quote_gjm_string(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<&str>>().join(" ")
+        .replace('\\', "\\\\")
+        .replace('\'', "\\'")
+}
 
-    /// Converts from MusicXml "step" and "octave" into a pitch index
-    fn convert_pitch_index(step: &str, octave: u32) -> u32 {
-        // Each octave has 12 pitch indexes and octave starts at one, not zero.
-        let mut pitch_index;
-        if octave == 0 {
-            pitch_index = octave * 12;
-        } else {
-            pitch_index = (octave - 1) * 12;
-        }
-        // The note index is how many half steps from A flat the note is.
-        match step {
-            "A" => {
-                pitch_index += 13;
-            }
-            "B" => {
-                pitch_index += 15;
-            }
-            "C" => {
-                pitch_index += 4;
-            }
-            "D" => {
-                pitch_index += 6;
-            }
-            "E" => {
-                pitch_index += 8;
-            }
-            "F" => {
-                pitch_index += 9;
-            }
-            "G" => {
-                pitch_index += 11;
+/// A writer that writes every "\n" it is given as "\r\n", for readers that expect Windows line
+/// endings
+struct CrlfWriter<W: OtherWrite> {
+    inner: W,
+}
+
+impl<W: OtherWrite> OtherWrite for CrlfWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for (i, line) in buf.split(|byte| *byte == b'\n').enumerate() {
+            if i > 0 {
+                self.inner.write_all(b"\r\n")?;
             }
-            _ => {}
+            self.inner.write_all(line)?;
         }
-        pitch_index
+        Ok(buf.len())
     }
 
-    /// Parses the tags and values within a "note" tag, returning the constructed Note and whether
-    /// it is part of a previously started chord
-    ///
-    /// # Arguments
-    ///
-    /// * 'parser' - A mutable reference to the parser located inside the "note" tag
-    ///
-    /// Returns a Tuple of the (Note, is_a_chord)
-    ///
-    fn parse_note(parser: &mut EventReader<BufReader<File>>) -> (Self, bool) {
-        let mut note = Note::new();
-        let mut is_chord = false;
-        loop {
-            match parser.next() {
-                Ok(XmlEvent::StartElement {name, ..}) => {
-                    match name.local_name.as_str() {
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Parses the value of a tag as a number. This function expects that the provided parser is
+/// already inside the tag specified by label and will only return once it has parsed the closing
+/// tag with that same label.
+///
+/// # Arguments
+///
+/// * 'label'  - The name of the tag holding the number
+/// * 'parser' - A mutable reference to the parser located inside the tag
+///
+fn parse_tag_number<T: FromStr, R: Read>(label: &str, parser: &mut EventReader<R>) -> Result<T, Error> {
+    let value = parse_tag_value(label, parser)?;
+    parse_number(label, &value)
+}
+
+/// Parses the value of a tag as a count that has to be above zero, such as the divisions or the
+/// beats of a meter, which the rest of the conversion divides by. A zero is an invalid value.
+///
+/// # Arguments
+///
+/// * 'label'  - The name of the tag holding the count
+/// * 'parser' - A mutable reference to the parser located inside the tag
+///
+fn parse_tag_count<T: FromStr + Default + PartialEq, R: Read>(label: &str, parser: &mut EventReader<R>) -> Result<T, Error> {
+    let value = parse_tag_value(label, parser)?;
+    let count = parse_number(label, &value)?;
+    if count == T::default() {
+        return Err(Error::InvalidValue { tag: label.to_string(), value, measure: None });
+    }
+    Ok(count)
+}
+
+/// Parses a number found in a tag or attribute, returning an error naming the tag if it isn't one
+///
+/// # Arguments
+///
+/// * 'tag'   - The name of the tag or attribute the value came from
+/// * 'value' - The text to parse
+///
+fn parse_number<T: FromStr>(tag: &str, value: &str) -> Result<T, Error> {
+    value.trim().parse::<T>().map_err(|_| Error::InvalidValue { tag: tag.to_string(), value: value.to_string(), measure: None })
+}
+
+/// The volume out of 100 that each dynamic mark is played at, used when a direction gives no
+/// playback dynamics of its own. Accents such as "sfz" don't change the volume that follows.
+const DYNAMIC_MARKS: [(&str, u32); 14] = [
+    ("pppppp", 5),
+    ("ppppp", 8),
+    ("pppp", 10),
+    ("ppp", 20),
+    ("pp", 30),
+    ("p", 40),
+    ("mp", 55),
+    ("mf", 65),
+    ("f", 80),
+    ("ff", 90),
+    ("fff", 95),
+    ("ffff", 100),
+    ("fffff", 100),
+    ("ffffff", 100),
+];
+
+/// Parses a "dynamics" mark into the volume it is played at, if it is one with a set volume.
+/// This function expects that the provided parser is already inside the "dynamics" tag and will
+/// only return once it has parsed the closing tag.
+///
+/// # Arguments
+///
+/// * 'parser' - A mutable reference to the parser located inside the "dynamics" tag
+///
+fn parse_dynamics<R: Read>(parser: &mut EventReader<R>) -> Result<Option<u32>, Error> {
+    let mut volume = None;
+    loop {
+        match parser.next() {
+            Ok(XmlEvent::StartElement {name, ..}) => {
+                if let Some((_, mark_volume)) = DYNAMIC_MARKS.iter().find(|(mark, _)| *mark == name.local_name) {
+                    volume = Some(*mark_volume);
+                }
+            }
+            Ok(XmlEvent::EndElement {name}) => {
+                if name.local_name.as_str() == "dynamics" {
+                    break;
+                }
+            }
+            Ok(XmlEvent::EndDocument) => {
+                break;
+            }
+            Err(e) => {
+                return Err(Error::Xml(e));
+            }
+            _ => {}
+        }
+    }
+    Ok(volume)
+}
+
+/// Parses a "metronome" mark into a tempo in quarter notes per minute. Marks that don't give a
+/// number of beats per minute, such as metric modulations, have no tempo. This function expects
+/// that the provided parser is already inside the "metronome" tag and will only return once it has
+/// parsed the closing tag.
+///
+/// # Arguments
+///
+/// * 'parser' - A mutable reference to the parser located inside the "metronome" tag
+///
+fn parse_metronome<R: Read>(parser: &mut EventReader<R>) -> Result<Option<f64>, Error> {
+    let mut beat_units = Vec::<NoteType>::new();
+    let mut dots = 0;
+    let mut per_minute = None;
+    loop {
+        match parser.next() {
+            Ok(XmlEvent::StartElement {name, ..}) => {
+                match name.local_name.as_str() {
+                    "beat-unit" => {
+                        let token = parse_tag_value("beat-unit", parser)?;
+                        match NoteType::from_musicxml(&token) {
+                            Some(note_type) => beat_units.push(note_type),
+                            None => {warning!("Unknown metronome beat unit '{}'", token);}
+                        }
+                    }
+                    "beat-unit-dot" => {
+                        dots += 1;
+                    }
+                    "per-minute" => {
+                        // Marks such as "c. 120" or "120-132" are read as their first number
+                        let value = parse_tag_value("per-minute", parser)?;
+                        let number: String = value.trim_start_matches(|c: char| !c.is_ascii_digit())
+                            .chars()
+                            .take_while(|c| c.is_ascii_digit() || *c == '.')
+                            .collect();
+                        per_minute = number.parse::<f64>().ok();
+                        if per_minute.is_none() {
+                            warning!("Could not read a tempo from metronome mark '{}'", value);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Ok(XmlEvent::EndElement {name}) => {
+                if name.local_name.as_str() == "metronome" {
+                    break;
+                }
+            }
+            Ok(XmlEvent::EndDocument) => {
+                return Err(Error::MissingScore);
+            }
+            Err(e) => {
+                return Err(Error::Xml(e));
+            }
+            _ => {}
+        }
+    }
+    match (beat_units.as_slice(), per_minute) {
+        ([beat_unit], Some(per_minute)) => {
+            // Each dot adds half of the length added by the one before it
+            let mut length = beat_unit.quarter_length();
+            let mut dot_length = length;
+            for _ in 0..dots {
+                dot_length /= 2.0;
+                length += dot_length;
+            }
+            Ok(Some(per_minute * length))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Skips over the rest of a tag, including everything inside of it. This function expects that the
+/// provided parser is already inside the tag specified by label and will only return once it has
+/// parsed the closing tag with that same label.
+///
+/// # Arguments
+///
+/// * 'label' - A string slice holding the label of the tag to skip
+/// * 'parser' - A mutable reference to the parser located inside the referenced tag
+///
+fn skip_tag<R: Read>(label: &str, parser: &mut EventReader<R>) -> Result<(), Error> {
+    loop {
+        match parser.next() {
+            Ok(XmlEvent::EndElement {name}) => {
+                if name.local_name.as_str() == label {
+                    return Ok(());
+                }
+            }
+            Ok(XmlEvent::EndDocument) => {
+                return Err(Error::MissingScore);
+            }
+            Err(e) => {
+                return Err(Error::Xml(e));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Moves chords slightly off the grid so playback sounds less mechanical. Offsets come from a small
+/// seeded xorshift generator so the same seed always gives the same output. GJM has no velocity
+/// for each note, only a volume for each measure, so timing is all that can be humanized.
+#[derive(Debug)]
+struct Humanizer {
+    state: u64,
+    /// The largest number of stamps a chord may be moved in either direction
+    amount: u32,
+}
+
+impl Humanizer {
+    /// Returns a Humanizer that moves chords by up to amount stamps
+    ///
+    /// # Arguments
+    ///
+    /// * 'seed'   - The seed of the generator
+    /// * 'amount' - The largest number of stamps a chord may be moved, zero turns humanizing off
+    ///
+    fn new(seed: u64, amount: u32) -> Self {
+        // Xorshift gets stuck on zero, so mix the seed with a constant first
+        let mut state = seed ^ 0x9E37_79B9_7F4A_7C15;
+        if state == 0 {
+            state = 1;
+        }
+        Self {state, amount}
+    }
+
+    /// Returns the next offset between -amount and amount
+    fn next_offset(&mut self) -> i64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state % (2 * self.amount as u64 + 1)) as i64 - self.amount as i64
+    }
+
+    /// Offsets the stamps of a measure without reordering chords or moving any to end past the
+    /// end of the measure
+    ///
+    /// # Arguments
+    ///
+    /// * 'stamps'       - The stamp index of each chord in order
+    /// * 'lengths'      - The number of stamps each chord lasts
+    /// * 'duration_max' - The DurationStampMax of the measure
+    ///
+    fn humanize(&mut self, stamps: &mut [u32], lengths: &[u32], duration_max: u32) {
+        if self.amount == 0 {
+            return;
+        }
+        for j in 0..stamps.len() {
+            let offset = self.next_offset();
+            // Stay after the chord before and before the chord after, and keep the chord ending
+            // inside the measure unless it already ended past it
+            let low = if j > 0 { stamps[j - 1] as i64 + 1 } else { 0 };
+            let end = duration_max as i64 + 1 - lengths[j] as i64;
+            let mut high = (duration_max as i64).min(end.max(stamps[j] as i64));
+            if j + 1 < stamps.len() {
+                high = high.min(stamps[j + 1] as i64 - 1);
+            }
+            let stamp = stamps[j] as i64 + offset;
+            if low <= high {
+                stamps[j] = stamp.max(low).min(high) as u32;
+            }
+        }
+    }
+}
+
+/// An enum to hold the duration value of a single note
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum NoteType {
+    TenTwentyFourth,
+    FiveTwelfth,
+    TwoFiftySixth,
+    OneTwentyEighth,
+    // Nothing shorter than SixtyFourth, a single stamp, is supported by GJM
+    SixtyFourth,
+    ThirtySecond,
+    Sixteenth,
+    Eighth,
+    Quarter,
+    Half,
+    Whole,
+    // Nothing longer than Whole is supported by GJM
+    Breve,
+    Long,
+    Maxima,
+}
+
+impl NoteType {
+    /// Every note type along with its MusicXml "type" token, from shortest to longest. This is the
+    /// only place the tokens are spelled out.
+    const MUSICXML_TOKENS: [(NoteType, &'static str); 14] = [
+        (NoteType::TenTwentyFourth, "1024th"),
+        (NoteType::FiveTwelfth, "512th"),
+        (NoteType::TwoFiftySixth, "256th"),
+        (NoteType::OneTwentyEighth, "128th"),
+        (NoteType::SixtyFourth, "64th"),
+        (NoteType::ThirtySecond, "32nd"),
+        (NoteType::Sixteenth, "16th"),
+        (NoteType::Eighth, "eighth"),
+        (NoteType::Quarter, "quarter"),
+        (NoteType::Half, "half"),
+        (NoteType::Whole, "whole"),
+        (NoteType::Breve, "breve"),
+        (NoteType::Long, "long"),
+        (NoteType::Maxima, "maxima"),
+    ];
+
+    /// Converts a MusicXml "type" token into a note type
+    fn from_musicxml(token: &str) -> Option<NoteType> {
+        for (note_type, musicxml_token) in NoteType::MUSICXML_TOKENS.iter() {
+            if *musicxml_token == token.trim() {
+                return Some(*note_type);
+            }
+        }
+        None
+    }
+
+    /// Converts a GJM "DurationType" into the note type it names
+    fn from_gjm_name(name: &str) -> Option<NoteType> {
+        match name {
+            "The64th" => Some(NoteType::SixtyFourth),
+            "The32nd" => Some(NoteType::ThirtySecond),
+            "The16th" => Some(NoteType::Sixteenth),
+            "Eighth" => Some(NoteType::Eighth),
+            "Quarter" => Some(NoteType::Quarter),
+            "Half" => Some(NoteType::Half),
+            "Whole" => Some(NoteType::Whole),
+            _ => None,
+        }
+    }
+
+    /// Returns the length of this note type in quarter notes
+    fn quarter_length(&self) -> f64 {
+        match self {
+            NoteType::TenTwentyFourth => 1.0 / 256.0,
+            NoteType::FiveTwelfth => 1.0 / 128.0,
+            NoteType::TwoFiftySixth => 1.0 / 64.0,
+            NoteType::OneTwentyEighth => 1.0 / 32.0,
+            NoteType::SixtyFourth => 1.0 / 16.0,
+            NoteType::ThirtySecond => 1.0 / 8.0,
+            NoteType::Sixteenth => 1.0 / 4.0,
+            NoteType::Eighth => 1.0 / 2.0,
+            NoteType::Quarter => 1.0,
+            NoteType::Half => 2.0,
+            NoteType::Whole => 4.0,
+            NoteType::Breve => 8.0,
+            NoteType::Long => 16.0,
+            NoteType::Maxima => 32.0,
+        }
+    }
+
+    /// Finds the note type, and how many dots it has, that lasts the given number of quarter notes
+    fn from_quarter_length(length: f64) -> Option<(NoteType, u8)> {
+        for (note_type, _) in NoteType::MUSICXML_TOKENS.iter() {
+            let base = note_type.quarter_length();
+            // Durations are whole divisions, so allow a little slack for uneven tuplets
+            for dots in 0..3 {
+                if (length - base * dot_factor(dots)).abs() < base / 100.0 {
+                    return Some((*note_type, dots));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// An ornament marked on a note, such as a trill or turn
+#[derive(Clone, Debug)]
+struct Ornament {
+    /// The name of the ornament tag, i.e. "trill-mark" or "inverted-turn"
+    kind: String,
+    /// The alteration in half steps of the ornament's auxiliary note, from its "accidental-mark"
+    accidental: Option<i32>,
+}
+
+impl Ornament {
+    /// Converts a MusicXml "accidental-mark" value into an alteration in half steps
+    fn convert_accidental_mark(value: &str) -> Option<i32> {
+        match value {
+            "sharp" => Some(1),
+            "natural" => Some(0),
+            "flat" => Some(-1),
+            "double-sharp" | "sharp-sharp" => Some(2),
+            "flat-flat" => Some(-2),
+            _ => None,
+        }
+    }
+}
+
+/// One syllable of the words sung on a note
+#[derive(Clone, Debug)]
+struct Lyric {
+    /// The verse the syllable belongs to, as given by the lyric's "number"
+    verse: String,
+    /// The text of the syllable, with elided syllables joined by a space
+    text: String,
+    /// Whether the word goes on into the next syllable, from a "begin" or "middle" syllabic
+    hyphen: bool,
+}
+
+impl Lyric {
+    /// Parses a MusicXml "lyric" tag, returning None for lyrics without text such as humming or
+    /// laughing
+    ///
+    /// # Arguments
+    ///
+    /// * 'parser'     - A mutable reference to the parser located inside the "lyric" tag
+    /// * 'attributes' - The attributes of the "lyric" tag
+    ///
+    fn parse_lyric<R: Read>(parser: &mut EventReader<R>, attributes: &[OwnedAttribute]) -> Result<Option<Self>, Error> {
+        let verse = attributes.iter()
+            .find(|attr| attr.name.local_name.as_str() == "number")
+            .map_or("1".to_string(), |attr| attr.value.trim().to_string());
+        let mut texts = Vec::<String>::new();
+        let mut hyphen = false;
+        loop {
+            match parser.next() {
+                Ok(XmlEvent::StartElement {name, ..}) => {
+                    match name.local_name.as_str() {
+                        "text" => {
+                            texts.push(parse_tag_value("text", parser)?.trim().to_string());
+                        }
+                        "syllabic" => {
+                            // With elisions the last syllabic says how the lyric ends
+                            hyphen = matches!(parse_tag_value("syllabic", parser)?.trim(), "begin" | "middle");
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(XmlEvent::EndElement {name}) if name.local_name.as_str() == "lyric" => {
+                    break;
+                }
+                Err(e) => {
+                    return Err(Error::Xml(e));
+                }
+                _ => {}
+            }
+        }
+        texts.retain(|text| !text.is_empty());
+        if texts.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Self {verse, text: texts.join(" "), hyphen}))
+    }
+}
+
+/// Fingering and tablature markings of a note
+#[derive(Clone, Debug, Default)]
+struct Technical {
+    /// Which finger plays the note, kept as text since it can be a range like "1-3"
+    fingering: Option<String>,
+    /// Which string of a stringed instrument plays the note, starting at one
+    string: Option<u32>,
+    /// Which fret the string is stopped at, zero being an open string
+    fret: Option<u32>,
+}
+
+impl Technical {
+    /// Returns whether any marking is present
+    fn is_empty(&self) -> bool {
+        self.fingering.is_none() && self.string.is_none() && self.fret.is_none()
+    }
+
+    /// Describes the markings that are present, i.e. "string 2, fret 5"
+    fn describe(&self) -> String {
+        let mut markings = Vec::<String>::new();
+        if let Some(fingering) = &self.fingering {
+            markings.push(format!("fingering {}", fingering));
+        }
+        if let Some(string) = self.string {
+            markings.push(format!("string {}", string));
+        }
+        if let Some(fret) = self.fret {
+            markings.push(format!("fret {}", fret));
+        }
+        markings.join(", ")
+    }
+}
+
+/// A Representation of a single note
+#[derive(Clone, Debug)]
+struct Note {
+    /// The piano key number of the note, with A0 as 1 and middle C (C4) as 40, increasing by one
+    /// each half step
+    pitch_index: u32,
+    /// Note alteration in half steps, i.e. a flat note has alter = -1
+    alter: i32,
+    /// Duration of the note in divisions
+    duration: u32,
+    /// Note duration type as an enum
+    note_type: NoteType,
+    /// In multi-staff parts staff is used to track which staff each note sits on
+    staff: u8,
+    /// The voice the note belongs to within its staff
+    voice: u32,
+    /// Whether the note is a rest or not
+    is_rest: bool,
+    /// Whether the rest fills the whole measure, whatever its type says
+    measure_rest: bool,
+    /// The number of dots after the note
+    dots: u8,
+    /// Whether the note is arpeggiated
+    arpeggiate: bool,
+    /// Whether the arpeggio is rolled from the top note down instead of the bottom note up
+    arpeggio_down: bool,
+    /// Whether the note is held under a fermata
+    fermata: bool,
+    /// Whether the note is the start of a triplet
+    triplet: bool,
+    /// Whether a tie to the next note of the same pitch starts on this note
+    tie_start: bool,
+    /// Whether a tie from the previous note of the same pitch stops on this note
+    tie_stop: bool,
+    /// Whether a slur phrasing this note with the following notes starts on it
+    slur_start: bool,
+    /// Whether a slur phrasing this note with the previous notes stops on it
+    slur_stop: bool,
+    /// Whether a primary beam begins on this note
+    beam_start: bool,
+    /// Whether a primary beam continues through or ends on this note
+    beam_continue: bool,
+    /// Whether a primary beam ends on this note
+    beam_stop: bool,
+    /// The number of notes played in the time of tuplet_normal notes, one if not in a tuplet
+    tuplet_actual: u32,
+    /// The number of notes normally played in the time of the tuplet, one if not in a tuplet
+    tuplet_normal: u32,
+    /// The ornaments marked on the note
+    ornaments: Vec<Ornament>,
+    /// The fingering and tablature markings of the note
+    technical: Technical,
+    /// The syllables sung on the note, one for each verse
+    lyrics: Vec<Lyric>,
+    /// Whether the note is unpitched percussion, in which case pitch_index is its staff position
+    unpitched: bool,
+    /// Whether an unpitched note gives its staff position, without which it is placed by its
+    /// instrument instead
+    displayed: bool,
+    /// The id of the instrument of the part that plays the note, if the part has several
+    instrument: Option<String>,
+    /// The letter name of the note as written, empty for rests
+    step: String,
+    /// Whether a glissando or slide starts on this note
+    glissando_start: bool,
+    /// Whether a glissando or slide stops on this note
+    glissando_stop: bool,
+    /// Whether the note is a grace note, which takes no time of its own
+    grace: bool,
+    /// Whether the grace note is slashed, making it a quick acciaccatura rather than an appoggiatura
+    grace_slash: bool,
+    /// Whether the note is muted by a playback hint, so it keeps its time but isn't played
+    muted: bool,
+    /// Whether the note is a cue note, a small preview of another part that isn't played
+    cue: bool,
+    /// The grace notes played just before this note, grouped into the chords they are struck in
+    grace_chords: Vec<Vec<Note>>,
+}
+
+impl Note {
+    /// Returns a default instantiation of a Note
+    fn new() -> Self {
+        Note {
+            pitch_index: 0,
+            alter: 0,
+            duration: 0,
+            note_type: NoteType::Quarter,
+            staff: 1,
+            voice: 1,
+            is_rest: false,
+            measure_rest: false,
+            dots: 0,
+            arpeggiate: false,
+            arpeggio_down: false,
+            fermata: false,
+            triplet: false,
+            tie_start: false,
+            tie_stop: false,
+            slur_start: false,
+            slur_stop: false,
+            beam_start: false,
+            beam_continue: false,
+            beam_stop: false,
+            tuplet_actual: 1,
+            tuplet_normal: 1,
+            ornaments: Vec::<Ornament>::new(),
+            technical: Technical::default(),
+            lyrics: Vec::<Lyric>::new(),
+            unpitched: false,
+            displayed: false,
+            instrument: None,
+            step: "".to_string(),
+            glissando_start: false,
+            glissando_stop: false,
+            grace: false,
+            grace_slash: false,
+            muted: false,
+            cue: false,
+            grace_chords: Vec::<Vec<Note>>::new(),
+        }
+    }
+
+    /// Returns the duration in divisions that the note type (and dots) would normally take up
+    fn get_nominal_duration(&self, divisions: u32) -> f64 {
+        self.note_type.quarter_length() * dot_factor(self.dots) * divisions as f64
+    }
+
+    /// Converts from MusicXml "step" and "octave" into a pitch index
+    fn convert_pitch_index(step: &str, octave: u32) -> u32 {
+        // Pitch indexes are piano key numbers, so A0 is 1, middle C (C4) is 40 and C8 is 88. Each
+        // octave has 12 pitch indexes and starts on C, and the first 12 are subtracted at the end.
+        let mut pitch_index = octave * 12;
+        // The note index is how many half steps from A flat the note is.
+        match step {
+            "A" => {
+                pitch_index += 13;
+            }
+            "B" => {
+                pitch_index += 15;
+            }
+            "C" => {
+                pitch_index += 4;
+            }
+            "D" => {
+                pitch_index += 6;
+            }
+            "E" => {
+                pitch_index += 8;
+            }
+            "F" => {
+                pitch_index += 9;
+            }
+            "G" => {
+                pitch_index += 11;
+            }
+            _ => {}
+        }
+        // Only the A and B of octave 0 are on a piano, so anything lower is moved up an octave
+        if pitch_index < 12 {
+            warning!("{}{} is below the lowest pitch GJM supports, moving it up an octave", step, octave);
+            pitch_index += 12;
+        }
+        pitch_index - 12
+    }
+
+    /// Parses the tags and values within a "note" tag, returning the constructed Note and whether
+    /// it is part of a previously started chord
+    ///
+    /// # Arguments
+    ///
+    /// * 'parser' - A mutable reference to the parser located inside the "note" tag
+    /// * 'divisions' - The number of divisions per beat, used when the note has no "type" tag
+    /// * 'options' - The conversion options in use
+    ///
+    /// Returns a Tuple of the (Note, is_a_chord)
+    ///
+    fn parse_note<R: Read>(parser: &mut EventReader<R>, divisions: u32, options: &Options) -> Result<(Self, bool), Error> {
+        let mut note = Note::new();
+        let mut is_chord = false;
+        let mut has_type = false;
+        // Whether the note is printed at cue size, as editorial notes are
+        let mut cue_size = false;
+        loop {
+            match parser.next() {
+                Ok(XmlEvent::StartElement {name, attributes, ..}) => {
+                    let attributes = musicxml_attributes(attributes);
+                    match name.local_name.as_str() {
                         "pitch" => {
                             let mut step = "".to_string();
                             let mut octave: u32 = 0;
                             loop {
                                 match parser.next() {
-                                    Ok(XmlEvent::StartElement {name, ..}) => {
+                                    Ok(XmlEvent::StartElement {name, ..}) => {
+                                        match name.local_name.as_str() {
+                                            "step" => {
+                                                step = parse_tag_value("step", parser)?;
+                                            }
+                                            "octave" => {
+                                                octave = parse_tag_number("octave", parser)?;
+                                            }
+                                            "alter" => {
+                                                note.alter = parse_tag_number("alter", parser)?;
+                                            }
+                                            _ => {}
+                                        }
+                                    }
+                                    Ok(XmlEvent::EndElement {name}) => {
+                                        if name.local_name.as_str() == "pitch" {
+                                            note.pitch_index = Note::convert_pitch_index(step.as_str(), octave);
+                                            note.step = step.clone();
+                                            // GJM only has equal tempered pitches, so round the
+                                            // tuning offset to the nearest half step
+                                            let offset = (options.tuning_offset as f64 / 100.0).round() as i32;
+                                            if offset < 0 && (-offset) as u32 > note.pitch_index {
+                                                warning!("Tuning offset moves a note below the lowest pitch");
+                                                note.pitch_index = 0;
+                                            } else {
+                                                note.pitch_index = (note.pitch_index as i32 + offset) as u32;
+                                            }
+                                            break;
+                                        }
+                                    }
+                                    Err(e) => {
+                                        return Err(Error::Xml(e));
+                                    }
+                                    _ => {}
+                                }
+                            }
+                        }
+                        "unpitched" => {
+                            // Unpitched notes are placed on the staff by display step and octave,
+                            // which stands in for the drum lane they are played on
+                            let mut step = "".to_string();
+                            let mut octave: Option<u32> = None;
+                            loop {
+                                match parser.next() {
+                                    Ok(XmlEvent::StartElement {name, ..}) => {
+                                        match name.local_name.as_str() {
+                                            "display-step" => {
+                                                step = parse_tag_value("display-step", parser)?;
+                                            }
+                                            "display-octave" => {
+                                                octave = parse_tag_value("display-octave", parser)?.parse::<u32>().ok();
+                                            }
+                                            _ => {}
+                                        }
+                                    }
+                                    Ok(XmlEvent::EndElement {name}) => {
+                                        if name.local_name.as_str() == "unpitched" {
+                                            break;
+                                        }
+                                    }
+                                    Err(e) => {
+                                        return Err(Error::Xml(e));
+                                    }
+                                    _ => {}
+                                }
+                            }
+                            note.unpitched = true;
+                            match octave {
+                                Some(octave) if !step.is_empty() => {
+                                    note.pitch_index = Note::convert_pitch_index(step.as_str(), octave);
+                                    note.displayed = true;
+                                }
+                                _ => {
+                                    // Without a position the note is placed once the part's
+                                    // instruments are known, or on the middle line of the staff
+                                    note.pitch_index = Note::convert_pitch_index("B", 4);
+                                }
+                            }
+                        }
+                        "chord" => {
+                            is_chord = true;
+                        }
+                        "instrument" => {
+                            note.instrument = attributes.iter()
+                                .find(|attr| attr.name.local_name.as_str() == "id")
+                                .map(|attr| attr.value.clone());
+                        }
+                        "grace" => {
+                            note.grace = true;
+                            note.grace_slash = attributes.iter()
+                                .any(|attr| attr.name.local_name.as_str() == "slash" && attr.value.trim() == "yes");
+                        }
+                        "cue" => {
+                            note.cue = true;
+                        }
+                        "play" => {
+                            // Playback hints can hold several elements, so read the whole subtree
+                            loop {
+                                match parser.next() {
+                                    Ok(XmlEvent::StartElement {name, ..}) => {
+                                        if name.local_name.as_str() == "mute" {
+                                            note.muted = parse_tag_value("mute", parser)?.trim() != "off";
+                                        }
+                                    }
+                                    Ok(XmlEvent::EndElement {name}) => {
+                                        if name.local_name.as_str() == "play" {
+                                            break;
+                                        }
+                                    }
+                                    Err(e) => {
+                                        return Err(Error::Xml(e));
+                                    }
+                                    Ok(XmlEvent::EndDocument) => {
+                                        break;
+                                    }
+                                    _ => {}
+                                }
+                            }
+                        }
+                        "type" => {
+                            cue_size = attributes.iter()
+                                .any(|attr| attr.name.local_name.as_str() == "size" && matches!(attr.value.trim(), "cue" | "grace-cue"));
+                            let token = parse_tag_value("type", parser)?;
+                            match NoteType::from_musicxml(token.as_str()) {
+                                Some(note_type) => {
+                                    note.note_type = note_type;
+                                    has_type = true;
+                                }
+                                None => {warning!("Unrecognized note type '{}'", token);}
+                            }
+                        }
+                        "duration" => {
+                            note.duration = parse_tag_number("duration", parser)?;
+                        }
+                        "staff" => {
+                            // Staves are numbered from one, so treat a zero as the first staff
+                            note.staff = parse_tag_number::<u8, _>("staff", parser)?.max(1);
+                        }
+                        "voice" => {
+                            // Voices are usually numbered, anything else shares the first voice
+                            note.voice = parse_tag_value("voice", parser)?.trim().parse::<u32>().unwrap_or(1);
+                        }
+                        "rest" => {
+                            note.is_rest = true;
+                            note.measure_rest = attributes.iter()
+                                .any(|attr| attr.name.local_name.as_str() == "measure" && attr.value.trim() == "yes");
+                        }
+                        "dot" => {
+                            note.dots += 1;
+                        }
+                        "lyric" => {
+                            if let Some(lyric) = Lyric::parse_lyric(parser, &attributes)? {
+                                note.lyrics.push(lyric);
+                            }
+                        }
+                        "tie" => {
+                            // The playback tie, which some exports write without the "tied"
+                            // notation, so either one ties the note
+                            for attr in attributes.iter() {
+                                if attr.name.local_name.as_str() == "type" {
+                                    if attr.value == "start" {
+                                        note.tie_start = true;
+                                    } else if attr.value == "stop" {
+                                        note.tie_stop = true;
+                                    }
+                                }
+                            }
+                        }
+                        "time-modification" => {
+                            loop {
+                                match parser.next() {
+                                    Ok(XmlEvent::StartElement {name, ..}) => {
+                                        match name.local_name.as_str() {
+                                            "actual-notes" => {
+                                                note.tuplet_actual = parse_tag_number("actual-notes", parser)?;
+                                            }
+                                            "normal-notes" => {
+                                                note.tuplet_normal = parse_tag_number("normal-notes", parser)?;
+                                            }
+                                            _ => {}
+                                        }
+                                    }
+                                    Ok(XmlEvent::EndElement {name}) => {
+                                        if name.local_name.as_str() == "time-modification" {
+                                            break;
+                                        }
+                                    }
+                                    Err(e) => {
+                                        return Err(Error::Xml(e));
+                                    }
+                                    _ => {}
+                                }
+                            }
+                        }
+                        "beam" => {
+                            // Only the primary beam (number 1, the default) groups notes together
+                            let mut primary = true;
+                            for attr in attributes {
+                                if attr.name.local_name.as_str() == "number" && attr.value != "1" {
+                                    primary = false;
+                                }
+                            }
+                            let value = parse_tag_value("beam", parser)?;
+                            if primary {
+                                match value.as_str() {
+                                    "begin" => {
+                                        note.beam_start = true;
+                                    }
+                                    "continue" => {
+                                        note.beam_continue = true;
+                                    }
+                                    "end" => {
+                                        note.beam_continue = true;
+                                        note.beam_stop = true;
+                                    }
+                                    _ => {}
+                                }
+                            }
+                        }
+                        "notations" => {
+                            loop {
+                                match parser.next() {
+                                    Ok(XmlEvent::StartElement {name, attributes, ..}) => {
+                                        let attributes = musicxml_attributes(attributes);
+                                        match name.local_name.as_str() {
+                                            "technical" => {
+                                                loop {
+                                                    match parser.next() {
+                                                        Ok(XmlEvent::StartElement {name, ..}) => {
+                                                            match name.local_name.as_str() {
+                                                                "fingering" => {
+                                                                    note.technical.fingering = Some(parse_tag_value("fingering", parser)?);
+                                                                }
+                                                                "string" => {
+                                                                    note.technical.string = parse_tag_value("string", parser)?.trim().parse::<u32>().ok();
+                                                                }
+                                                                "fret" => {
+                                                                    note.technical.fret = parse_tag_value("fret", parser)?.trim().parse::<u32>().ok();
+                                                                }
+                                                                _ => {}
+                                                            }
+                                                        }
+                                                        Ok(XmlEvent::EndElement {name}) => {
+                                                            if name.local_name.as_str() == "technical" {
+                                                                break;
+                                                            }
+                                                        }
+                                                        Err(e) => {
+                                                            return Err(Error::Xml(e));
+                                                        }
+                                                        _ => {}
+                                                    }
+                                                }
+                                            }
+                                            "ornaments" => {
+                                                loop {
+                                                    match parser.next() {
+                                                        Ok(XmlEvent::StartElement {name, ..}) => {
+                                                            match name.local_name.as_str() {
+                                                                "accidental-mark" => {
+                                                                    // An accidental mark belongs to the ornament before it
+                                                                    let value = parse_tag_value("accidental-mark", parser)?;
+                                                                    let accidental = Ornament::convert_accidental_mark(value.as_str());
+                                                                    if accidental.is_none() {
+                                                                        warning!("Unrecognized accidental mark '{}'", value);
+                                                                    }
+                                                                    if let Some(ornament) = note.ornaments.last_mut() {
+                                                                        ornament.accidental = accidental;
+                                                                    }
+                                                                }
+                                                                // The wavy line only extends the trill it follows
+                                                                "wavy-line" => {}
+                                                                kind => {
+                                                                    note.ornaments.push(Ornament {
+                                                                        kind: kind.to_string(),
+                                                                        accidental: None,
+                                                                    });
+                                                                }
+                                                            }
+                                                        }
+                                                        Ok(XmlEvent::EndElement {name}) => {
+                                                            if name.local_name.as_str() == "ornaments" {
+                                                                break;
+                                                            }
+                                                        }
+                                                        Err(e) => {
+                                                            return Err(Error::Xml(e));
+                                                        }
+                                                        _ => {}
+                                                    }
+                                                }
+                                            }
+                                            "fermata" => {
+                                                note.fermata = true;
+                                            }
+                                            "arpeggiate" => {
+                                                note.arpeggiate = true;
+                                                note.arpeggio_down = attributes.iter()
+                                                    .any(|attr| attr.name.local_name.as_str() == "direction" && attr.value.trim() == "down");
+                                            }
+                                            "non-arpeggiate" => {
+                                                // A bracket marking the chord to be played all at once
+                                                note.arpeggiate = false;
+                                            }
+                                            "tuplet" => {
+                                                if !attributes.is_empty() {
+                                                    for attr in attributes {
+                                                        if attr.name.local_name.as_str() == "type" {
+                                                            if attr.value == "start" {
+                                                                note.triplet = true;
+                                                            }
+                                                        }
+                                                    }
+                                                }
+                                            }
+                                            "slur" => {
+                                                if !attributes.is_empty() {
+                                                    for attr in attributes {
+                                                        if attr.name.local_name.as_str() == "type" {
+                                                            if attr.value == "start" {
+                                                                note.slur_start = true;
+                                                            } else if attr.value == "stop" {
+                                                                note.slur_stop = true;
+                                                            }
+                                                        }
+                                                    }
+                                                }
+                                            }
+                                            "glissando" | "slide" => {
+                                                for attr in attributes {
+                                                    if attr.name.local_name.as_str() == "type" {
+                                                        if attr.value == "start" {
+                                                            note.glissando_start = true;
+                                                        } else if attr.value == "stop" {
+                                                            note.glissando_stop = true;
+                                                        }
+                                                    }
+                                                }
+                                            }
+                                            "tied" => {
+                                                if !attributes.is_empty() {
+                                                    for attr in attributes {
+                                                        if attr.name.local_name.as_str() == "type" {
+                                                            if attr.value == "start" {
+                                                                note.tie_start = true;
+                                                            } else if attr.value == "stop" {
+                                                                note.tie_stop = true;
+                                                            }
+                                                        }
+                                                    }
+                                                }
+                                            }
+                                            _ => {}
+                                        }
+                                    }
+                                    Ok(XmlEvent::EndElement {name}) => {
+                                        if name.local_name.as_str() == "notations" {
+                                            break;
+                                        }
+                                    }
+                                    Err(e) => {
+                                        return Err(Error::Xml(e));
+                                    }
+                                    _ => {}
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(XmlEvent::EndElement {name}) => {
+                    if name.local_name.as_str() == "note" {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    return Err(Error::Xml(e));
+                }
+                _ => {}
+            }
+        }
+
+        // Small notes are editorial like cue notes and left out the same way, except for grace
+        // notes, which are usually printed small anyway
+        if cue_size && !note.grace {
+            note.cue = true;
+        }
+
+        // Without a type tag work the type out from the written length, which for tuplets is the
+        // duration scaled back up by actual/normal
+        if !has_type && note.duration > 0 && divisions > 0 && note.tuplet_normal > 0 {
+            let written = note.duration as f64 * note.tuplet_actual as f64 / note.tuplet_normal as f64;
+            match NoteType::from_quarter_length(written / divisions as f64) {
+                Some((note_type, dots)) => {
+                    note.note_type = note_type;
+                    note.dots = dots;
+                }
+                None => {warning!("Could not work out a note type for a duration of {}", note.duration);}
+            }
+        }
+
+        Ok((note, is_chord))
+    }
+
+    /// Moves the note from the written pitch of a transposing instrument to the pitch it sounds at.
+    /// The step is moved by the diatonic steps and the alteration makes up the rest of the
+    /// chromatic steps, so the note keeps the spelling the transposition gives it.
+    ///
+    /// # Arguments
+    ///
+    /// * 'diatonic'  - The number of steps to move the note's step by
+    /// * 'chromatic' - The number of half steps to move the note's pitch by
+    ///
+    fn transpose(&mut self, diatonic: i32, chromatic: i32) {
+        // The pitch indexes of the naturals in an octave, counted from A flat
+        const NATURALS: [i32; 7] = [1, 3, 4, 6, 8, 9, 11];
+        let pitch_index = self.pitch_index as i32;
+        let step = match NATURALS.iter().position(|natural| *natural == pitch_index.rem_euclid(12)) {
+            Some(step) => step as i32,
+            None => return,
+        };
+        let steps = pitch_index.div_euclid(12) * 7 + step + diatonic;
+        let transposed = steps.div_euclid(7) * 12 + NATURALS[steps.rem_euclid(7) as usize];
+        if transposed < 0 {
+            warning!("A transposed note is below the lowest pitch GJM supports, leaving it at written pitch");
+            return;
+        }
+        self.alter += chromatic - (transposed - pitch_index);
+        self.pitch_index = transposed as u32;
+        self.step = ["A", "B", "C", "D", "E", "F", "G"][steps.rem_euclid(7) as usize].to_string();
+    }
+
+    /// Moves the note by whole octaves until it sounds within a range, for instruments that can't
+    /// play the full range of the score
+    ///
+    /// # Arguments
+    ///
+    /// * 'low'  - The lowest pitch index the note may sound at
+    /// * 'high' - The highest pitch index the note may sound at
+    ///
+    fn fold_into_range(&mut self, low: u32, high: u32) {
+        let mut pitch = self.pitch_index as i32 + self.alter;
+        while pitch > high as i32 && pitch - 12 >= 0 {
+            pitch -= 12;
+            self.pitch_index -= 12;
+        }
+        while pitch < low as i32 {
+            pitch += 12;
+            self.pitch_index += 12;
+        }
+        if pitch > high as i32 {
+            warning!("The pitch range is narrower than an octave, a note is left above it");
+        }
+    }
+
+    /// Returns the letter of the note counted from C, so C is 0 and B is 6. Notes without a step
+    /// fall back on the natural their pitch index is written on.
+    fn get_letter(&self) -> Option<usize> {
+        const LETTERS: [&str; 7] = ["C", "D", "E", "F", "G", "A", "B"];
+        match LETTERS.iter().position(|letter| *letter == self.step) {
+            Some(letter) => Some(letter),
+            // The pitch indexes of the naturals in an octave from C, counted from A flat
+            None => [4, 6, 8, 9, 11, 1, 3].iter().position(|natural| *natural == self.pitch_index % 12),
+        }
+    }
+
+    /// Returns the fixed numbered sign of the note, which comes from its letter whatever its
+    /// alteration, so that C, C sharp and C flat are all 1
+    fn get_numbered_sign(&self) -> u32 {
+        self.get_letter().map_or(1, |letter| letter as u32 + 1)
+    }
+
+    /// Returns the numbered sign of the note counted from the tonic of the key (movable do), so
+    /// that the tonic is always 1
+    ///
+    /// # Arguments
+    ///
+    /// * 'key' - The major key represented by a shift from C Major
+    ///
+    fn get_movable_numbered_sign(&self, key: i32) -> u32 {
+        match self.get_letter() {
+            Some(letter) => {
+                // Each fifth up the circle moves the tonic four letters up
+                let tonic = (key * 4).rem_euclid(7) as usize;
+                ((letter + 7 - tonic) % 7) as u32 + 1
+            }
+            None => 1,
+        }
+    }
+
+    fn get_alterant_type(&self) -> &str {
+        let mut result = "";
+        match self.alter {
+            -2 => {result = "DoubleFlat";},
+            -1 => {result = "Flat";},
+            0 => {result = "Natural";},
+            1 => {result = "Sharp";},
+            2 => {result = "DoubleSharp";},
+            _ => {},
+        }
+        result
+    }
+}
+
+/// A collection of Notes that all begin on the same division
+#[derive(Clone, Debug)]
+struct Chord {
+    /// The Notes of the Chord
+    notes: Vec<Note>,
+    /// The division the chord begins on
+    start_time: u32,
+    duration: u32,
+    note_type: NoteType,
+    dots: u8,
+    is_rest: bool,
+    arpeggiate: bool,
+    arpeggio_down: bool,
+    /// Whether any note of the chord is held under a fermata. GJM has no fermatas, so the chord is
+    /// still written at its written length.
+    fermata: bool,
+    triplet: bool,
+    tie_start: bool,
+    tie_stop: bool,
+    slur_start: bool,
+    slur_stop: bool,
+    /// The voice the chord belongs to within its staff
+    voice: u32,
+    /// Volume out of 100 where the chord starts
+    volume: u32,
+    /// The grace chords played just before the chord
+    grace_chords: Vec<Vec<Note>>,
+}
+
+impl Chord {
+    /// Returns a default instantiation of a Chord
+    fn new() -> Self {
+        Self {
+            notes: Vec::<Note>::new(),
+            start_time: 0,
+            duration: 0,
+            note_type: NoteType::Quarter,
+            dots: 0,
+            is_rest: false,
+            arpeggiate: false,
+            arpeggio_down: false,
+            fermata: false,
+            triplet: false,
+            tie_start: false,
+            tie_stop: false,
+            slur_start: false,
+            slur_stop: false,
+            voice: 1,
+            volume: 80,
+            grace_chords: Vec::<Vec<Note>>::new(),
+        }
+    }
+
+    /// Returns the syllables sung on the chord. Notes cut into tied pieces keep their words on the
+    /// first piece only, so notes tied from an earlier one are left out.
+    fn get_lyrics(&self) -> Vec<&Lyric> {
+        self.notes.iter()
+            .filter(|note| !note.tie_stop)
+            .flat_map(|note| note.lyrics.iter())
+            .collect()
+    }
+
+    /// Get the gjm duration type of the chord. Note types GJM has no name for are clamped to the
+    /// nearest one it does, with a warning.
+    ///
+    /// # Arguments
+    ///
+    /// * 'measure' - The index of the measure the chord is in, for the warning
+    ///
+    fn gjm_note_string(&self, measure: usize) -> &'static str {
+        match self.note_type {
+            NoteType::TenTwentyFourth | NoteType::FiveTwelfth | NoteType::TwoFiftySixth | NoteType::OneTwentyEighth => {
+                warning!("{:?} note in measure {} is shorter than GJM supports, writing it as a 64th", self.note_type, measure);
+                "The64th"
+            },
+            NoteType::SixtyFourth => "The64th",
+            NoteType::ThirtySecond => "The32nd",
+            NoteType::Sixteenth => "The16th",
+            NoteType::Eighth => "Eighth",
+            NoteType::Quarter => "Quarter",
+            NoteType::Half => "Half",
+            NoteType::Whole => "Whole",
+            NoteType::Breve | NoteType::Long | NoteType::Maxima => {
+                warning!("{:?} note in measure {} is longer than GJM supports, writing it as a whole", self.note_type, measure);
+                "Whole"
+            },
+        }
+    }
+}
+
+/// Enumerated Clef sign values
+#[derive(Clone, Debug, Copy, PartialEq)]
+pub enum Clef {
+    /// Bass clef, the F clef on the fourth line
+    F,
+    /// Treble clef, the G clef on the second line
+    G,
+    /// Alto clef, the C clef on the middle line
+    Alto,
+    /// Tenor clef, the C clef on the fourth line
+    Tenor,
+}
+
+impl Clef {
+    /// Converts a MusicXml clef sign and line into the GJM clef closest to it, with a warning when
+    /// GJM has no clef on that line. Signs that are not clefs GJM can get close to give None.
+    ///
+    /// # Arguments
+    ///
+    /// * 'sign' - The "sign" of the clef
+    /// * 'line' - The staff line of the clef counted from the bottom, if given
+    ///
+    fn from_musicxml(sign: &str, line: Option<u32>) -> Option<Self> {
+        let clef = match (sign, line) {
+            ("G", None | Some(2)) => Clef::G,
+            ("F", None | Some(4)) => Clef::F,
+            ("C", None | Some(3)) => Clef::Alto,
+            ("C", Some(4)) => Clef::Tenor,
+            // GJM has no percussion clef, and unpitched notes give their positions as if on a
+            // treble staff
+            ("percussion", _) => Clef::G,
+            // Soprano and mezzo-soprano clefs sit closest to the treble clef and the baritone
+            // clef to the bass clef
+            ("G", _) | ("C", Some(1 | 2)) | ("TAB", _) => {
+                warning!("GJM has no {} clef on line {}, using a treble clef", sign, line.unwrap_or(0));
+                Clef::G
+            }
+            ("F", _) | ("C", _) => {
+                warning!("GJM has no {} clef on line {}, using a bass clef", sign, line.unwrap_or(0));
+                Clef::F
+            }
+            _ => return None,
+        };
+        Some(clef)
+    }
+
+    /// Returns how GJM names the clef
+    fn gjm_string(&self) -> &'static str {
+        match self {
+            Clef::F => "L4F",
+            Clef::G => "L2G",
+            Clef::Alto => "L3C",
+            Clef::Tenor => "L4C",
+        }
+    }
+}
+
+/// Enumerated key signature modes
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mode {
+    Major,
+    Minor,
+    Dorian,
+    Phrygian,
+    Lydian,
+    Mixolydian,
+    Aeolian,
+    Ionian,
+    Locrian,
+}
+
+impl Mode {
+    /// Converts a MusicXml "mode" value into a mode, defaulting to major with a warning for any
+    /// mode that isn't known
+    fn from_musicxml(value: &str) -> Self {
+        match value.trim() {
+            "major" => Mode::Major,
+            "minor" => Mode::Minor,
+            "dorian" => Mode::Dorian,
+            "phrygian" => Mode::Phrygian,
+            "lydian" => Mode::Lydian,
+            "mixolydian" => Mode::Mixolydian,
+            "aeolian" => Mode::Aeolian,
+            "ionian" => Mode::Ionian,
+            "locrian" => Mode::Locrian,
+            _ => {
+                warning!("Unknown key mode '{}', using major", value.trim());
+                Mode::Major
+            }
+        }
+    }
+}
+
+/// Get the name GJM gives a key in numbered notation. GJM only names major keys, so every mode is
+/// named after its relative major, which is the key with the same number of sharps or flats.
+///
+/// # Arguments
+///
+/// * 'key' - The key represented by a shift from C Major, i.e. Bflat Major would have key = -2
+///
+fn numbered_key_name(key: i32) -> &'static str {
+    const NAMES: [&str; 15] = ["Cb", "Gb", "Db", "Ab", "Eb", "Bb", "F", "C", "G", "D", "A", "E", "B", "F#", "C#"];
+    // Keys past seven sharps or flats are named after the key that sounds the same
+    let mut key = key;
+    while key > 7 {
+        key -= 12;
+    }
+    while key < -7 {
+        key += 12;
+    }
+    NAMES[(key + 7) as usize]
+}
+
+/// A collection of attributes that apply to measures
+#[derive(Clone, Debug)]
+struct Attributes {
+    /// Number of divisions per beat
+    divisions: u32,
+    /// The number of divisions each division of the file stands for, more than one only when the
+    /// divisions changed part way through the measure and the measure keeps counting in a finer
+    /// division that both fit into
+    division_scale: u32,
+    /// Volume out of 100
+    volume: u32,
+    /// Beats per minute
+    tempo: u32,
+    /// The major key represented by a shift from C Major, i.e. Bflat Major would have key = -2
+    key: i32,
+    /// The mode of the key, which GJM names after its relative major
+    mode: Mode,
+    /// The key as written, which differs from the key that sounds for transposing instruments
+    written_key: i32,
+    /// The number of steps the written notes are moved by to get the steps that sound
+    transpose_diatonic: i32,
+    /// The number of half steps the written notes are moved by to get the pitches that sound
+    transpose_chromatic: i32,
+    /// The number of beats per measure (the top of the key signature)
+    beats: u8,
+    /// What type of note counts as a beat (the bottom of the key signature)
+    beat_type: u8,
+    /// What Clef the associated measure uses
+    clef: Clef,
+    /// The number of octaves the clef moves the notes by, such as -1 for the treble clef with an 8
+    /// below used for guitar and tenor parts
+    clef_octave_change: i32,
+    /// Whether the part has declared its divisions in this or an earlier measure
+    divisions_declared: bool,
+    /// The number of measures in the multiple rest starting at this measure, zero if none starts
+    multiple_rest: u32,
+    /// Whether the music is marked to be swung
+    swing: bool,
+}
+
+impl Attributes {
+    /// Returns a deafult instantiation of the Attributes type
+    fn new() -> Self {
+        Self {
+            divisions: 24,
+            division_scale: 1,
+            volume: 80,
+            tempo: 108,
+            key: 0,
+            mode: Mode::Major,
+            written_key: 0,
+            transpose_diatonic: 0,
+            transpose_chromatic: 0,
+            beats: 4,
+            beat_type: 4,
+            clef: Clef::G,
+            clef_octave_change: 0,
+            divisions_declared: false,
+            multiple_rest: 0,
+            swing: false,
+        }
+    }
+
+    /// Returns the divisions per beat that the file counts durations in
+    fn get_file_divisions(&self) -> u32 {
+        self.divisions / self.division_scale
+    }
+
+    /// Parses the tags and values inside of the "attributes" tag, returning a number of Attribute
+    /// structures equal to the number of staves present or the number provided by the caller,
+    /// whichever is higher
+    ///
+    /// # Arguments
+    ///
+    /// * 'parser' - A mutable reference to the parser located inside the "attributes" tag
+    /// * 'attribute_list' - a mutable vector of attributes to use as a baseline
+    /// * 'options' - The conversion options in use
+    ///
+    fn parse_attributes<R: Read>(parser: &mut EventReader<R>, mut attribute_list: Vec<Self>, options: &Options) -> Result<Vec<Self>, Error> {
+        if attribute_list.is_empty() {
+            attribute_list.push(Self::new());
+        }
+        loop {
+            match parser.next() {
+                Ok(XmlEvent::StartElement {name, attributes, ..}) => {
+                    let attributes = musicxml_attributes(attributes);
+                    match name.local_name.as_str() {
+                        "divisions" => {
+                            let divisions: u32 = parse_tag_count("divisions", parser)?;
+                            for i in 0..attribute_list.len() {
+                                attribute_list[i].divisions = divisions;
+                                attribute_list[i].division_scale = 1;
+                                attribute_list[i].divisions_declared = true;
+                            }
+                        }
+                        "key" => {
+                            loop {
+                                match parser.next() {
+                                    Ok(XmlEvent::StartElement{name,..}) => {
                                         match name.local_name.as_str() {
-                                            "step" => {
-                                                step = parse_tag_value("step", parser);
+                                            "fifths" => {
+                                                let key: i32 = parse_tag_number("fifths", parser)?;
+                                                for i in 0..attribute_list.len() {
+                                                    attribute_list[i].written_key = key;
+                                                }
                                             }
-                                            "octave" => {
-                                                octave = parse_tag_value("octave", parser).parse::<u32>().unwrap();
+                                            "mode" => {
+                                                let mode = Mode::from_musicxml(&parse_tag_value("mode", parser)?);
+                                                for attributes in attribute_list.iter_mut() {
+                                                    attributes.mode = mode;
+                                                }
                                             }
-                                            "alter" => {
-                                                note.alter = parse_tag_value("alter", parser).parse::<i32>().unwrap();
+                                            _ => {}
+                                        }
+                                    }
+                                    Ok(XmlEvent::EndElement{name}) => {
+                                        if name.local_name.as_str() == "key" {
+                                            break;
+                                        }
+                                    }
+                                    Err(e) => {
+                                        return Err(Error::Xml(e));
+                                    }
+                                    _ => {}
+                                }
+                            }
+                        }
+                        "time" => {
+                            loop {
+                                match parser.next() {
+                                    Ok(XmlEvent::StartElement{name, ..}) => {
+                                        match name.local_name.as_str() {
+                                            "beats" => {
+                                                let beats: u8 = parse_tag_count("beats", parser)?;
+                                                for i in 0..attribute_list.len() {
+                                                    attribute_list[i].beats = beats;
+                                                }
+                                            }
+                                            "beat-type" => {
+                                                let beat_type: u8 = parse_tag_count("beat-type", parser)?;
+                                                for i in 0..attribute_list.len() {
+                                                    attribute_list[i].beat_type = beat_type;
+                                                }
+                                            }
+                                            _ => {}
+                                        }
+                                    }
+                                    Ok(XmlEvent::EndElement{name}) => {
+                                        if name.local_name.as_str() == "time" {
+                                            break;
+                                        }
+                                    }
+                                    Err(e) => {
+                                        return Err(Error::Xml(e));
+                                    }
+                                    _ => {}
+                                }
+                            }
+                        }
+                        "transpose" => {
+                            // A transposition with a number is only for that staff
+                            let staff = attributes.iter()
+                                .find(|attr| attr.name.local_name.as_str() == "number")
+                                .and_then(|attr| attr.value.trim().parse::<usize>().ok());
+                            let mut diatonic = 0;
+                            let mut chromatic = 0;
+                            let mut octave_change = 0;
+                            loop {
+                                match parser.next() {
+                                    Ok(XmlEvent::StartElement{name,..}) => {
+                                        match name.local_name.as_str() {
+                                            "diatonic" => {
+                                                diatonic = parse_tag_number("diatonic", parser)?;
+                                            }
+                                            "chromatic" => {
+                                                chromatic = parse_tag_number("chromatic", parser)?;
+                                            }
+                                            "octave-change" => {
+                                                octave_change = parse_tag_number("octave-change", parser)?;
                                             }
                                             _ => {}
                                         }
                                     }
+                                    Ok(XmlEvent::EndElement{name}) => {
+                                        if name.local_name.as_str() == "transpose" {
+                                            break;
+                                        }
+                                    }
+                                    Err(e) => {
+                                        return Err(Error::Xml(e));
+                                    }
+                                    _ => {}
+                                }
+                            }
+                            for (i, attribute) in attribute_list.iter_mut().enumerate() {
+                                if staff.is_none() || staff == Some(i + 1) {
+                                    attribute.transpose_diatonic = diatonic + 7 * octave_change;
+                                    attribute.transpose_chromatic = chromatic + 12 * octave_change;
+                                }
+                            }
+                        }
+                        "multiple-rest" => {
+                            let count = parse_tag_value("multiple-rest", parser)?.trim().parse::<u32>().unwrap_or(0);
+                            for attributes in attribute_list.iter_mut() {
+                                attributes.multiple_rest = count;
+                            }
+                        }
+                        "staves" => {
+                            let staves = parse_tag_number("staves", parser)?;
+                            // Don't add extra attribute sets unless number of staves is >= 2
+                            for i in 1..staves {
+                                if i as usize >= attribute_list.len() {
+                                    let next_attr = attribute_list[0].clone();
+                                    attribute_list.push(next_attr);
+                                }
+                            }
+                        }
+                        "clef" => {
+                            // Assume this refers to the first staff unless otherwise specified
+                            let mut index = 1;
+                            // In a single staff Part there are no attributes to the clef tag
+                            if !attributes.is_empty() {
+                                for attr in attributes {
+                                    if attr.name.local_name.as_str() == "number" {
+                                        match attr.value.parse::<usize>() {
+                                            Ok(number) => {
+                                                index = number;
+                                            }
+                                            Err(_) => {
+                                                warning!("Invalid clef number '{}', defaulting to staff 1", attr.value);
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                            // Staff numbers start at one and must refer to a staff we know about
+                            if index == 0 || index > attribute_list.len() {
+                                warning!("Clef number {} is out of range, defaulting to staff 1", index);
+                                index = 1;
+                            }
+                            // A new clef has no octave change unless it gives one
+                            attribute_list[index - 1].clef_octave_change = 0;
+                            // The line can come after the sign, so both are read before choosing
+                            let mut sign = "".to_string();
+                            let mut line = None;
+                            loop {
+                                match parser.next() {
+                                    Ok(XmlEvent::StartElement {name, ..}) => {
+                                        if name.local_name.as_str() == "clef-octave-change" {
+                                            attribute_list[index - 1].clef_octave_change = parse_tag_number("clef-octave-change", parser)?;
+                                        } else if name.local_name.as_str() == "sign" {
+                                            sign = parse_tag_value("sign", parser)?.trim().to_string();
+                                        } else if name.local_name.as_str() == "line" {
+                                            line = Some(parse_tag_number("line", parser)?);
+                                        }
+                                    }
                                     Ok(XmlEvent::EndElement {name}) => {
-                                        if name.local_name.as_str() == "pitch" {
-                                            note.pitch_index = Note::convert_pitch_index(step.as_str(), octave);
+                                        if name.local_name.as_str() == "clef" {
+                                            match Clef::from_musicxml(&sign, line) {
+                                                Some(clef) => {
+                                                    attribute_list[index - 1].clef = clef;
+                                                }
+                                                None => {
+                                                    match options.unsupported_clef {
+                                                        UnsupportedClef::Warn => {
+                                                            warning!("Unrecognized Clef value '{}', keeping the previous clef", sign);
+                                                        }
+                                                        UnsupportedClef::Fallback(clef) => {
+                                                            warning!("Unrecognized Clef value '{}', using {:?} instead", sign, clef);
+                                                            attribute_list[index - 1].clef = clef;
+                                                        }
+                                                        UnsupportedClef::Error => {
+                                                            return Err(Error::UnsupportedClef(sign));
+                                                        }
+                                                    }
+                                                }
+                                            }
                                             break;
                                         }
                                     }
-                                    _ => {}
+                                    Err(e) => {
+                                        return Err(Error::Xml(e));
+                                    }
+                                    _ => {}
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(XmlEvent::EndElement {name, ..}) => {
+                    if name.local_name.as_str() == "attributes" {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    return Err(Error::Xml(e));
+                }
+                _ => {}
+            }
+        }
+        // Transposing instruments sound in a different key than the one they are written in, by
+        // seven fifths for every half step
+        for attribute in attribute_list.iter_mut() {
+            attribute.key = attribute.written_key;
+            if !options.written_pitch {
+                attribute.key += attribute.transpose_chromatic * 7;
+                while attribute.key > 7 {
+                    attribute.key -= 12;
+                }
+                while attribute.key < -7 {
+                    attribute.key += 12;
+                }
+            }
+        }
+        Ok(attribute_list)
+    }
+}
+
+/// The repeat structure marked by the barlines of a measure
+#[derive(Clone, Debug, Default)]
+struct Repeats {
+    /// Whether a forward repeat starts a repeated section at this measure
+    start: bool,
+    /// The number of times the section ending at this measure is played, zero when no backward
+    /// repeat ends a section here
+    end_times: u32,
+    /// The passes of the ending that starts at this measure, if one does
+    ending_start: Option<Vec<u32>>,
+    /// Whether an ending finishes at this measure
+    ending_stop: bool,
+}
+
+impl Repeats {
+    /// Parses the repeats and endings inside of a "barline" tag
+    ///
+    /// # Arguments
+    ///
+    /// * 'parser' - A mutable reference to the parser located inside the "barline" tag
+    ///
+    fn parse_barline<R: Read>(parser: &mut EventReader<R>) -> Result<Self, Error> {
+        let mut repeats = Repeats::default();
+        loop {
+            match parser.next() {
+                Ok(XmlEvent::StartElement {name, attributes, ..}) => {
+                    let attributes = musicxml_attributes(attributes);
+                    let get_attribute = |label: &str| attributes.iter()
+                        .find(|attr| attr.name.local_name.as_str() == label)
+                        .map(|attr| attr.value.as_str());
+                    match name.local_name.as_str() {
+                        "repeat" => {
+                            match get_attribute("direction") {
+                                Some("forward") => {
+                                    repeats.start = true;
+                                }
+                                Some("backward") => {
+                                    // A section is played twice unless it says otherwise
+                                    repeats.end_times = match get_attribute("times") {
+                                        Some(times) => parse_number("repeat times", times)?,
+                                        None => 2,
+                                    };
+                                }
+                                _ => {}
+                            }
+                        }
+                        "ending" => {
+                            match get_attribute("type") {
+                                Some("start") => {
+                                    // Endings played on several passes list them, as in "1, 2"
+                                    let mut numbers = Vec::<u32>::new();
+                                    for number in get_attribute("number").unwrap_or("").split([',', ' ']) {
+                                        if !number.is_empty() {
+                                            numbers.push(parse_number("ending number", number)?);
+                                        }
+                                    }
+                                    repeats.ending_start = Some(numbers);
+                                }
+                                Some("stop") | Some("discontinue") => {
+                                    repeats.ending_stop = true;
                                 }
+                                _ => {}
                             }
                         }
-                        "chord" => {
-                            is_chord = true;
-                        }
-                        "type" => {
-                            match parse_tag_value("type", parser).as_str() {
-                                "1024th" => {
-                                    note.note_type = NoteType::TenTwentyFourth;
+                        _ => {}
+                    }
+                }
+                Ok(XmlEvent::EndElement {name}) => {
+                    if name.local_name.as_str() == "barline" {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    return Err(Error::Xml(e));
+                }
+                _ => {}
+            }
+        }
+        Ok(repeats)
+    }
+
+    /// Adds the repeats of another barline of the same measure, since a measure has a barline on
+    /// each side
+    ///
+    /// # Arguments
+    ///
+    /// * 'other' - The repeats of the other barline
+    ///
+    fn merge(&mut self, other: &Repeats) {
+        self.start |= other.start;
+        self.end_times = self.end_times.max(other.end_times);
+        if other.ending_start.is_some() {
+            self.ending_start = other.ending_start.clone();
+        }
+        self.ending_stop |= other.ending_stop;
+    }
+}
+
+/// A collection of Chords and a set of Attributes that represent a single Measure of a single Part
+#[derive(Clone, Debug)]
+struct Measure {
+    chords: Vec<Chord>,
+    attributes: Attributes,
+    /// Whether a direction in this measure sets the tempo
+    tempo_declared: bool,
+    /// The line of the MusicXml file the measure starts on, if known
+    line: Option<u64>,
+    /// The repeats and endings marked by the barlines of the measure
+    repeats: Repeats,
+    /// The length in divisions of a pickup, or another measure marked as not counting towards
+    /// the meter, which is as long as its longest staff instead of its time signature
+    implicit_duration: Option<u32>,
+    /// The clef the measure is written with when its clef changes part way through, since GJM
+    /// has one clef for each measure. The clef at the end of the measure is the one that carries
+    /// on into the next measure.
+    shown_clef: Option<Clef>,
+    /// The stereo placements given by sound directions in the measure, such as "a pan of -45
+    /// degrees", which GJM has no way to play
+    placements: Vec<String>,
+}
+
+impl Measure {
+    /// Returns an instance of a measure using a set of Attributes as the base
+    ///
+    /// # Arguments
+    ///
+    /// * 'attr' - the Attributes structure to use in the measure
+    ///
+    fn from_attributes(attr: Attributes) -> Self {
+        Self {
+            chords: Vec::<Chord>::new(),
+            attributes: attr,
+            tempo_declared: false,
+            line: None,
+            repeats: Repeats::default(),
+            implicit_duration: None,
+            shown_clef: None,
+            placements: Vec::<String>::new(),
+        }
+    }
+
+    /// Returns a measure holding a single rest that fills the whole measure, used in place of a
+    /// measure that could not be parsed
+    ///
+    /// # Arguments
+    ///
+    /// * 'attr' - the Attributes structure to use in the measure
+    ///
+    fn rest_from_attributes(attr: Attributes) -> Self {
+        let mut measure = Measure::from_attributes(attr);
+        let mut rest = Chord::new();
+        rest.is_rest = true;
+        rest.volume = measure.attributes.volume;
+        rest.duration = measure.get_full_duration();
+        let quarters = rest.duration as f64 / measure.attributes.divisions as f64;
+        if let Some((note_type, dots)) = NoteType::from_quarter_length(quarters) {
+            rest.note_type = note_type;
+            rest.dots = dots;
+        }
+        measure.chords.push(rest);
+        measure.split_long_chords();
+        measure.split_double_dotted_chords();
+        measure
+    }
+
+    /// Returns the volume the measure starts at, which GJM holds for the whole measure since it
+    /// has no volume for each chord. A volume change part way through the measure is heard from
+    /// the next measure.
+    fn get_start_volume(&self) -> u32 {
+        self.chords.first().map_or(self.attributes.volume, |chord| chord.volume)
+    }
+
+    /// Returns the clef the measure is written with
+    fn get_clef(&self) -> Clef {
+        self.shown_clef.unwrap_or(self.attributes.clef)
+    }
+
+    /// Returns the clef that holds for the longest part of a measure whose clef changes part way
+    /// through, the earliest one winning a tie, or None if the clef doesn't change
+    ///
+    /// # Arguments
+    ///
+    /// * 'changes' - The clef changes of the staff as (position, old clef, new clef) in the order
+    ///   they were read
+    /// * 'end'     - The position the measure ends at
+    ///
+    fn calc_shown_clef(changes: &[(u32, Clef, Clef)], end: u32) -> Option<Clef> {
+        let mut changes = changes.to_vec();
+        changes.sort_by_key(|(position, _, _)| *position);
+        let (_, first, _) = *changes.first()?;
+        // Add up how long each clef holds, from the start of the measure to the first change and
+        // then from each change to the next
+        let mut spans = Vec::<(Clef, u32)>::new();
+        let mut add_span = |clef: Clef, length: u32| {
+            match spans.iter_mut().find(|(known, _)| *known == clef) {
+                Some((_, total)) => *total += length,
+                None => spans.push((clef, length)),
+            }
+        };
+        let mut start = 0;
+        let mut clef = first;
+        for (position, _, new) in changes {
+            add_span(clef, position - start);
+            start = position;
+            clef = new;
+        }
+        add_span(clef, end.saturating_sub(start));
+        let longest = spans.iter().map(|(_, length)| *length).max()?;
+        spans.iter().find(|(_, length)| *length == longest).map(|(clef, _)| *clef)
+    }
+
+    /// Get the MusicXml duration of a completely filled measure in divisions
+    fn get_full_duration(&self) -> u32 {
+        if let Some(duration) = self.implicit_duration {
+            return duration;
+        }
+        // Divisions count quarter notes, so scale the beats by how many quarters each one is
+        (self.attributes.divisions as u64 * self.attributes.beats as u64 * 4 / self.attributes.beat_type as u64) as u32
+    }
+
+    /// Parse a MusicXml measure and return a list of single staff measures
+    ///
+    /// # Arguments
+    ///
+    /// * 'parser'  - A mutable reference to the parser located inside the tag holding the measure
+    /// * 'attrs'   - A list of Attributes to use as the base attributes of any parsed measures
+    /// * 'end_tag' - The label of the tag holding the measure, "measure" in partwise scores and
+    ///   "part" in timewise scores
+    /// * 'options' - The conversion options in use
+    ///
+    fn parse_measure<R: Read>(parser: &mut EventReader<R>, attrs: Vec<Attributes>, end_tag: &str, options: &Options) -> Result<Vec<Self>, Error> {
+        let mut measures: Vec<Self> = Vec::<Self>::new();
+        // Use a BTreeMap to group notes by start location and also sort chords by start location
+        let mut note_map: BTreeMap<u32, Vec<Note>> = BTreeMap::new();
+        let mut current_position: u32 = 0;
+        let mut last_position: u32 = 0;
+        // How far chords shortened to their shortest note have moved current_position before the
+        // position the file counts from. Backups in the file are counted from the file's position,
+        // so they have to undo this to land where the file means on every staff and voice.
+        let mut shortened: u32 = 0;
+        // The location of each note in the beamed group currently being read as (position, index)
+        let mut beam_group = Vec::<(u32, usize)>::new();
+        // Grace chords read since the last full note, waiting for the note they lead into
+        let mut grace_chords = Vec::<Vec<Note>>::new();
+        // The tempo of the last metronome mark in the measure, only used if no sound sets a tempo
+        let mut metronome_tempo = None;
+        // The volume changes made in the measure as (position, volume)
+        let mut volume_changes = Vec::<(u32, u32)>::new();
+        let start_volume = attrs.first().map_or(Attributes::new().volume, |attr| attr.volume);
+        // The clefs changed part way through the measure as (staff index, position, old clef, new
+        // clef)
+        let mut clef_changes = Vec::<(usize, u32, Clef, Clef)>::new();
+
+        for mut attr in attrs {
+            // A finer division from a change part way through the last measure isn't carried on
+            attr.divisions = attr.get_file_divisions();
+            attr.division_scale = 1;
+            measures.push(Measure::from_attributes(attr));
+        }
+        loop {
+            match parser.next() {
+                Ok(XmlEvent::StartElement {name, ..}) => {
+                    match name.local_name.as_str() {
+                        "attributes" => {
+                            // Start from each staff's current attributes so that changes made
+                            // earlier in the measure, and changes to other staves, are kept
+                            let current_attributes = measures.iter().map(|measure| measure.attributes.clone()).collect();
+                            let mut tmp_attributes = Attributes::parse_attributes(parser, current_attributes, options)?;
+                            let old_divisions = measures[0].attributes.divisions;
+                            let new_divisions = tmp_attributes[0].divisions;
+                            let declared = tmp_attributes[0].division_scale != measures[0].attributes.division_scale;
+                            if (declared || new_divisions != old_divisions) && (current_position > 0 || !note_map.is_empty()) {
+                                // The notes so far count in the old divisions, so count the whole
+                                // measure in a division both the old and new ones fit into
+                                let divisions = lcm(old_divisions, new_divisions);
+                                let factor = divisions / old_divisions;
+                                Measure::scale_positions(&mut note_map, factor);
+                                current_position *= factor;
+                                last_position *= factor;
+                                shortened *= factor;
+                                for (position, _) in beam_group.iter_mut() {
+                                    *position *= factor;
+                                }
+                                for (_, position, _, _) in clef_changes.iter_mut() {
+                                    *position *= factor;
                                 }
-                                "512th" => {
-                                    note.note_type = NoteType::FiveTwelfth;
+                                for attributes in tmp_attributes.iter_mut() {
+                                    attributes.divisions = divisions;
+                                    attributes.division_scale = divisions / new_divisions;
+                                }
+                            }
+                            if current_position > 0 {
+                                for (i, measure) in measures.iter().enumerate() {
+                                    if let Some(attributes) = tmp_attributes.get(i) {
+                                        if attributes.clef != measure.attributes.clef {
+                                            clef_changes.push((i, current_position, measure.attributes.clef, attributes.clef));
+                                        }
+                                    }
                                 }
-                                "256th" => {
-                                    note.note_type = NoteType::TwoFiftySixth;
+                            }
+                            // Attributes will tell us how many staves we have, make a measure for
+                            // each one
+                            if measures.len() < tmp_attributes.len() {
+                                for i in 0.. measures.len() {
+                                    measures[i].attributes = tmp_attributes[i].clone();
                                 }
-                                "128th" => {
-                                    note.note_type = NoteType::OneTwentyEighth;
+                                for i in measures.len()..tmp_attributes.len() {
+                                    measures.push(Measure::from_attributes(tmp_attributes[i].clone()));
                                 }
-                                "64th" => {
-                                    note.note_type = NoteType::SixtyFourth;
+                            } else {
+                                for i in 0..tmp_attributes.len() {
+                                    measures[i].attributes = tmp_attributes[i].clone();
                                 }
-                                "32nd" => {
-                                    note.note_type = NoteType::ThirtySecond;
+                            }
+                        }
+                        "note" => {
+                            let (mut tmp_note, is_chord) = Note::parse_note(parser, measures[0].attributes.get_file_divisions(), options)?;
+                            tmp_note.duration *= measures[0].attributes.division_scale;
+                            if tmp_note.measure_rest {
+                                // Whole measure rests are often written as whole rests whatever the
+                                // meter, and some exports leave out their duration, so go by the
+                                // meter instead. A pickup's rest keeps the shorter duration it has.
+                                let measure = measures.get(tmp_note.staff as usize - 1).unwrap_or(&measures[0]);
+                                if tmp_note.duration == 0 {
+                                    tmp_note.duration = measure.get_full_duration();
                                 }
-                                "16th" => {
-                                    note.note_type = NoteType::Sixteenth;
+                                let quarters = tmp_note.duration as f64 / measure.attributes.divisions as f64;
+                                if let Some((note_type, dots)) = NoteType::from_quarter_length(quarters) {
+                                    tmp_note.note_type = note_type;
+                                    tmp_note.dots = dots;
                                 }
-                                "eighth" => {
-                                    note.note_type = NoteType::Eighth;
+                            }
+                            if !tmp_note.is_rest && !tmp_note.unpitched {
+                                if !options.written_pitch {
+                                    let attributes = &measures.get(tmp_note.staff as usize - 1).unwrap_or(&measures[0]).attributes;
+                                    let (mut diatonic, mut chromatic) = (attributes.transpose_diatonic, attributes.transpose_chromatic);
+                                    // A transposition already gives the octave of octave clefs, so
+                                    // the clef only moves the notes of parts that don't have one
+                                    if diatonic == 0 && chromatic == 0 {
+                                        diatonic = attributes.clef_octave_change * 7;
+                                        chromatic = attributes.clef_octave_change * 12;
+                                    }
+                                    tmp_note.transpose(diatonic, chromatic);
                                 }
-                                "quarter" => {
-                                    note.note_type = NoteType::Quarter;
+                                if let Some((low, high)) = options.pitch_range {
+                                    tmp_note.fold_into_range(low, high);
                                 }
-                                "half" => {
-                                    note.note_type = NoteType::Half;
+                            }
+                            if tmp_note.cue {
+                                // Cue notes aren't played, so like a forward they only move the
+                                // position on, leaving the time to the notes of the part itself
+                                if !is_chord {
+                                    last_position = current_position;
+                                    current_position += tmp_note.duration;
                                 }
-                                "whole" => {
-                                    note.note_type = NoteType::Whole;
+                                continue;
+                            }
+                            if tmp_note.grace {
+                                // Grace notes take no time, so keep them aside until the note they
+                                // lead into, grouping notes marked as chords with the one before
+                                match grace_chords.last_mut() {
+                                    Some(grace_chord) if is_chord => grace_chord.push(tmp_note),
+                                    _ => grace_chords.push(vec![tmp_note]),
                                 }
-                                "breve" => {
-                                    note.note_type = NoteType::Breve;
+                                continue;
+                            }
+                            tmp_note.grace_chords.append(&mut grace_chords);
+                            // Assume position will be current_position
+                            let mut position = current_position;
+                            if is_chord {
+                                // If it's part of a chord just put it in the last position
+                                position = last_position;
+                                // current_position won't change unless we have different durations
+                                // in the same chord, in which case use the smaller duration. A
+                                // backup between chord notes can leave current_position before the
+                                // chord, in which case the backup decides where the next note goes.
+                                if current_position > last_position && tmp_note.duration < (current_position - last_position) {
+                                    shortened += current_position - (last_position + tmp_note.duration);
+                                    current_position = last_position + tmp_note.duration;
                                 }
-                                "long" => {
-                                    note.note_type = NoteType::Long;
+                            } else {
+                                last_position = current_position;
+                                current_position += tmp_note.duration;
+                            }
+                            let beam_start = tmp_note.beam_start;
+                            let beam_continue = tmp_note.beam_continue;
+                            let beam_stop = tmp_note.beam_stop;
+                            let index;
+                            if let Some(notes) = note_map.get_mut(&position) {
+                                index = notes.len();
+                                notes.push(tmp_note);
+                            } else {
+                                index = 0;
+                                note_map.insert(position, vec![tmp_note]);
+                            }
+                            if options.infer_beamed_tuplets && !is_chord {
+                                if beam_start {
+                                    beam_group.clear();
+                                    beam_group.push((position, index));
+                                } else if beam_continue && !beam_group.is_empty() {
+                                    beam_group.push((position, index));
                                 }
-                                "maxima" => {
-                                    note.note_type = NoteType::Maxima;
+                                if beam_stop && !beam_group.is_empty() {
+                                    Measure::infer_beamed_tuplet(&mut note_map, &beam_group, measures[0].attributes.divisions);
+                                    beam_group.clear();
                                 }
-                                _ => {}
                             }
                         }
-                        "duration" => {
-                            note.duration = parse_tag_value("duration", parser).parse::<u32>().unwrap();
-                        }
-                        "staff" => {
-                            note.staff = parse_tag_value("staff", parser).parse::<u8>().unwrap();
+                        "backup" => {
+                            // Backup allows for changing the current_position without using chord
+                            // tags
+                            loop {
+                                match parser.next() {
+                                    Ok(XmlEvent::StartElement {name, ..}) => {
+                                        if name.local_name.as_str() == "duration" {
+                                            let tmp_duration: u32 = parse_tag_number("duration", parser)?;
+                                            let tmp_duration = tmp_duration * measures[0].attributes.division_scale;
+                                            // The other staff or voice starts from where the file
+                                            // says, not from where shortened chords left off
+                                            current_position += shortened;
+                                            shortened = 0;
+                                            if current_position >= tmp_duration {
+                                                current_position -= tmp_duration;
+                                            } else {
+                                                current_position = 0;
+                                            }
+                                        }
+                                    }
+                                    Ok(XmlEvent::EndElement {name}) => {
+                                        if name.local_name.as_str() == "backup" {
+                                            break;
+                                        }
+                                    }
+                                    Err(e) => {
+                                        return Err(Error::Xml(e));
+                                    }
+                                    _ => {}
+                                }
+                            }
                         }
-                        "rest" => {
-                            note.is_rest = true;
+                        "forward" => {
+                            // Forward moves the current_position on without a note, leaving an
+                            // invisible rest that lines up a voice that starts part way through
+                            loop {
+                                match parser.next() {
+                                    Ok(XmlEvent::StartElement {name, ..}) => {
+                                        if name.local_name.as_str() == "duration" {
+                                            let tmp_duration: u32 = parse_tag_number("duration", parser)?;
+                                            let tmp_duration = tmp_duration * measures[0].attributes.division_scale;
+                                            current_position += tmp_duration;
+                                        }
+                                    }
+                                    Ok(XmlEvent::EndElement {name}) => {
+                                        if name.local_name.as_str() == "forward" {
+                                            break;
+                                        }
+                                    }
+                                    Err(e) => {
+                                        return Err(Error::Xml(e));
+                                    }
+                                    _ => {}
+                                }
+                            }
                         }
-                        "dot" => {
-                            note.dotted = true;
+                        "barline" => {
+                            let repeats = Repeats::parse_barline(parser)?;
+                            for measure in measures.iter_mut() {
+                                measure.repeats.merge(&repeats);
+                            }
                         }
-                        "notations" => {
+                        "direction" => {
+                            // Like tempos, a dynamic mark is only used when no sound sets the
+                            // volume, and either applies from where the direction is placed
+                            let mut sound_volume = None;
+                            let mut marked_volume = None;
+                            let mut offset: i64 = 0;
                             loop {
                                 match parser.next() {
                                     Ok(XmlEvent::StartElement {name, attributes, ..}) => {
-                                        match name.local_name.as_str() {
-                                            "arpeggiate" => {
-                                                note.arpeggiate = true;
-                                            }
-                                            "tuplet" => {
-                                                if !attributes.is_empty() {
-                                                    for attr in attributes {
-                                                        if attr.name.local_name.as_str() == "type" {
-                                                            if attr.value == "start" {
-                                                                note.triplet = true;
-                                                            }
+                                        let attributes = musicxml_attributes(attributes);
+                                        if name.local_name.as_str() == "sound" {
+                                            for attr in attributes {
+                                                match attr.name.local_name.as_str() {
+                                                    "dynamics" => {
+                                                        sound_volume = Some(parse_number::<f64>("sound dynamics", &attr.value)?.round() as u32);
+                                                    }
+                                                    "tempo" => {
+                                                        let tempo = parse_number::<f64>("sound tempo", &attr.value)?.round() as u32;
+                                                        for i in 0..measures.len() {
+                                                            measures[i].attributes.tempo = tempo;
+                                                            measures[i].tempo_declared = true;
                                                         }
                                                     }
-                                                }
-                                            }
-                                            "slur" => {
-                                                if !attributes.is_empty() {
-                                                    for attr in attributes {
-                                                        if attr.name.local_name.as_str() == "type" {
-                                                            if attr.value == "start" {
-                                                                note.slur_start = true;
-                                                            } else if attr.value == "stop" {
-                                                                note.slur_stop = true;
-                                                            }
+                                                    "pan" | "elevation" => {
+                                                        // GJM tracks have no stereo placement
+                                                        let degrees = parse_number::<f64>("sound placement", &attr.value)?;
+                                                        let placement = match attr.name.local_name.as_str() {
+                                                            "pan" => format!("a pan of {} degrees", degrees),
+                                                            _ => format!("an elevation of {} degrees", degrees),
+                                                        };
+                                                        warning!("GJM has no stereo placement, so {} is dropped", placement);
+                                                        for measure in measures.iter_mut() {
+                                                            measure.placements.push(placement.clone());
                                                         }
                                                     }
+                                                    // Direction has more tags, but they are either
+                                                    // for visual formatting or have no equivalent
+                                                    // in GJM
+                                                    _ => {}
                                                 }
                                             }
-                                            "tied" => {
-                                                if !attributes.is_empty() {
-                                                    for attr in attributes {
-                                                        if attr.name.local_name.as_str() == "type" {
-                                                            if attr.value == "start" {
-                                                                note.slur_start = true;
-                                                            } else if attr.value == "stop" {
-                                                                note.slur_stop = true;
-                                                            }
-                                                        }
-                                                    }
+                                        } else if name.local_name.as_str() == "dynamics" {
+                                            marked_volume = parse_dynamics(parser)?.or(marked_volume);
+                                        } else if name.local_name.as_str() == "offset" {
+                                            // Offsets are in divisions and may be negative
+                                            offset = parse_tag_number::<i64, _>("offset", parser)? * measures[0].attributes.division_scale as i64;
+                                        } else if name.local_name.as_str() == "metronome" {
+                                            if let Some(tempo) = parse_metronome(parser)? {
+                                                metronome_tempo = Some(tempo.round() as u32);
+                                            }
+                                        } else if name.local_name.as_str() == "words" {
+                                            // Swing is usually only marked with text
+                                            let words = parse_tag_value("words", parser)?.to_lowercase();
+                                            let swing = if words.contains("straight") || words.contains("even 8") {
+                                                Some(false)
+                                            } else if words.contains("swing") {
+                                                Some(true)
+                                            } else {
+                                                None
+                                            };
+                                            if let Some(swing) = swing {
+                                                for measure in measures.iter_mut() {
+                                                    measure.attributes.swing = swing;
                                                 }
                                             }
-                                            _ => {}
+                                        } else if name.local_name.as_str() == "swing" || name.local_name.as_str() == "straight" {
+                                            // A "straight" tag inside "swing" turns swing back off
+                                            for measure in measures.iter_mut() {
+                                                measure.attributes.swing = name.local_name.as_str() == "swing";
+                                            }
                                         }
                                     }
                                     Ok(XmlEvent::EndElement {name}) => {
-                                        if name.local_name.as_str() == "notations" {
+                                        if name.local_name.as_str() == "direction" {
+                                            if let Some(volume) = sound_volume.or(marked_volume) {
+                                                let position = (current_position as i64 + offset).max(0) as u32;
+                                                volume_changes.push((position, volume));
+                                            }
                                             break;
                                         }
                                     }
+                                    Err(e) => {
+                                        return Err(Error::Xml(e));
+                                    }
                                     _ => {}
                                 }
                             }
                         }
-                        _ => {}
+                        other => {
+                            trace!("Ignoring <{}>", other);
+                        }
+                    }
+                }
+                Ok(XmlEvent::EndElement {name, ..}) => {
+                    if name.local_name.as_str() == end_tag {
+                        if !grace_chords.is_empty() {
+                            warning!("Dropping {} grace chords at the end of the measure with no note to lead into", grace_chords.len());
+                        }
+                        // GJM has no ornaments, so the notes they are marked on are played plain
+                        let mut ornaments: Vec<&str> = note_map.values().flatten()
+                            .flat_map(|note| note.ornaments.iter().map(|ornament| ornament.kind.as_str()))
+                            .collect();
+                        ornaments.sort_unstable();
+                        ornaments.dedup();
+                        if !ornaments.is_empty() {
+                            warning!("Dropping the {} ornaments, which GJM does not support", ornaments.join(", "));
+                        }
+                        // Sound tempos are meant for playback, so a metronome mark is only used
+                        // when the measure has no sound tempo
+                        if let Some(tempo) = metronome_tempo {
+                            if !measures[0].tempo_declared {
+                                for measure in measures.iter_mut() {
+                                    measure.attributes.tempo = tempo;
+                                    measure.tempo_declared = true;
+                                }
+                            }
+                        }
+                        // Some exports put notes on extra staves without declaring "staves", so make
+                        // a measure for every staff a note is on
+                        let staff_count = note_map.values().flatten().map(|note| note.staff as usize).max().unwrap_or(1);
+                        if measures.len() < staff_count {
+                            warning!("Notes are on staff {} but only {} staves are declared", staff_count, measures.len());
+                        }
+                        while measures.len() < staff_count {
+                            let attributes = measures[0].attributes.clone();
+                            measures.push(Measure::from_attributes(attributes));
+                        }
+                        if options.chord_duration == ChordDuration::Split {
+                            Measure::split_mixed_chords(&mut note_map, measures[0].attributes.divisions);
+                        }
+                        // To finish parsing measures, turn the collection of notes into chords and
+                        // save those chords to their respective measures based on staff #
+                        let mut chords: Vec<Vec<Chord>> = vec![Vec::<Chord>::new()];
+                        // Create a list of chords for each staff
+                        for _i in 1..measures.len() {
+                            chords.push(Vec::<Chord>::new());
+                        }
+                        for (start, note_vec) in note_map {
+                            for mut note in note_vec {
+                                let staff_chords = &mut chords[(note.staff - 1) as usize];
+                                // GJM tracks are per staff, so each voice on a staff becomes its own
+                                // chord, and chords of different voices starting together are written
+                                // one after another in voice order
+                                let same_chord = staff_chords.iter_mut()
+                                    .rev()
+                                    .take_while(|chord| chord.start_time == start)
+                                    .find(|chord| chord.voice == note.voice);
+                                match same_chord {
+                                    Some(last_chord) => {
+                                        // GJM gives a chord a single duration, so a chord whose
+                                        // notes last different times takes the shortest by default.
+                                        // The next chord in the voice starts after the shortest
+                                        // note too, so this is the only length that neither leaves a
+                                        // gap nor overlaps it. Taking the longest instead keeps
+                                        // every note held, and splitting ties the rest of each
+                                        // longer note on, so the chords stay in order either way.
+                                        let replace = match options.chord_duration {
+                                            ChordDuration::Longest => last_chord.duration < note.duration,
+                                            _ => last_chord.duration > note.duration,
+                                        };
+                                        if replace {
+                                            last_chord.duration = note.duration;
+                                            last_chord.note_type = note.note_type;
+                                            last_chord.dots = note.dots;
+                                        }
+                                        // Split notes carry their ties into whichever chord they join
+                                        if options.chord_duration == ChordDuration::Split {
+                                            last_chord.tie_start |= note.tie_start;
+                                            last_chord.tie_stop |= note.tie_stop;
+                                        }
+                                        last_chord.slur_start |= note.slur_start;
+                                        last_chord.slur_stop |= note.slur_stop;
+                                        last_chord.fermata |= note.fermata;
+                                        last_chord.grace_chords.append(&mut note.grace_chords);
+                                        last_chord.notes.push(note);
+                                    }
+                                    None => {
+                                        let mut tmp_chord = Chord::new();
+                                        tmp_chord.start_time = start;
+                                        tmp_chord.duration = note.duration;
+                                        tmp_chord.note_type = note.note_type;
+                                        tmp_chord.dots = note.dots;
+                                        tmp_chord.is_rest = note.is_rest;
+                                        tmp_chord.arpeggiate = note.arpeggiate;
+                                        tmp_chord.arpeggio_down = note.arpeggio_down;
+                                        tmp_chord.fermata = note.fermata;
+                                        tmp_chord.triplet = note.triplet;
+                                        tmp_chord.tie_start = note.tie_start;
+                                        tmp_chord.tie_stop = note.tie_stop;
+                                        tmp_chord.slur_start = note.slur_start;
+                                        tmp_chord.slur_stop = note.slur_stop;
+                                        tmp_chord.voice = note.voice;
+                                        tmp_chord.grace_chords.append(&mut note.grace_chords);
+                                        tmp_chord.notes.push(note);
+                                        staff_chords.push(tmp_chord);
+                                    }
+                                }
+                            }
+                        }
+                        // Each chord is played at the volume in effect where it starts, and the
+                        // volume at the end of the measure carries on into the next one
+                        volume_changes.sort_by_key(|(position, _)| *position);
+                        for staff_chords in chords.iter_mut() {
+                            staff_chords.sort_by_key(|chord| (chord.start_time, chord.voice));
+                            for chord in staff_chords.iter_mut() {
+                                chord.volume = volume_changes.iter()
+                                    .take_while(|(position, _)| *position <= chord.start_time)
+                                    .last()
+                                    .map_or(start_volume, |(_, volume)| *volume);
+                            }
+                        }
+                        if let Some((_, volume)) = volume_changes.last() {
+                            for measure in measures.iter_mut() {
+                                measure.attributes.volume = *volume;
+                            }
+                        }
+                        for (i, measure) in measures.iter_mut().enumerate() {
+                            let changes: Vec<(u32, Clef, Clef)> = clef_changes.iter()
+                                .filter(|(staff, _, _, _)| *staff == i)
+                                .map(|(_, position, old, new)| (*position, *old, *new))
+                                .collect();
+                            let end = chords[i].iter().map(|chord| chord.start_time + chord.duration).max().unwrap_or(0);
+                            measure.shown_clef = Measure::calc_shown_clef(&changes, end.max(measure.get_full_duration()));
+                        }
+                        for i in 0..measures.len() {
+                            measures[i].chords.append(&mut chords[i]);
+                            if options.glissando == Glissando::Fill {
+                                measures[i].fill_glissandi();
+                            }
+                            measures[i].trim_overlapping_notes(options);
+                            measures[i].split_long_chords();
+                            measures[i].split_double_dotted_chords();
+                        }
+                        break;
+                    }
+                }
+                Err(e) => {
+                    return Err(Error::Xml(e));
+                }
+                _ => {}
+            }
+        }
+        Ok(measures)
+    }
+
+    /// Multiplies the positions and durations of the notes read so far in a measure, for when the
+    /// measure starts counting in a finer division
+    ///
+    /// # Arguments
+    ///
+    /// * 'note_map' - The notes of the measure keyed by start position
+    /// * 'factor'   - How many of the new divisions make up one of the old
+    ///
+    fn scale_positions(note_map: &mut BTreeMap<u32, Vec<Note>>, factor: u32) {
+        *note_map = std::mem::take(note_map).into_iter()
+            .map(|(position, mut notes)| {
+                for note in notes.iter_mut() {
+                    note.duration *= factor;
+                }
+                (position * factor, notes)
+            })
+            .collect();
+    }
+
+    /// Cuts every note that lasts longer than the shortest note it starts with on its staff down
+    /// to that length, and ties the rest of it on as a new note where the shorter note ends. This
+    /// keeps each chord the length of its shortest note while still holding the longer notes.
+    ///
+    /// # Arguments
+    ///
+    /// * 'note_map'  - The notes of the measure keyed by start position
+    /// * 'divisions' - The number of divisions per beat of the measure
+    ///
+    fn split_mixed_chords(note_map: &mut BTreeMap<u32, Vec<Note>>, divisions: u32) {
+        let mut next = note_map.keys().next().copied();
+        while let Some(position) = next {
+            // The rest of each cut note as (start, note)
+            let mut remainders = Vec::<(u32, Note)>::new();
+            let notes = note_map.get_mut(&position).unwrap();
+            for i in 0..notes.len() {
+                if notes[i].is_rest {
+                    continue;
+                }
+                let shortest = notes.iter()
+                    .filter(|note| !note.is_rest && note.staff == notes[i].staff && note.duration > 0)
+                    .map(|note| note.duration)
+                    .min()
+                    .unwrap_or(0);
+                if shortest == 0 || notes[i].duration <= shortest {
+                    continue;
+                }
+                let mut remainder = notes[i].clone();
+                remainder.duration -= shortest;
+                remainder.tie_stop = true;
+                remainder.arpeggiate = false;
+                remainder.grace_chords.clear();
+                let note = &mut notes[i];
+                note.duration = shortest;
+                note.tie_start = true;
+                for piece in [note, &mut remainder] {
+                    if let Some((note_type, dots)) = NoteType::from_quarter_length(piece.duration as f64 / divisions as f64) {
+                        piece.note_type = note_type;
+                        piece.dots = dots;
+                    }
+                }
+                remainders.push((position + shortest, remainder));
+            }
+            for (start, remainder) in remainders {
+                note_map.entry(start).or_default().push(remainder);
+            }
+            next = note_map.range(position + 1..).next().map(|(key, _)| *key);
+        }
+    }
+
+    /// Marks a beamed group of notes as a triplet when the group has no explicit tuplet but its
+    /// durations only fit the beat if three of its notes take the time of two. This is a heuristic
+    /// repair for exports that leave out "time-modification" and "tuplet", so it only runs when
+    /// enabled in the Options.
+    ///
+    /// # Arguments
+    ///
+    /// * 'note_map'    - The notes of the measure keyed by start position
+    /// * 'group'       - The (position, index) of each note in the beamed group
+    /// * 'divisions'   - The number of divisions per beat of the measure
+    ///
+    fn infer_beamed_tuplet(note_map: &mut BTreeMap<u32, Vec<Note>>, group: &[(u32, usize)], divisions: u32) {
+        let mut actual_dur = 0;
+        let mut nominal_dur = 0f64;
+        for (position, index) in group {
+            let note = &note_map[position][*index];
+            if note.triplet {
+                // An explicit tuplet is always trusted over the heuristic
+                return;
+            }
+            actual_dur += note.duration;
+            nominal_dur += note.get_nominal_duration(divisions);
+        }
+        // Three notes in the time of two leaves the group at two thirds of its written length
+        if group.len() > 1 && (actual_dur as f64 * 3.0 - nominal_dur * 2.0).abs() < 1.0 {
+            warning!("Inferred a triplet from a beamed group without a tuplet");
+            // A group of six or more even notes holds a triplet for every three, each marked at
+            // its first note like an explicit tuplet start, and any other group is one triplet
+            let step = match group.len() % 3 {
+                0 => 3,
+                _ => group.len(),
+            };
+            for (position, index) in group.iter().step_by(step) {
+                if let Some(notes) = note_map.get_mut(position) {
+                    notes[*index].triplet = true;
+                }
+            }
+        }
+    }
+
+    /// Replaces each single note that starts a glissando with a chromatic run up or down to the
+    /// note the glissando stops on. The run splits the note into a power of two of equal parts so
+    /// that each part still has a note type. Glissandi that don't stop in the same measure, or
+    /// that start on a chord, are left as they are.
+    fn fill_glissandi(&mut self) {
+        let mut chords = Vec::<Chord>::new();
+        for i in 0..self.chords.len() {
+            let chord = &self.chords[i];
+            if !chord.notes.iter().any(|note| note.glissando_start) {
+                chords.push(chord.clone());
+                continue;
+            }
+            let end = self.chords[i + 1..].iter()
+                .flat_map(|chord| chord.notes.iter())
+                .find(|note| note.glissando_stop);
+            let end = match end {
+                Some(end) if chord.notes.len() == 1 => end,
+                _ => {
+                    warning!("Could not fill the glissando, keeping its end notes");
+                    chords.push(chord.clone());
+                    continue;
+                }
+            };
+            let from = chord.notes[0].pitch_index as i32 + chord.notes[0].alter;
+            let to = end.pitch_index as i32 + end.alter;
+            let steps = (to - from).unsigned_abs() + 1;
+            let mut parts = 1;
+            // Stop splitting before a part would be shorter than a 32nd note
+            while parts * 2 <= steps && chord.duration * 8 >= self.attributes.divisions * parts * 2 && chord.duration.is_multiple_of(parts * 2) {
+                parts *= 2;
+            }
+            let part_dur = chord.duration / parts;
+            for k in 0..parts {
+                let mut piece = chord.clone();
+                piece.start_time = chord.start_time + k * part_dur;
+                piece.duration = part_dur;
+                if let Some((note_type, dots)) = NoteType::from_quarter_length(part_dur as f64 / self.attributes.divisions as f64) {
+                    piece.note_type = note_type;
+                    piece.dots = dots;
+                }
+                let pitch = from + ((to - from) as f64 * k as f64 / parts as f64).round() as i32;
+                let note = &mut piece.notes[0];
+                note.pitch_index = pitch.max(0) as u32;
+                note.alter = 0;
+                note.glissando_start = false;
+                if k > 0 {
+                    piece.tie_stop = false;
+                    piece.arpeggiate = false;
+                    piece.grace_chords.clear();
+                }
+                if k + 1 < parts {
+                    piece.tie_start = false;
+                }
+                chords.push(piece);
+            }
+        }
+        self.chords = chords;
+    }
+
+    /// Finds notes that are struck again while a note of the same pitch is still sounding on the
+    /// same staff, which GJM would play as a stuck note. Each one is warned about and, unless in
+    /// strict mode, the earlier note is cut short so it ends where the repeated note begins. A note
+    /// that shares its chord with notes that don't overlap is moved into a chord of its own, starting
+    /// with the chord, so only that note is cut.
+    ///
+    /// # Arguments
+    ///
+    /// * 'options' - The conversion options in use
+    ///
+    fn trim_overlapping_notes(&mut self, options: &Options) {
+        // The most recent chord each sounding pitch belongs to as (pitch, chord index)
+        let mut sounding = Vec::<(i32, usize)>::new();
+        // The notes to cut as (chord index, pitch, duration to cut it to)
+        let mut cuts = Vec::<(usize, i32, u32)>::new();
+        for i in 0..self.chords.len() {
+            if self.chords[i].is_rest {
+                continue;
+            }
+            let start = self.chords[i].start_time;
+            let pitches: Vec<i32> = self.chords[i].notes.iter().map(|note| note.pitch_index as i32 + note.alter).collect();
+            for pitch in pitches {
+                let earlier = match sounding.iter().position(|(p, _)| *p == pitch) {
+                    Some(pos) => sounding.remove(pos).1,
+                    None => {
+                        sounding.push((pitch, i));
+                        continue;
+                    }
+                };
+                let held = &self.chords[earlier];
+                // A pitch shared by voices starting together is a unison, not an overlap
+                if earlier != i && held.start_time < start && held.start_time + held.duration > start {
+                    warning!("Overlapping notes of pitch {} on one staff", pitch);
+                    if !options.strict {
+                        cuts.push((earlier, pitch, start - held.start_time));
+                    }
+                }
+                sounding.push((pitch, i));
+            }
+        }
+
+        // Cut from the last chord back so inserting chords doesn't move the ones still to cut
+        cuts.sort_by_key(|(index, _, _)| *index);
+        while let Some(&(index, _, _)) = cuts.last() {
+            let chord_cuts: Vec<(i32, u32)> = cuts.iter().filter(|(i, _, _)| *i == index).map(|(_, pitch, dur)| (*pitch, *dur)).collect();
+            cuts.retain(|(i, _, _)| *i != index);
+
+            let mut held = self.chords.remove(index);
+            let mut shortened = Vec::<Chord>::new();
+            for (pitch, duration) in chord_cuts {
+                let position = held.notes.iter().position(|note| note.pitch_index as i32 + note.alter == pitch);
+                let note = match position {
+                    Some(position) => held.notes.remove(position),
+                    None => continue,
+                };
+                // Notes cut to the same length share a chord
+                match shortened.iter_mut().find(|chord| chord.duration == duration) {
+                    Some(chord) => chord.notes.push(note),
+                    None => {
+                        let mut chord = held.clone();
+                        chord.notes = vec![note];
+                        chord.duration = duration;
+                        match NoteType::from_quarter_length(duration as f64 / self.attributes.divisions as f64) {
+                            Some((note_type, dots)) => {
+                                chord.note_type = note_type;
+                                chord.dots = dots;
+                            }
+                            None => {warning!("Could not work out a note type for a shortened note");}
+                        }
+                        shortened.push(chord);
+                    }
+                }
+            }
+            // The notes that don't overlap keep their chord and its length
+            let mut at = index;
+            if !held.notes.is_empty() {
+                self.chords.insert(at, held);
+                at += 1;
+            }
+            for chord in shortened {
+                self.chords.insert(at, chord);
+                at += 1;
+            }
+        }
+    }
+
+    /// Splits every chord that lasts longer than a whole note, which is the longest note GJM
+    /// supports, into whole note chords tied together, with any remainder tied on at the end. A
+    /// remainder no single note type can hold is split further into the longest notes that fit.
+    fn split_long_chords(&mut self) {
+        let whole_dur = self.attributes.divisions * 4;
+        if whole_dur == 0 {
+            return;
+        }
+        let divisions = self.attributes.divisions as f64;
+        let mut chords = Vec::<Chord>::new();
+        for chord in self.chords.drain(..) {
+            if chord.duration <= whole_dur {
+                chords.push(chord);
+                continue;
+            }
+            let mut start_time = chord.start_time;
+            let mut remaining = chord.duration;
+            while remaining > 0 {
+                let (duration, note_type, dots) = if remaining >= whole_dur {
+                    (whole_dur, NoteType::Whole, 0)
+                } else if let Some((note_type, dots)) = NoteType::from_quarter_length(remaining as f64 / divisions) {
+                    (remaining, note_type, dots)
+                } else {
+                    // Take the longest note that lasts a whole number of divisions and fits, and
+                    // leave the rest for the next piece
+                    let fits = |note_type: &NoteType| note_type.quarter_length() * divisions <= remaining as f64;
+                    let mut longest = NoteType::MUSICXML_TOKENS.iter().rev().map(|(note_type, _)| *note_type).filter(fits);
+                    match longest.clone().find(|note_type| (note_type.quarter_length() * divisions).fract() == 0.0) {
+                        Some(note_type) => ((note_type.quarter_length() * divisions) as u32, note_type, 0),
+                        None => {
+                            warning!("Could not work out a note type for the end of a split note");
+                            (remaining, longest.next().unwrap_or(NoteType::TenTwentyFourth), 0)
+                        }
+                    }
+                };
+                let mut piece = chord.clone();
+                piece.start_time = start_time;
+                piece.duration = duration;
+                piece.note_type = note_type;
+                piece.dots = dots;
+                // Tie the pieces together, keeping any tie into the note on the first piece and any
+                // tie out of the note on the last. Rests don't need ties.
+                let first = start_time == chord.start_time;
+                let last = remaining == duration;
+                piece.tie_stop = if first { chord.tie_stop } else { !chord.is_rest };
+                piece.tie_start = if last { chord.tie_start } else { !chord.is_rest };
+                if !first {
+                    piece.arpeggiate = false;
+                    piece.triplet = false;
+                    piece.grace_chords.clear();
+                }
+                start_time += duration;
+                remaining -= duration;
+                chords.push(piece);
+            }
+        }
+        self.chords = chords;
+    }
+
+    /// Splits chords with more than one dot, which GJM can't write, into a dotted chord tied to a
+    /// chord holding the length of the other dots, so that the written length stays the same
+    fn split_double_dotted_chords(&mut self) {
+        let mut chords = Vec::<Chord>::new();
+        for chord in self.chords.drain(..) {
+            if chord.dots < 2 {
+                chords.push(chord);
+                continue;
+            }
+            // The other dots add a fraction of the base length, such as a quarter for a double dot
+            let rest_length = chord.note_type.quarter_length() * (dot_factor(chord.dots) - dot_factor(1));
+            let (rest_type, rest_dots) = match NoteType::from_quarter_length(rest_length) {
+                Some(found) => found,
+                None => {
+                    warning!("Could not split a note with {} dots, writing it with one", chord.dots);
+                    let mut chord = chord;
+                    chord.dots = 1;
+                    chords.push(chord);
+                    continue;
+                }
+            };
+            // Scale the durations the same way as the note types so that tuplets keep their timing
+            let mut first = chord.clone();
+            first.dots = 1;
+            first.duration = (chord.duration as f64 * dot_factor(1) / dot_factor(chord.dots)).round() as u32;
+            let mut second = chord.clone();
+            second.note_type = rest_type;
+            second.dots = rest_dots;
+            second.start_time = chord.start_time + first.duration;
+            second.duration = chord.duration - first.duration;
+            // Tie the pieces together like a split long note, rests don't need ties
+            first.tie_start = !chord.is_rest;
+            second.tie_stop = !chord.is_rest;
+            second.arpeggiate = false;
+            second.grace_chords.clear();
+            chords.push(first);
+            chords.push(second);
+        }
+        self.chords = chords;
+    }
+
+    /// Get the number of note packs written for a measure. Each chord, rests included, is written
+    /// as a pack numbered from zero, so NotePackCount is always one more than the last pack's
+    /// number, as in the files this converter has always written.
+    fn get_note_pack_count(&self) -> usize {
+        self.chords.len()
+    }
+
+    /// Get the gjm duration value of a measure
+    ///
+    /// # Arguments
+    ///
+    /// * 'rounding' - How to round the end of the measure to a stamp
+    ///
+    fn get_duration_max(&self, rounding: Rounding) -> u32 {
+        // A measure without any notes still takes up its full length, and every staff of a pickup
+        // is as long as the longest one
+        if self.chords.is_empty() || self.implicit_duration.is_some() {
+            return self.get_full_duration_max();
+        }
+        // To convert to gjm we find the stamp the last chord of the measure ends on
+        let mxml_actual_dur = self.get_end_time();
+        // Subtract one because gjm expects the max start duration minus the minimum note length.
+        let mut duration_max = rounding.apply(self.get_stamp(mxml_actual_dur)) as u32;
+        if duration_max > 0 {
+            duration_max -= 1;
+        }
+        duration_max
+    }
+
+    /// Get the gjm duration value of a completely filled measure, ignoring the chords it holds
+    fn get_full_duration_max(&self) -> u32 {
+        if let Some(duration) = self.implicit_duration {
+            return (self.get_stamp(duration).round() as u32).saturating_sub(1);
+        }
+        let gjm_max_dur = (64 / self.attributes.beat_type as u32) * self.attributes.beats as u32;
+        if gjm_max_dur > 0 {
+            gjm_max_dur - 1
+        } else {
+            0
+        }
+    }
+
+    /// Get the unrounded gjm stamp a MusicXml position in the measure falls on. Divisions count
+    /// quarter notes whatever the meter, so the position is scaled by the 16 stamps of a quarter
+    /// note over the divisions of a quarter note.
+    ///
+    /// # Arguments
+    ///
+    /// * 'position' - The position in divisions from the start of the measure
+    ///
+    fn get_stamp(&self, position: u32) -> f64 {
+        // Multiply before dividing so a position that falls exactly on a stamp stays exact, even
+        // with the large divisions of high resolution exports, and use u64 so nothing overflows
+        (position as u64 * 16) as f64 / self.attributes.divisions as f64
+    }
+
+    /// Moves each chord that starts on the second eighth of a beat later, so that pairs of eighths
+    /// are played long-short. Chords in tuplets are left alone, and the length of the measure is
+    /// unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * 'stamps'   - The stamp each chord of the measure starts on
+    /// * 'ratio'    - How much longer the first eighth of each pair is than the second
+    /// * 'rounding' - How to round each moved position to a stamp
+    ///
+    fn swing_stamps(&self, stamps: &mut [u32], ratio: f64, rounding: Rounding) {
+        let beat = self.attributes.divisions;
+        if beat == 0 || beat % 2 == 1 {
+            return;
+        }
+        for (i, chord) in self.chords.iter().enumerate() {
+            let in_tuplet = chord.notes.iter().any(|note| note.tuplet_actual != note.tuplet_normal);
+            if in_tuplet || chord.start_time % beat != beat / 2 {
+                continue;
+            }
+            let beat_start = chord.start_time - beat / 2;
+            let swung = beat_start as f64 + beat as f64 * ratio / (ratio + 1.0);
+            // Work out the stamp from the unrounded position so nothing lands between divisions
+            let start = self.get_stamp(beat_start) + (self.get_stamp(beat_start + beat) - self.get_stamp(beat_start)) * (swung - beat_start as f64) / beat as f64;
+            stamps[i] = rounding.apply(start) as u32;
+        }
+    }
+
+    /// Get the division the last chord of the measure ends on
+    fn get_end_time(&self) -> u32 {
+        let mut end_time = 0;
+        for chord in self.chords.iter() {
+            if chord.start_time + chord.duration > end_time {
+                end_time = chord.start_time + chord.duration;
+            }
+        }
+        end_time
+    }
+
+    /// Get the gjm stamp index each chord of the measure starts on. Stamps are rounded from the
+    /// MusicXml position each chord starts on instead of adding up chord lengths, so rests and gaps
+    /// always take up their true duration, rounding errors can't build up and the last chord always
+    /// ends exactly on DurationStampMax + 1.
+    ///
+    /// # Arguments
+    ///
+    /// * 'rounding' - How to round each position to a stamp
+    ///
+    fn get_stamp_indexes(&self, rounding: Rounding) -> Vec<u32> {
+        let mut stamps = Vec::<u32>::new();
+        for chord in self.chords.iter() {
+            stamps.push(rounding.apply(self.get_stamp(chord.start_time)) as u32);
+        }
+        stamps
+    }
+
+    /// Get the number of stamps each chord lasts, in the same order as the chords
+    ///
+    /// # Arguments
+    ///
+    /// * 'rounding' - How to round each length to whole stamps
+    ///
+    fn get_stamp_lengths(&self, rounding: Rounding) -> Vec<u32> {
+        self.chords.iter().map(|chord| rounding.apply(self.get_stamp(chord.duration)) as u32).collect()
+    }
+}
+
+/// A collection of sets of measures that are considered the same Part by MusicXml but exist on different
+/// staves, requiring they be treated as seperate by GJM
+#[derive(Debug)]
+struct Part {
+    measures: Vec<Vec<Measure>>,
+    /// The full name of the part from the part list
+    name: Option<String>,
+    /// The abbreviated name of the part from the part list
+    abbreviation: Option<String>,
+    /// The name of the part's instrument from the part list
+    instrument_name: Option<String>,
+    /// The General MIDI program of the part's instrument from the part list
+    midi_program: Option<u32>,
+    /// The number of measures that could not be parsed and were replaced with rests
+    skipped_measures: usize,
+    /// The divisions to start new staves with in place of the default, if any
+    inherited_divisions: Option<u32>,
+    /// The number of measures of the current multiple rest still to come
+    rest_measures_left: u32,
+    /// The number of the last measure parsed, if it had one
+    last_number: Option<u32>,
+}
+
+impl Part {
+    /// Returns a default instantiation of a Part
+    fn new() -> Self {
+        Self {
+            measures: vec![Vec::<Measure>::new()],
+            name: None,
+            abbreviation: None,
+            instrument_name: None,
+            midi_program: None,
+            skipped_measures: 0,
+            inherited_divisions: None,
+            rest_measures_left: 0,
+            last_number: None,
+        }
+    }
+
+    /// Returns the label to give the part's tracks, falling back on the other kind of name when
+    /// the preferred one is missing
+    fn get_label(&self, options: &Options) -> Option<&String> {
+        match options.track_label {
+            TrackLabel::Name => self.name.as_ref().or(self.abbreviation.as_ref()),
+            TrackLabel::Abbreviation => self.abbreviation.as_ref().or(self.name.as_ref()),
+        }
+    }
+
+    /// Returns the label to give one of the part's tracks, which names the staff as well when the
+    /// part has more than one, such as "Piano (staff 2)"
+    ///
+    /// # Arguments
+    ///
+    /// * 'staff'   - The index of the staff the track is written from
+    /// * 'options' - The conversion options in use
+    ///
+    fn get_track_label(&self, staff: usize, options: &Options) -> Option<String> {
+        let label = self.get_label(options)?.replace('\n', " ");
+        if self.measures.len() > 1 {
+            Some(format!("{} (staff {})", label, staff + 1))
+        } else {
+            Some(label)
+        }
+    }
+
+    /// Returns the GJM instrument to play the part's tracks with. An instrument given in the options
+    /// is used for every part, otherwise the MIDI program is matched first and then the instrument
+    /// and part names, falling back on the piano.
+    fn get_instrument<'a>(&'a self, options: &'a Options) -> &'a str {
+        if let Some(instrument) = &options.instrument {
+            return instrument;
+        }
+        self.midi_program.and_then(instrument::from_midi_program)
+            .or_else(|| self.instrument_name.as_deref().and_then(instrument::from_name))
+            .or_else(|| self.name.as_deref().and_then(instrument::from_name))
+            .unwrap_or(instrument::DEFAULT)
+    }
+
+    /// Gives a part without any measures one empty measure, since GJM can't open a file with a
+    /// track that has no measures
+    fn pad_empty(&mut self) {
+        if self.measures[0].is_empty() {
+            self.measures[0].push(Measure::from_attributes(Attributes::new()));
+        }
+    }
+
+    /// Writes out the repeats of the part by copying every repeated measure to each place it is
+    /// played
+    ///
+    /// # Arguments
+    ///
+    /// * 'order' - The indexes of the measures in the order they are played, which comes from the
+    ///   first part so that the tracks stay lined up
+    ///
+    fn unfold_repeats(&mut self, order: &[usize]) {
+        for staff in self.measures.iter_mut() {
+            let mut unfolded = Vec::<Measure>::new();
+            for (n, i) in order.iter().enumerate() {
+                if let Some(measure) = staff.get(*i) {
+                    let mut measure = measure.clone();
+                    // Jumping back may leave a different tempo in place than the one the measure
+                    // is played at, so give the tempo again
+                    if n > 0 && order[n - 1] + 1 != *i {
+                        measure.tempo_declared = true;
+                    }
+                    unfolded.push(measure);
+                }
+            }
+            *staff = unfolded;
+        }
+    }
+
+    /// Places the unpitched notes that don't give a staff position at the MIDI key of their
+    /// instrument, so that each drum of a kit keeps its own pitch. Notes with no known instrument
+    /// stay on the middle line of the staff.
+    ///
+    /// # Arguments
+    ///
+    /// * 'unpitched_keys' - The MIDI key of each unpitched instrument of the part by instrument id
+    ///
+    fn place_unpitched_notes(&mut self, unpitched_keys: &BTreeMap<String, u32>) {
+        // Parts with a single instrument don't have to say which one plays each note
+        let only_key = if unpitched_keys.len() == 1 { unpitched_keys.values().next() } else { None };
+        let notes = self.measures.iter_mut().flatten()
+            .flat_map(|measure| measure.chords.iter_mut())
+            .flat_map(|chord| chord.notes.iter_mut().chain(chord.grace_chords.iter_mut().flatten()))
+            .filter(|note| note.unpitched && !note.displayed);
+        for note in notes {
+            let key = match &note.instrument {
+                Some(id) => unpitched_keys.get(id),
+                None => only_key,
+            };
+            // MIDI keys start 20 below piano keys, and black keys are written as sharps
+            match key.map(|key| *key as i32 - 20) {
+                Some(pitch) if (1..=88).contains(&pitch) => {
+                    let natural = if [2, 5, 7, 10, 0].contains(&(pitch % 12)) { pitch - 1 } else { pitch };
+                    note.pitch_index = natural as u32;
+                    note.alter = pitch - natural;
+                }
+                _ => {
+                    warning!("Unpitched note has no display position or known instrument, using B4");
+                }
+            }
+        }
+    }
+
+    /// Parses the tags and values inside of a "part" tag and returns a single part that may have
+    /// multiple parts by GJM standards
+    ///
+    /// # Arguments
+    ///
+    /// * 'parser'  - A mutable reference to the parser located inside the "part" tag
+    /// * 'options' - The conversion options in use
+    /// * 'inherited_divisions' - The divisions to start with in place of the default, if any
+    ///
+    fn parse_part<R: Read>(parser: &mut EventReader<R>, options: &Options, inherited_divisions: Option<u32>) -> Result<Self, Error> {
+        let mut part = Part::new();
+        part.inherited_divisions = inherited_divisions;
+        loop {
+            match parser.next() {
+                Ok(XmlEvent::StartElement {name, attributes, ..}) => {
+                    let attributes = musicxml_attributes(attributes);
+                    if name.local_name.as_str() == "measure" {
+                        let number = attributes.iter()
+                            .find(|attr| attr.name.local_name.as_str() == "number")
+                            .and_then(|attr| attr.value.parse::<u32>().ok());
+                        let implicit = is_implicit(&attributes);
+                        part.parse_measure(parser, number, implicit, "measure", options)?;
                     }
                 }
-                Ok(XmlEvent::EndElement {name}) => {
-                    if name.local_name.as_str() == "note" {
+                Ok(XmlEvent::EndElement {name, ..}) => {
+                    if name.local_name.as_str() == "part" {
                         break;
                     }
                 }
+                Err(e) => {
+                    return Err(Error::Xml(e));
+                }
                 _ => {}
             }
         }
-
-        (note, is_chord)
+        Ok(part)
     }
 
-    fn get_numbered_sign(&self) -> u32 {
-        // Each octave has 12 pitch indexes and octave starts at one, not zero.
-        let index = self.pitch_index % 12;
-        let mut value = 1;
-        // The note index is how many half steps from A flat the note is.
-        match index {
-            1 => {
-                value = 1;
+    /// Parses the contents of one measure of the part and adds a measure to each of its staves
+    ///
+    /// # Arguments
+    ///
+    /// * 'parser'  - A mutable reference to the parser located inside the tag holding the measure
+    /// * 'number'   - The number the score gives the measure, if any
+    /// * 'implicit' - Whether the measure is marked as not counting towards the meter, as pickups
+    ///   are
+    /// * 'end_tag'  - The label of the tag holding the measure, "measure" in partwise scores and
+    ///   "part" in timewise scores
+    /// * 'options'  - The conversion options in use
+    ///
+    fn parse_measure<R: Read>(&mut self, parser: &mut EventReader<R>, number: Option<u32>, implicit: bool, end_tag: &str, options: &Options) -> Result<(), Error> {
+        // Positions count lines from zero
+        let line = parser.position().row + 1;
+        // Some exports leave out the measures of a multiple rest after the first, so fill measure
+        // numbers that were skipped over with rests
+        if let (Some(number), Some(last)) = (number, self.last_number) {
+            let missing = number.saturating_sub(last + 1).min(self.rest_measures_left);
+            for _ in 0..missing {
+                for staff in self.measures.iter_mut() {
+                    if let Some(measure) = staff.last() {
+                        let rest = Measure::rest_from_attributes(measure.attributes.clone());
+                        staff.push(rest);
+                    }
+                }
             }
-            3 => {
-                value = 2;
+            self.rest_measures_left -= missing;
+        }
+        self.last_number = number;
+        log::set_measure(Some(self.measures[0].len()));
+        match number {
+            Some(number) => trace!("Reading measure number {}", number),
+            None => trace!("Reading a measure without a number"),
+        }
+        // Attributes carry over from one measure to the next if available
+        let mut attrs = Vec::<Attributes>::new();
+        for i in 0..self.measures.len() {
+            if !self.measures[i].is_empty() {
+                attrs.push(self.measures[i].last().unwrap().attributes.clone());
+            } else {
+                let mut attr = Attributes::new();
+                if let Some(divisions) = self.inherited_divisions {
+                    attr.divisions = divisions;
+                }
+                attrs.push(attr);
             }
-            4 => {
-                value = 3;
+        }
+        let mut tmp_measures: Vec<Measure> = match Measure::parse_measure(parser, attrs.clone(), end_tag, options) {
+            Ok(tmp_measures) => tmp_measures,
+            Err(Error::Xml(e)) => {
+                // There's no way to find the end of the measure in broken XML
+                return Err(Error::Xml(e));
             }
-            6 => {
-                value = 4;
+            Err(e) => {
+                // Report the measure by the number a reader would look it up by
+                let e = e.in_measure(number.map_or(self.measures[0].len() + 1, |number| number as usize));
+                if !options.keep_going {
+                    return Err(e);
+                }
+                // Keep the timeline intact by putting rests in place of the measure
+                warning!("Replacing measure with rests after an error: {}", e);
+                skip_tag(end_tag, parser)?;
+                self.skipped_measures += 1;
+                attrs.into_iter().map(Measure::rest_from_attributes).collect()
             }
-            8 => {
-                value = 5;
+        };
+        if implicit {
+            // A pickup only lasts as long as the notes it holds
+            let duration = tmp_measures.iter().map(|measure| measure.get_end_time()).max().unwrap_or(0);
+            if duration > 0 && duration < tmp_measures[0].get_full_duration() {
+                for measure in tmp_measures.iter_mut() {
+                    measure.implicit_duration = Some(duration);
+                }
             }
-            9 => {
-                value = 6;
+        }
+        if tmp_measures[0].attributes.multiple_rest > 0 {
+            self.rest_measures_left = tmp_measures[0].attributes.multiple_rest;
+            for measure in tmp_measures.iter_mut() {
+                measure.attributes.multiple_rest = 0;
             }
-            11 => {
-                value = 7;
+        }
+        if self.rest_measures_left > 0 {
+            self.rest_measures_left -= 1;
+            // Each measure of a multiple rest is one full measure rest sized by its own meter,
+            // which may change part way through the rest
+            for measure in tmp_measures.iter_mut() {
+                if measure.chords.iter().all(|chord| chord.is_rest) {
+                    let tempo_declared = measure.tempo_declared;
+                    let repeats = measure.repeats.clone();
+                    *measure = Measure::rest_from_attributes(measure.attributes.clone());
+                    measure.tempo_declared = tempo_declared;
+                    measure.repeats = repeats;
+                }
             }
-            _ => {}
         }
-        value
+        // A staff that first appears part way through is filled with rests up to here so it lines
+        // up with the other staves
+        while tmp_measures.len() > self.measures.len() {
+            let padding = self.measures[0].iter()
+                .map(|measure| Measure::rest_from_attributes(measure.attributes.clone()))
+                .collect();
+            self.measures.push(padding);
+        }
+        trace!("Read {} chords on {} staves", tmp_measures.iter().map(|measure| measure.chords.len()).sum::<usize>(), tmp_measures.len());
+        for (i, mut measure) in tmp_measures.into_iter().enumerate() {
+            measure.line = Some(line);
+            self.measures[i].push(measure);
+        }
+        log::set_measure(None);
+        Ok(())
     }
 
-    fn get_alterant_type(&self) -> &str {
-        let mut result = "";
-        match self.alter {
-            -1 => {result = "Flat";},
-            0 => {result = "Natural";},
-            1 => {result = "Sharp";},
-            _ => {},
+    /// Returns whether any measure of the part declares its divisions
+    fn declares_divisions(&self) -> bool {
+        match self.measures[0].last() {
+            Some(measure) => measure.attributes.divisions_declared,
+            None => false,
         }
-        result
     }
-}
 
-/// A collection of Notes that all begin on the same division
-#[derive(Clone, Debug)]
-struct Chord {
-    /// The Notes of the Chord
-    notes: Vec<Note>,
-    /// The division the chord begins on
-    start_time: u32,
-    duration: u32,
-    note_type: NoteType,
-    dotted: bool,
-    is_rest: bool,
-    arpeggiate: bool,
-    triplet: bool,
-    slur_start: bool,
-    slur_stop: bool,
-}
+    /// Writes one staff of the part as a GJM track
+    ///
+    /// # Arguments
+    ///
+    /// * 'file'      - The output to write to
+    /// * 'staff'     - The index of the staff to write
+    /// * 'part_idx'  - The index of the GJM track being written
+    /// * 'options'   - The conversion options in use
+    /// * 'humanizer' - The source of timing offsets for the chords
+    ///
+    fn write_track_gjn<W: OtherWrite>(&self, file: &mut W, staff: usize, part_idx: usize, options: &Options, humanizer: &mut Humanizer) -> std::io::Result<()> {
+        let part = &self.measures[staff];
+        let line = format!("{}[{}] = {{\n", indent(1), part_idx);
+        file.write_all(line.as_bytes())?;
 
-impl Chord {
-    /// Returns a default instantiation of a Chord
-    fn new() -> Self {
-        Self {
-            notes: Vec::<Note>::new(),
-            start_time: 0,
-            duration: 0,
-            note_type: NoteType::Quarter,
-            dotted: false,
-            is_rest: false,
-            arpeggiate: false,
-            triplet: false,
-            slur_start: false,
-            slur_stop: false,
+        // GJM has no field for track names, so label the track with a comment
+        if let Some(label) = self.get_track_label(staff, options) {
+            let line = format!("{}-- {}\n", indent(2), label);
+            file.write_all(line.as_bytes())?;
         }
-    }
 
-    fn gjm_note_string(&self) -> &str{
-        let mut value = "";
-        match self.note_type {
-            NoteType::ThirtySecond => {
-                value = "The32nd";
-            },
-            NoteType::Sixteenth => {
-                value = "The16th";
-            },
-            NoteType::Eighth => {
-                value = "Eighth";
-            },
-            NoteType::Quarter => {
-                value = "Quarter";
-            },
-            NoteType::Half => {
-                value = "Half";
-            },
-            NoteType::Whole => {
-                value = "Whole";
-            },
-            _ => {}
+        write_track_maps(file, part, self.get_instrument(options))?;
+
+        for (i, measure) in part.iter().enumerate() {
+            // Measure index
+            let line = format!("{}[{}] = {{\n", indent(2), i);
+            file.write_all(line.as_bytes())?;
+
+            // Duration of measure (expressed as divisions)
+            let line = format!("{}DurationStampMax = {},\n", indent(3), measure.get_duration_max(options.rounding));
+            file.write_all(line.as_bytes())?;
+
+            // Number of note packs, rests included
+            let line = format!("{}NotePackCount = {},\n", indent(3), measure.get_note_pack_count());
+            file.write_all(line.as_bytes())?;
+
+            let mut stamps = measure.get_stamp_indexes(options.rounding);
+            let swing = match options.swing {
+                Swing::Off => false,
+                Swing::Detect => measure.attributes.swing,
+                Swing::Force => true,
+            };
+            if swing {
+                measure.swing_stamps(&mut stamps, options.swing_ratio.unwrap_or(2.0), options.rounding);
+            }
+            humanizer.humanize(&mut stamps, &measure.get_stamp_lengths(options.rounding), measure.get_duration_max(options.rounding));
+            for (j, chord) in measure.chords.iter().enumerate() {
+                // Chord index
+                let line = format!("{}[{}] = {{\n", indent(3), j);
+                file.write_all(line.as_bytes())?;
+
+                // Add a line if chord is a rest and set notecount to zero for that chord. Muted notes
+                // aren't played, so a chord of only muted notes keeps its time as a rest.
+                let mut note_count = chord.notes.iter().filter(|note| !note.muted).count();
+                if chord.is_rest || note_count == 0 {
+                    let line = format!("{}IsRest = true,\n", indent(4));
+                    file.write_all(line.as_bytes())?;
+                    note_count = 0;
+                }
+
+                // Add ties, slurs have nothing to be written as in GJM
+                if chord.tie_start && chord.tie_stop {
+                    let line = format!("{}TieType ='Both',\n", indent(4));
+                    file.write_all(line.as_bytes())?;
+                } else if chord.tie_start {
+                    let line = format!("{}TieType ='Start',\n", indent(4));
+                    file.write_all(line.as_bytes())?;
+                } else if chord.tie_stop {
+                    let line = format!("{}TieType ='End',\n", indent(4));
+                    file.write_all(line.as_bytes())?;
+                }
+
+                // Add a line if chord is dotted, chords with more dots were split into a dotted
+                // chord and a tied chord while parsing
+                if chord.dots > 0 {
+                    let line = format!("{}IsDotted = true,\n", indent(4));
+                    file.write_all(line.as_bytes())?;
+                }
+
+                // Triplet if appropriate (any tuple is a triplet for now)
+                if chord.triplet {
+                    let line = format!("{}Triplet = true,\n", indent(4));
+                    file.write_all(line.as_bytes())?;
+                }
+
+                // Duration type is just string version of note type
+                let line = format!("{}DurationType = '{}',\n", indent(4), chord.gjm_note_string(i));
+                file.write_all(line.as_bytes())?;
+                
+                // Arpeggiate if appropriate, upward unless marked otherwise. GJM has no setting for
+                // how fast the chord rolls, so it always rolls at its own rate
+                if chord.arpeggiate {
+                    let mode = if chord.arpeggio_down { "Downward" } else { "Upward" };
+                    let line = format!("{}ArpeggioMode ='{}',\n", indent(4), mode);
+                    file.write_all(line.as_bytes())?;
+                }
+
+                let line = format!("{}StampIndex = {},\n", indent(4), stamps[j]);
+                file.write_all(line.as_bytes())?;
+
+                // PitchSignCount is just how many notes are in the chord
+                let line = format!("{}ClassicPitchSignCount = {},\n", indent(4), note_count);
+                file.write_all(line.as_bytes())?;
+
+                if note_count > 0 {
+                    let line = format!("{}ClassicPitchSign = {{\n", indent(4));
+                    file.write_all(line.as_bytes())?;
+                    for note in chord.notes.iter().filter(|note| !note.muted) {
+                        let numbered_sign = match options.numbered_notation {
+                            NumberedNotation::Fixed => note.get_numbered_sign(),
+                            NumberedNotation::Movable => note.get_movable_numbered_sign(measure.attributes.key),
+                        };
+                        let line = format!("{}[{}] = {{ NumberedSign = {}, PlayingPitchIndex = {}, AlterantType = '{}', RawAlterantType = '{}', }},\n",
+                            indent(5),
+                            note.pitch_index,
+                            numbered_sign,
+                            note.pitch_index as i32 + note.alter,
+                            note.get_alterant_type(),
+                            note.get_alterant_type(),
+                        );
+                        file.write_all(line.as_bytes())?;
+                    }
+                    let line = format!("{}}},\n", indent(4));
+                    file.write_all(line.as_bytes())?;
+                }
+
+                // Close the chord
+                let line = format!("{}}},\n", indent(3));
+                file.write_all(line.as_bytes())?;
+            }
+            // Close the measure
+            let line = format!("{}}},\n", indent(2));
+            file.write_all(line.as_bytes())?;
         }
-        value
+
+        // Close the part
+        let line = format!("{}}},\n", indent(1));
+        file.write_all(line.as_bytes())?;
+        Ok(())
     }
 
-    fn gjm_duration(&self, ratio: f64) -> u32 {
-        (self.duration as f64 * ratio).round() as u32
+    /// Returns the average pitch index of the notes on a staff, or None if it has no notes
+    ///
+    /// # Arguments
+    ///
+    /// * 'staff' - The index of the staff
+    ///
+    fn get_average_pitch(&self, staff: usize) -> Option<f64> {
+        let mut total = 0f64;
+        let mut count = 0;
+        for measure in self.measures[staff].iter() {
+            for chord in measure.chords.iter().filter(|chord| !chord.is_rest) {
+                for note in chord.notes.iter() {
+                    total += note.pitch_index as f64 + note.alter as f64;
+                    count += 1;
+                }
+            }
+        }
+        if count == 0 {
+            None
+        } else {
+            Some(total / count as f64)
+        }
     }
 }
 
-/// Enumerated Clef sign values
-#[derive(Clone, Debug, Copy, PartialEq)]
-enum Clef {
-    F,  // Treble Clef
-    G,  // Bass Clef
+/// A bracket or brace joining parts of the part list that belong together, such as the staves of
+/// a piano or the strings of an orchestra. GJM has no way to group tracks, so groups are only
+/// kept for whoever uses the parsed score.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PartGroup {
+    /// The name printed beside the group, if any
+    pub name: Option<String>,
+    /// The symbol joining the group's staves, i.e. "brace", "bracket", "line" or "square", if any
+    pub symbol: Option<String>,
+    /// The indexes of the parts in the group, in the order they are in the score
+    pub parts: Vec<usize>,
 }
 
-/// A collection of attributes that apply to measures
+/// The names given to a part by a "score-part" tag in the part list
 #[derive(Clone, Debug)]
-struct Attributes {
-    /// Number of divisions per beat
-    divisions: u32,
-    /// Volume out of 100
-    volume: u32,
-    /// Beats per minute
-    tempo: u32,
-    /// The major key represented by a shift from C Major, i.e. Bflat Major would have key = -2
-    key: i32,
-    /// The number of beats per measure (the top of the key signature)
-    beats: u8,
-    /// What type of note counts as a beat (the bottom of the key signature)
-    beat_type: u8,
-    /// What Clef the associated measure uses
-    clef: Clef,
+struct ScorePart {
+    name: Option<String>,
+    abbreviation: Option<String>,
+    /// The name of the first instrument of the part
+    instrument_name: Option<String>,
+    /// The General MIDI program of the first instrument of the part
+    midi_program: Option<u32>,
+    /// The MIDI key of each unpitched instrument of the part by instrument id, which for drum
+    /// kits says which drum the instrument is
+    unpitched_keys: BTreeMap<String, u32>,
+    /// The indexes of the score's part groups that the part belongs to
+    groups: Vec<usize>,
 }
 
-impl Attributes {
-    /// Returns a deafult instantiation of the Attributes type
-    fn new() -> Self {
-        Self {
-            divisions: 24,
-            volume: 80,
-            tempo: 108,
-            key: 0,
-            beats: 4,
-            beat_type: 4,
-            clef: Clef::G,
-        }
-    }
-
-    /// Parses the tags and values inside of the "attributes" tag, returning a number of Attribute
-    /// structures equal to the number of staves present or the number provided by the caller,
-    /// whichever is higher
+impl ScorePart {
+    /// Parses the tags and values inside of a "score-part" tag
     ///
     /// # Arguments
     ///
-    /// * 'parser' - A mutable reference to the parser located inside the "attributes" tag
-    /// * 'attribute_list' - a mutable vector of attributes to use as a baseline
+    /// * 'parser' - A mutable reference to the parser located inside the "score-part" tag
     ///
-    fn parse_attributes(parser: &mut EventReader<BufReader<File>>, mut attribute_list: Vec<Self>) -> Vec<Self> {
-        if attribute_list.is_empty() {
-            attribute_list.push(Self::new());
-        }
+    fn parse_score_part<R: Read>(parser: &mut EventReader<R>) -> Result<Self, Error> {
+        let mut score_part = Self {
+            name: None,
+            abbreviation: None,
+            instrument_name: None,
+            midi_program: None,
+            unpitched_keys: BTreeMap::<String, u32>::new(),
+            groups: Vec::<usize>::new(),
+        };
+        let mut instrument_id = "".to_string();
         loop {
             match parser.next() {
                 Ok(XmlEvent::StartElement {name, attributes, ..}) => {
-                    match name.local_name.as_str() {
-                        "divisions" => {
-                            let divisions: u32 = parse_tag_value("divisions", parser).parse::<u32>().unwrap();
-                            for i in 0..attribute_list.len() {
-                                attribute_list[i].divisions = divisions;
-                            }
-                        }
-                        "key" => {
-                            loop {
-                                match parser.next() {
-                                    Ok(XmlEvent::StartElement{name,..}) => {
-                                        match name.local_name.as_str() {
-                                            "fifths" => {
-                                                let key: i32 = parse_tag_value("fifths", parser).parse::<i32>().unwrap();
-                                                for i in 0..attribute_list.len() {
-                                                    attribute_list[i].key = key;
-                                                }
-                                            }
-                                            _ => {}
-                                        }
-                                    }
-                                    Ok(XmlEvent::EndElement{name}) => {
-                                        if name.local_name.as_str() == "key" {
-                                            break;
-                                        }
-                                    }
-                                    _ => {}
-                                }
-                            }
-                        }
-                        "time" => {
-                            loop {
-                                match parser.next() {
-                                    Ok(XmlEvent::StartElement{name, ..}) => {
-                                        match name.local_name.as_str() {
-                                            "beats" => {
-                                                let beats: u8 = parse_tag_value("beats", parser).parse::<u8>().unwrap();
-                                                for i in 0..attribute_list.len() {
-                                                    attribute_list[i].beats = beats;
-                                                }
-                                            }
-                                            "beat-type" => {
-                                                let beat_type: u8 = parse_tag_value("beat-type", parser).parse::<u8>().unwrap();
-                                                for i in 0..attribute_list.len() {
-                                                    attribute_list[i].beat_type = beat_type;
-                                                }
-                                            }
-                                            _ => {}
-                                        }
-                                    }
-                                    Ok(XmlEvent::EndElement{name}) => {
-                                        if name.local_name.as_str() == "time" {
-                                            break;
-                                        }
-                                    }
-                                    _ => {}
-                                }
-                            }
-                        }
-                        "staves" => {
-                            let staves = parse_tag_value("staves", parser).parse::<u8>().unwrap();
-                            // Don't add extra attribute sets unless number of staves is >= 2
-                            for i in 1..staves {
-                                if i as usize >= attribute_list.len() {
-                                    let next_attr = attribute_list[0].clone();
-                                    attribute_list.push(next_attr);
-                                }
-                            }
-                        }
-                        "clef" => {
-                            // Assume this refers to the first staff unless otherwise specified
-                            let mut index = 1;
-                            // In a single staff Part there are no attributes to the clef tag
-                            if !attributes.is_empty() {
-                                for attr in attributes {
-                                    if attr.name.local_name.as_str() == "number" {
-                                        index = attr.value.parse().unwrap();
-                                    }
-                                }
-                            }
-                            loop {
-                                match parser.next() {
-                                    Ok(XmlEvent::StartElement {name, ..}) => {
-                                        if name.local_name.as_str() == "sign" {
-                                            match parse_tag_value("sign", parser).as_str() {
-                                                "G" => {
-                                                    attribute_list[index - 1].clef = Clef::G;
-                                                }
-                                                "F" => {
-                                                    attribute_list[index - 1].clef = Clef::F;
-                                                }
-                                                _ => {println!("Unrecognized Clef value");}
-                                            }
-                                        }
-                                    }
-                                    Ok(XmlEvent::EndElement {name}) => {
-                                        if name.local_name.as_str() == "clef" {
-                                            break;
-                                        }
-                                    }
-                                    _ => {}
-                                }
-                            }
+                    match name.local_name.as_str() {
+                        "midi-instrument" => {
+                            instrument_id = attributes.into_iter()
+                                .find(|attr| attr.name.local_name.as_str() == "id")
+                                .map_or("".to_string(), |attr| attr.value);
+                        }
+                        "midi-unpitched" => {
+                            let key = parse_tag_number("midi-unpitched", parser)?;
+                            score_part.unpitched_keys.insert(instrument_id.clone(), key);
+                        }
+                        "part-name" => {
+                            score_part.name = Some(parse_tag_value("part-name", parser)?);
+                        }
+                        "part-abbreviation" => {
+                            score_part.abbreviation = Some(parse_tag_value("part-abbreviation", parser)?);
+                        }
+                        // Parts with several instruments are played on the first one
+                        "instrument-name" if score_part.instrument_name.is_none() => {
+                            score_part.instrument_name = Some(parse_tag_value("instrument-name", parser)?);
+                        }
+                        "midi-program" if score_part.midi_program.is_none() => {
+                            score_part.midi_program = Some(parse_tag_number("midi-program", parser)?);
                         }
                         _ => {}
                     }
                 }
-                Ok(XmlEvent::EndElement {name, ..}) => {
-                    if name.local_name.as_str() == "attributes" {
+                Ok(XmlEvent::EndElement {name}) => {
+                    if name.local_name.as_str() == "score-part" {
                         break;
                     }
                 }
+                Err(e) => {
+                    return Err(Error::Xml(e));
+                }
                 _ => {}
             }
         }
-        attribute_list
+        Ok(score_part)
     }
 }
 
-/// A collection of Chords and a set of Attributes that represent a single Measure of a single Part
-#[derive(Clone, Debug)]
-struct Measure {
-    chords: Vec<Chord>,
-    attributes: Attributes,
+/// Counts the streamed tracks files made by this process, so that conversions running at the same
+/// time in batch mode each get their own file
+static STREAMED_FILE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// The tracks of a score written to a temporary file as each part is parsed, since the header of
+/// the score that comes before them can only be written once every part has been read. The file
+/// is removed when the score is dropped.
+#[derive(Debug)]
+struct StreamedTracks {
+    /// The path of the temporary file
+    path: PathBuf,
+    file: BufWriter<File>,
+    /// The number of tracks written or dropped so far, including the conductor track
+    track_count: usize,
+    /// The parts and staves that didn't fit in the tracks GJM supports
+    dropped: Vec<String>,
+    humanizer: Humanizer,
+    /// The order the first part plays its measures in, used to unfold the repeats of every part
+    repeat_order: Option<Vec<usize>>,
 }
 
-impl Measure {
-    /// Returns an instance of a measure using a set of Attributes as the base
+impl StreamedTracks {
+    /// Creates an empty temporary file for the tracks
     ///
     /// # Arguments
     ///
-    /// * 'attr' - the Attributes structure to use in the measure
+    /// * 'options' - The conversion options in use
     ///
-    fn from_attributes(attr: Attributes) -> Self {
-        Self {
-            chords: Vec::<Chord>::new(),
-            attributes: attr,
+    fn create(options: &Options) -> std::io::Result<Self> {
+        let count = STREAMED_FILE_COUNT.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("mxl_2_solo-{}-{}.tracks", std::process::id(), count));
+        Ok(Self {
+            file: BufWriter::new(File::create(&path)?),
+            path,
+            // The conductor track is written first, when the rest of the score is written
+            track_count: options.conductor_track as usize,
+            dropped: Vec::<String>::new(),
+            humanizer: Humanizer::new(options.seed, options.humanize),
+            repeat_order: None,
+        })
+    }
+}
+
+impl Drop for StreamedTracks {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// A chord as it was written to a GJM document, read back to find what writing it changed
+#[derive(Debug, Default)]
+struct WrittenChord {
+    is_rest: bool,
+    dotted: bool,
+    triplet: bool,
+    duration_type: String,
+    stamp: u32,
+    /// The playing pitch index of each note, in the order they were written
+    pitches: Vec<i32>,
+}
+
+/// Reads the chords of every track back out of a GJM document written by this program, as a list
+/// of tracks that each hold a list of measures. Only the layout this program writes is understood.
+///
+/// # Arguments
+///
+/// * 'gjm' - The GJM document
+///
+fn read_written_tracks(gjm: &str) -> Vec<Vec<Vec<WrittenChord>>> {
+    let mut tracks = Vec::<Vec<Vec<WrittenChord>>>::new();
+    let mut in_tracks = false;
+    for line in gjm.lines() {
+        if line.starts_with("Notation.RegularTracks") {
+            in_tracks = true;
+            continue;
+        }
+        if !in_tracks {
+            continue;
+        }
+        // Tracks, measures and chords are told apart by how deeply they are indented
+        let depth = line.len() - line.trim_start_matches('\t').len();
+        let line = line.trim();
+        if line.starts_with('[') && line.ends_with("= {") {
+            if depth == 1 {
+                tracks.push(Vec::new());
+            } else if let Some(track) = tracks.last_mut() {
+                if depth == 2 {
+                    track.push(Vec::new());
+                } else if let (3, Some(measure)) = (depth, track.last_mut()) {
+                    measure.push(WrittenChord::default());
+                }
+            }
+            continue;
+        }
+        let chord = match tracks.last_mut().and_then(|track| track.last_mut()).and_then(|measure| measure.last_mut()) {
+            Some(chord) => chord,
+            None => continue,
+        };
+        if depth == 4 {
+            if let Some((key, value)) = line.split_once('=') {
+                let value = value.trim().trim_end_matches(',').trim_matches('\'');
+                match key.trim() {
+                    "IsRest" => chord.is_rest = value == "true",
+                    "IsDotted" => chord.dotted = value == "true",
+                    "Triplet" => chord.triplet = value == "true",
+                    "DurationType" => chord.duration_type = value.to_string(),
+                    "StampIndex" => chord.stamp = value.parse().unwrap_or_default(),
+                    _ => {}
+                }
+            }
+        } else if depth == 5 {
+            if let Some((_, pitch)) = line.split_once("PlayingPitchIndex = ") {
+                if let Some(Ok(pitch)) = pitch.split(',').next().map(str::parse::<i32>) {
+                    chord.pitches.push(pitch);
+                }
+            }
         }
     }
+    tracks
+}
 
-    /// Parse a MusicXml measure and return a list of single staff measures
+/// The features that validating a score finds, but comparing the written output with the score
+/// finds more precisely, so a lossless check only takes them from the comparison
+const COMPARED_FEATURES: [&str; 3] = ["track count", "short notes", "long notes"];
+
+/// A collection of parts
+#[derive(Debug)]
+pub struct Score {
+    parts: Vec<Part>,
+    /// The title of the work, or of the movement when the work has none
+    title: Option<String>,
+    /// The composers of the work, separated by commas
+    composer: Option<String>,
+    /// The groups of parts in the part list, in the order they start
+    groups: Vec<PartGroup>,
+    /// The tracks already written while parsing, in which case the parts only keep the attributes
+    /// of their measures
+    streamed: Option<StreamedTracks>,
+}
+
+impl Score {
+    /// Returns a default instantiation of a Score
+    pub(crate) fn new() -> Self {
+        Self {parts: Vec::<Part>::new(), title: None, composer: None, groups: Vec::<PartGroup>::new(), streamed: None}
+    }
+
+    /// Parses the tags and values of an entire partwise score
     ///
     /// # Arguments
     ///
-    /// * 'parser'  - A mutable reference to the parser located inside the "measure" tag
-    /// * 'attrs'   - A list of Attributes to use as the base attributes of any parsed measures
+    /// * 'parser'  - A mutable reference to the parser located inside the "score-partwise" tag
+    /// * 'options' - The conversion options in use
+    /// * 'stream'  - Whether to write each part as soon as it is parsed
     ///
-    fn parse_measure(parser: &mut EventReader<BufReader<File>>, attrs: Vec<Attributes>) -> Vec<Self> {
-        let mut measures: Vec<Self> = Vec::<Self>::new();
-        // Use a BTreeMap to group notes by start location and also sort chords by start location
-        let mut note_map: BTreeMap<u32, Vec<Note>> = BTreeMap::new();
-        let mut current_position: u32 = 0;
-        let mut last_position: u32 = 0;
-
-        // Clone so we're not borrowing the moved attr
-        for attr in attrs.clone() {
-            measures.push(Measure::from_attributes(attr));
-        }
+    fn parse_score<R: Read>(parser: &mut EventReader<R>, options: &Options, stream: bool) -> Result<Self, Error> {
+        let mut score = Score::new();
+        // The part list comes before the parts, so keep the names of each part by id until then
+        let mut score_parts = BTreeMap::<String, ScorePart>::new();
+        // The groups started but not yet stopped in the part list as (number, group index)
+        let mut open_groups = Vec::<(String, usize)>::new();
         loop {
             match parser.next() {
-                Ok(XmlEvent::StartElement {name, ..}) => {
+                Ok(XmlEvent::StartElement {name, attributes, ..}) => {
+                    let attributes = musicxml_attributes(attributes);
+                    let mut id = "".to_string();
+                    for attr in attributes.iter() {
+                        if attr.name.local_name.as_str() == "id" {
+                            id = attr.value.clone();
+                        }
+                    }
                     match name.local_name.as_str() {
-                        "attributes" => {
-                            let tmp_attributes = Attributes::parse_attributes(parser, attrs.clone());
-                            // Attributes will tell us how many staves we have, make a measure for
-                            // each one
-                            if measures.len() < tmp_attributes.len() {
-                                for i in 0.. measures.len() {
-                                    measures[i].attributes = tmp_attributes[i].clone();
-                                }
-                                for i in measures.len()..tmp_attributes.len() {
-                                    measures.push(Measure::from_attributes(tmp_attributes[i].clone()));
-                                }
-                            } else {
-                                for i in 0..tmp_attributes.len() {
-                                    measures[i].attributes = tmp_attributes[i].clone();
-                                }
-                            }
+                        "work-title" | "movement-title" | "creator" => {
+                            score.parse_header_tag(&name.local_name, &attributes, parser)?;
                         }
-                        "note" => {
-                            let (tmp_note, is_chord) = Note::parse_note(parser);
-                            // Assume position will be current_position
-                            let mut position = current_position;
-                            if is_chord {
-                                // If it's part of a chord just put it in the last position
-                                position = last_position;
-                                // current_position won't change unless we have different durations
-                                // in the same chord, in which case use the smaller duration
-                                if tmp_note.duration < (current_position - last_position) {
-                                    current_position = last_position + tmp_note.duration;
-                                }
-                            } else {
-                                last_position = current_position;
-                                current_position += tmp_note.duration;
-                            }
-                            if let Some(notes) = note_map.get_mut(&position) {
-                                notes.push(tmp_note);
-                            } else {
-                                note_map.insert(position, vec![tmp_note]);
-                            }
+                        "part-group" => {
+                            score.parse_part_group(&attributes, parser, &mut open_groups)?;
                         }
-                        "backup" => {
-                            // Backup allows for changing the current_position without using chord
-                            // tags
-                            loop {
-                                match parser.next() {
-                                    Ok(XmlEvent::StartElement {name, ..}) => {
-                                        if name.local_name.as_str() == "duration" {
-                                            let tmp_duration = parse_tag_value("duration", parser).parse::<u32>().unwrap();
-                                            if current_position >= tmp_duration {
-                                                current_position -= tmp_duration;
-                                            } else {
-                                                current_position = 0;
-                                            }
-                                        }
-                                    }
-                                    Ok(XmlEvent::EndElement {name}) => {
-                                        if name.local_name.as_str() == "backup" {
-                                            break;
-                                        }
-                                    }
-                                    _ => {}
-                                }
-                            }
+                        "score-part" => {
+                            let mut score_part = ScorePart::parse_score_part(parser)?;
+                            score_part.groups = open_groups.iter().map(|(_, group)| *group).collect();
+                            score_parts.insert(id, score_part);
                         }
-                        "direction" => {
-                            loop {
-                                match parser.next() {
-                                    Ok(XmlEvent::StartElement {name, attributes, ..}) => {
-                                        if name.local_name.as_str() == "sound" {
-                                            for attr in attributes {
-                                                match attr.name.local_name.as_str() {
-                                                    "dynamics" => {
-                                                        let vol = attr.value.parse::<f64>().unwrap().round() as u32;
-                                                        for i in 0..measures.len() {
-                                                            measures[i].attributes.volume = vol;
-                                                        }
-                                                    }
-                                                    "tempo" => {
-                                                        let tempo = attr.value.parse::<f64>().unwrap().round() as u32;
-                                                        for i in 0..measures.len() {
-                                                            measures[i].attributes.tempo = tempo;
-                                                        }
-                                                    }
-                                                    // Direction has more tags but they are
-                                                    // normally for visual formatting
-                                                    _ => {}
-                                                }
-                                            }
-                                        }
-                                    }
-                                    Ok(XmlEvent::EndElement {name}) => {
-                                        if name.local_name.as_str() == "direction" {
-                                            break;
-                                        }
-                                    }
-                                    _ => {}
-                                }
+                        "part" => {
+                            log::set_part(Some(score.parts.len()));
+                            trace!("Reading part '{}'", id);
+                            let inherited_divisions = score.get_inherited_divisions();
+                            let part = Part::parse_part(parser, options, inherited_divisions)?;
+                            score.add_part(part, score_parts.get(&id), options)?;
+                            log::set_part(None);
+                            if stream {
+                                score.stream_last_part(options)?;
                             }
                         }
                         _ => {}
                     }
                 }
                 Ok(XmlEvent::EndElement {name, ..}) => {
-                    if name.local_name.as_str() == "measure" {
-                        // To finish parsing measures, turn the collection of notes into chords and
-                        // save those chords to their respective measures based on staff #
-                        let mut chords: Vec<Vec<Chord>> = vec![Vec::<Chord>::new()];
-                        // Create a list of chords for each staff
-                        for _i in 1..measures.len() {
-                            chords.push(Vec::<Chord>::new());
+                    if name.local_name.as_str() == "score-partwise" {
+                        break;
+                    }
+                }
+                Ok(XmlEvent::EndDocument) => {
+                    break;
+                }
+                Err(e) => {
+                    return Err(Error::Xml(e));
+                }
+                _ => {}
+            }
+        }
+        score.finish(options)?;
+        Ok(score)
+    }
+
+    /// Parses the tags and values of an entire timewise score, where each measure holds a "part"
+    /// tag for every part instead of each part holding its measures. The measures are gathered
+    /// into the same parts a partwise score would give.
+    fn parse_timewise_score<R: Read>(parser: &mut EventReader<R>, options: &Options) -> Result<Self, Error> {
+        let mut score = Score::new();
+        let mut score_parts = BTreeMap::<String, ScorePart>::new();
+        let mut open_groups = Vec::<(String, usize)>::new();
+        // The parts are kept in the order they first appear along with their ids
+        let mut parts = Vec::<(String, Part)>::new();
+        let mut number: Option<u32> = None;
+        let mut implicit = false;
+        loop {
+            match parser.next() {
+                Ok(XmlEvent::StartElement {name, attributes, ..}) => {
+                    let attributes = musicxml_attributes(attributes);
+                    let mut id = "".to_string();
+                    for attr in attributes.iter() {
+                        if attr.name.local_name.as_str() == "id" {
+                            id = attr.value.clone();
                         }
-                        for (start, note_vec) in note_map {
-                            for note in note_vec {
-                                let staff = note.staff;
-                                // Check for existing chords on this staff
-                                if let Some(last_chord) = chords[(staff - 1) as usize].last_mut() {
-                                    // Check most recent chord on this staff to update if possible
-                                    if last_chord.start_time != start {
-                                        let mut tmp_chord = Chord::new();
-                                        tmp_chord.start_time = start;
-                                        tmp_chord.duration = note.duration;
-                                        tmp_chord.note_type = note.note_type;
-                                        tmp_chord.dotted = note.dotted;
-                                        tmp_chord.is_rest = note.is_rest;
-                                        tmp_chord.arpeggiate = note.arpeggiate;
-                                        tmp_chord.triplet = note.triplet;
-                                        tmp_chord.slur_start = note.slur_start;
-                                        tmp_chord.slur_stop = note.slur_stop;
-                                        tmp_chord.notes.push(note);
-                                        chords[(staff - 1) as usize].push(tmp_chord);
-                                    } else {
-                                        if last_chord.duration > note.duration {
-                                            last_chord.duration = note.duration;
-                                            last_chord.note_type = note.note_type;
-                                            last_chord.dotted = note.dotted;
-                                        }
-                                        last_chord.notes.push(note);
-                                    }
-                                } else {
-                                    let mut tmp_chord = Chord::new();
-                                    tmp_chord.start_time = start;
-                                    tmp_chord.duration = note.duration;
-                                    tmp_chord.note_type = note.note_type;
-                                    tmp_chord.dotted = note.dotted;
-                                    tmp_chord.is_rest = note.is_rest;
-                                    tmp_chord.arpeggiate = note.arpeggiate;
-                                    tmp_chord.triplet = note.triplet;
-                                    tmp_chord.slur_start = note.slur_start;
-                                    tmp_chord.slur_stop = note.slur_stop;
-                                    tmp_chord.notes.push(note);
-                                    chords[(staff - 1) as usize].push(tmp_chord);
-                                }
-                            }
+                    }
+                    match name.local_name.as_str() {
+                        "work-title" | "movement-title" | "creator" => {
+                            score.parse_header_tag(&name.local_name, &attributes, parser)?;
                         }
-                        for i in 0..measures.len() {
-                            measures[i].chords.append(&mut chords[i]);
+                        "part-group" => {
+                            score.parse_part_group(&attributes, parser, &mut open_groups)?;
+                        }
+                        "score-part" => {
+                            let mut score_part = ScorePart::parse_score_part(parser)?;
+                            score_part.groups = open_groups.iter().map(|(_, group)| *group).collect();
+                            score_parts.insert(id, score_part);
+                        }
+                        "measure" => {
+                            number = attributes.iter()
+                                .find(|attr| attr.name.local_name.as_str() == "number")
+                                .and_then(|attr| attr.value.parse::<u32>().ok());
+                            implicit = is_implicit(&attributes);
                         }
+                        "part" => {
+                            let index = match parts.iter().position(|(part_id, _)| *part_id == id) {
+                                Some(index) => index,
+                                None => {
+                                    // Like partwise parts, fall back on the divisions of the part
+                                    // before for parts that don't declare any
+                                    let mut part = Part::new();
+                                    part.inherited_divisions = parts.last()
+                                        .and_then(|(_, last_part)| last_part.measures[0].last())
+                                        .map(|measure| measure.attributes.get_file_divisions());
+                                    parts.push((id, part));
+                                    parts.len() - 1
+                                }
+                            };
+                            log::set_part(Some(index));
+                            parts[index].1.parse_measure(parser, number, implicit, "part", options)?;
+                            log::set_part(None);
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(XmlEvent::EndElement {name, ..}) => {
+                    if name.local_name.as_str() == "score-timewise" {
                         break;
                     }
                 }
-                _ => {}
+                Ok(XmlEvent::EndDocument) => {
+                    break;
+                }
+                Err(e) => {
+                    return Err(Error::Xml(e));
+                }
+                _ => {}
+            }
+        }
+        for (index, (id, part)) in parts.into_iter().enumerate() {
+            log::set_part(Some(index));
+            score.add_part(part, score_parts.get(&id), options)?;
+        }
+        log::set_part(None);
+        score.finish(options)?;
+        Ok(score)
+    }
+
+    /// Parses the start or stop of a group of parts in the part list. Groups are told apart by
+    /// their numbers, so they can overlap and nest.
+    ///
+    /// # Arguments
+    ///
+    /// * 'attributes'  - The attributes of the "part-group" tag
+    /// * 'parser'      - A mutable reference to the parser located inside the "part-group" tag
+    /// * 'open_groups' - The groups started but not yet stopped as (number, group index)
+    ///
+    fn parse_part_group<R: Read>(&mut self, attributes: &[OwnedAttribute], parser: &mut EventReader<R>, open_groups: &mut Vec<(String, usize)>) -> Result<(), Error> {
+        let mut group = PartGroup::default();
+        loop {
+            match parser.next() {
+                Ok(XmlEvent::StartElement {name, ..}) => {
+                    match name.local_name.as_str() {
+                        "group-name" => {
+                            group.name = Some(parse_tag_value("group-name", parser)?.trim().to_string());
+                        }
+                        "group-symbol" => {
+                            group.symbol = Some(parse_tag_value("group-symbol", parser)?.trim().to_string());
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(XmlEvent::EndElement {name}) if name.local_name.as_str() == "part-group" => {
+                    break;
+                }
+                Err(e) => {
+                    return Err(Error::Xml(e));
+                }
+                _ => {}
+            }
+        }
+        let number = attributes.iter()
+            .find(|attr| attr.name.local_name.as_str() == "number")
+            .map_or("1".to_string(), |attr| attr.value.trim().to_string());
+        let start = attributes.iter().any(|attr| attr.name.local_name.as_str() == "type" && attr.value == "start");
+        // A new group with the number of one still open also ends it
+        open_groups.retain(|(open, _)| *open != number);
+        if start {
+            // A symbol of "none" draws nothing
+            group.symbol = group.symbol.filter(|symbol| symbol != "none");
+            open_groups.push((number, self.groups.len()));
+            self.groups.push(group);
+        }
+        Ok(())
+    }
+
+    /// Returns the groups of parts in the part list, in the order they start
+    pub fn get_part_groups(&self) -> &[PartGroup] {
+        &self.groups
+    }
+
+    /// Parses the title or a creator of the score, which come before the part list
+    ///
+    /// # Arguments
+    ///
+    /// * 'tag'        - The name of the tag, either "work-title", "movement-title" or "creator"
+    /// * 'attributes' - The attributes of the tag
+    /// * 'parser'     - A mutable reference to the parser located inside the tag
+    ///
+    fn parse_header_tag<R: Read>(&mut self, tag: &str, attributes: &[OwnedAttribute], parser: &mut EventReader<R>) -> Result<(), Error> {
+        let value = parse_tag_value(tag, parser)?.trim().to_string();
+        if value.is_empty() {
+            return Ok(());
+        }
+        match tag {
+            "work-title" => {
+                self.title = Some(value);
+            }
+            // Only used when the work has no title, which comes first
+            "movement-title" if self.title.is_none() => {
+                self.title = Some(value);
+            }
+            "creator" if attributes.iter().any(|attr| attr.name.local_name.as_str() == "type" && attr.value == "composer") => {
+                self.composer = Some(match self.composer.take() {
+                    Some(composers) => format!("{}, {}", composers, value),
+                    None => value,
+                });
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Returns the divisions a new part starts with when it doesn't declare its own, which are
+    /// the most recently seen divisions of the last part
+    fn get_inherited_divisions(&self) -> Option<u32> {
+        // Some exports only declare divisions in the first part
+        self.parts.last()
+            .and_then(|part| part.measures[0].last())
+            .map(|measure| measure.attributes.get_file_divisions())
+    }
+
+    /// Names a parsed part from the part list and adds it to the score
+    ///
+    /// # Arguments
+    ///
+    /// * 'part'       - The parsed part
+    /// * 'score_part' - The entry of the part list for the part, if there is one
+    /// * 'options'    - The conversion options in use
+    ///
+    fn add_part(&mut self, mut part: Part, score_part: Option<&ScorePart>, options: &Options) -> Result<(), Error> {
+        if let Some(score_part) = score_part {
+            part.name = score_part.name.clone();
+            part.abbreviation = score_part.abbreviation.clone();
+            part.instrument_name = score_part.instrument_name.clone();
+            part.midi_program = score_part.midi_program;
+        }
+        part.place_unpitched_notes(score_part.map_or(&BTreeMap::new(), |score_part| &score_part.unpitched_keys));
+        for &group in score_part.map_or(&Vec::new(), |score_part| &score_part.groups) {
+            self.groups[group].parts.push(self.parts.len());
+        }
+        if !part.declares_divisions() {
+            if options.strict {
+                return Err(Error::MissingDivisions(self.parts.len()));
+            }
+            if let Some(divisions) = part.inherited_divisions {
+                warning!("No divisions are declared, using {} from an earlier part", divisions);
+            }
+        }
+        self.parts.push(part);
+        Ok(())
+    }
+
+    /// Checks that the parsed score has something to convert and fills in what GJM needs
+    ///
+    /// # Arguments
+    ///
+    /// * 'options' - The conversion options in use
+    ///
+    fn finish(&mut self, options: &Options) -> Result<(), Error> {
+        // A document without any parts is most likely truncated rather than a template, so there is
+        // nothing to convert
+        if self.parts.is_empty() {
+            return Err(Error::NoParts);
+        }
+        for part in self.parts.iter_mut() {
+            part.pad_empty();
+        }
+
+        let skipped_measures = self.get_skipped_measure_count();
+        if skipped_measures > 0 {
+            warning!("{} measures could not be parsed and were replaced with rests", skipped_measures);
+        }
+        match &mut self.streamed {
+            // Streamed parts were already unfolded before they were written
+            Some(streamed) => streamed.file.flush()?,
+            None => {
+                if options.unfold {
+                    let order = calc_repeat_order(self.get_first_staff());
+                    for part in self.parts.iter_mut() {
+                        part.unfold_repeats(&order);
+                    }
+                }
             }
         }
-        measures
+        Ok(())
     }
 
-    /// Get the gjm duration value of a measure
-    fn get_duration_max(&self) -> u32 {
-        // To convert to gjm we get the ratio of the combined musicXml durations of all chords in
-        // the measure over the theoretical expected duration of a full measure with the given time
-        // signature and divisions. This lets us calculate the gjm duration as a ratio of the theoretical max.
-        let mxml_max_dur = self.attributes.divisions * self.attributes.beats as u32;
-        let gjm_max_dur = (64 / self.attributes.beat_type) * self.attributes.beats;
-        let mut mxml_actual_dur = 0;
-        for chord in self.chords.iter() {
-            mxml_actual_dur += chord.duration;
+    /// Writes the tracks of the last parsed part to the streamed tracks and drops its notes, keeping
+    /// only the attributes of its measures for the header of the score
+    ///
+    /// # Arguments
+    ///
+    /// * 'options' - The conversion options in use
+    ///
+    fn stream_last_part(&mut self, options: &Options) -> Result<(), Error> {
+        if self.streamed.is_none() {
+            self.streamed = Some(StreamedTracks::create(options)?);
         }
-        let mxml_dur_ratio = mxml_actual_dur as f64 / mxml_max_dur as f64;
-        // Subtract one because gjm expects the max start duration minus the minimum note length.
-        let mut duration_max = (mxml_dur_ratio * gjm_max_dur as f64).round() as u32;
-        if duration_max > 0 {
-            duration_max -= 1;
+        let streamed = self.streamed.as_mut().unwrap();
+        let p = self.parts.len() - 1;
+        let part = &mut self.parts[p];
+        part.pad_empty();
+        if options.unfold {
+            // The first part decides the order for every part, as when unfolding a whole score
+            let order = streamed.repeat_order.get_or_insert_with(|| calc_repeat_order(&part.measures[0]));
+            part.unfold_repeats(order);
         }
-        duration_max
+        for s in 0..part.measures.len() {
+            if streamed.track_count < get_max_tracks(options) {
+                part.write_track_gjn(&mut streamed.file, s, streamed.track_count, options, &mut streamed.humanizer)?;
+            } else {
+                streamed.dropped.push(format!("part {} staff {}", p, s + 1));
+            }
+            streamed.track_count += 1;
+        }
+        for measure in part.measures.iter_mut().flatten() {
+            measure.chords = Vec::<Chord>::new();
+        }
+        Ok(())
     }
 
-    fn get_duration_ratio(&self) -> f64 {
-        let mxml_max_dur = self.attributes.divisions * self.attributes.beats as u32;
-        let gjm_max_dur = (64 / self.attributes.beat_type) * self.attributes.beats;
-        gjm_max_dur as f64 / mxml_max_dur as f64
+    /// Returns the number of measures across all parts that were replaced with rests after errors
+    pub fn get_skipped_measure_count(&self) -> usize {
+        self.parts.iter().map(|part| part.skipped_measures).sum()
     }
-}
 
-/// A collection of sets of measures that are considered the same Part by MusicXml but exist on different
-/// staves, requiring they be treated as seperate by GJM
-#[derive(Debug)]
-struct Part {
-    measures: Vec<Vec<Measure>>,
-}
+    /// Writes the whole score as a GJM file, starting with the notation header
+    ///
+    /// # Arguments
+    ///
+    /// * 'file'    - Where to write the GJM file to
+    /// * 'options' - The conversion options in use
+    ///
+    pub fn write_gjm<W: OtherWrite>(&self, file: &mut W, options: &Options) -> std::io::Result<()> {
+        if options.crlf {
+            return self.write_notation_gjn(&mut CrlfWriter {inner: file}, options);
+        }
+        self.write_notation_gjn(file, options)
+    }
 
-impl Part {
-    /// Returns a default instantiation of a Part
-    fn new() -> Self {
-        Self {
-            measures: vec![Vec::<Measure>::new()],
+    /// Writes the whole GJM document with "\n" line endings
+    ///
+    /// # Arguments
+    ///
+    /// * 'file'    - The output to write to
+    /// * 'options' - The conversion options in use
+    ///
+    fn write_notation_gjn<W: OtherWrite>(&self, file: &mut W, options: &Options) -> std::io::Result<()> {
+        // File Version
+        let line = "Version ='1.1.0.0'\n";
+        file.write_all(line.as_bytes())?;
+
+        // Overall Notation info
+        let line = "Notation = {\n";
+        file.write_all(line.as_bytes())?;
+        //      Version and author info
+        let line = format!("\tVersion ='1.1.0.0',\n\tNotationName = '{}',\n\tNotationAuther = '{}',\n\tNotationTranslater = 'UnknownTranslator',\n\tNotationCreator = 'Dwarfed',\n\tVolume = 1,\n",
+            quote_gjm_string(self.title.as_deref().unwrap_or("Unnamed")),
+            quote_gjm_string(self.composer.as_deref().unwrap_or("UnknownAuthor")));
+        file.write_all(line.as_bytes())?;
+        //      Time signature info
+        let line = format!("\tBeatsPerMeasure = {},\n", self.get_beats_per_measure());
+        file.write_all(line.as_bytes())?;
+        let line = format!("\tBeatDurationType = '{}',\n", self.get_beat_duration_type());
+        file.write_all(line.as_bytes())?;
+        //      Time signature changes, only when asked for as GJM doesn't document them
+        if options.meter_map {
+            let line = "\tMeasureTimeSignatureMap = {\n";
+            file.write_all(line.as_bytes())?;
+            for (i, beats, beat_type) in calc_meter_map(self.get_first_staff()) {
+                let line = format!("\t\t{{ {}, {}, '{}' }},\n", i, beats, beat_type);
+                file.write_all(line.as_bytes())?;
+            }
+            let line = "\t},\n";
+            file.write_all(line.as_bytes())?;
         }
+        let line = format!("\tNumberedKeySignature = '{}',\n", self.get_numbered_key_signature());
+        file.write_all(line.as_bytes())?;
+
+        //      BPM
+        let line = "\tMeasureBeatsPerMinuteMap = {\n";
+        file.write_all(line.as_bytes())?;
+        let line = self.get_bpm_map();
+        file.write_all(line.as_bytes())?;
+        let line = "\t},\n";
+        file.write_all(line.as_bytes())?;
+
+        //      Number of Measures
+        let line = format!("\tMeasureAlignedCount = {},\n", self.get_measure_count());
+        file.write_all(line.as_bytes())?;
+
+        // Close notation info
+        let line = "}\n";
+        file.write_all(line.as_bytes())?;
+
+        // Track/measure/note info
+        self.write_score_gjn(file, options)
     }
 
-    /// Parses the tags and values inside of a "part" tag and returns a single part that may have
-    /// multiple parts by GJM standards
-    fn parse_part(parser: &mut EventReader<BufReader<File>>) -> Self {
-        let mut part = Part::new();
-        loop {
-            match parser.next() {
-                Ok(XmlEvent::StartElement {name, ..}) => {
-                    match name.local_name.as_str() {
-                        "measure" => {
-                            // Attributes carry over from one measure to the next if available
-                            let mut attrs = Vec::<Attributes>::new();
-                            for i in 0..part.measures.len() {
-                                if part.measures[i].len() > 0 {
-                                    attrs.push(part.measures[i].last().unwrap().attributes.clone());
-                                } else {
-                                    attrs.push(Attributes::new());
-                                }
-                            }
-                            let tmp_measures = Measure::parse_measure(parser, attrs);
-                            for i in 0..tmp_measures.len() {
-                                if tmp_measures.len() > part.measures.len() {
-                                    part.measures.push(Vec::<Measure>::new());
-                                }
-                                part.measures[i].push(tmp_measures[i].clone());
-                            }
-                        }
-                        _ => {}
-                    }
-                }
-                Ok(XmlEvent::EndElement {name, ..}) => {
-                    if name.local_name.as_str() == "part" {
-                        break;
+    pub fn write_score_gjn<W: OtherWrite>(&self, file: &mut W, options: &Options) -> std::io::Result<()> {
+        file.write_all(b"Notation.RegularTracks = {\n")?;
+        
+        let mut part_idx = 0;
+        if options.conductor_track {
+            self.write_conductor_gjn(file, &mut part_idx, options)?;
+        }
+        let mut dropped = Vec::<String>::new();
+        match &self.streamed {
+            Some(streamed) => {
+                std::io::copy(&mut File::open(&streamed.path)?, file)?;
+                dropped = streamed.dropped.clone();
+            }
+            None => {
+                let mut humanizer = Humanizer::new(options.seed, options.humanize);
+                for (p, s) in self.get_track_order(options) {
+                    if part_idx < get_max_tracks(options) {
+                        self.parts[p].write_track_gjn(file, s, part_idx, options, &mut humanizer)?;
+                    } else {
+                        dropped.push(format!("part {} staff {}", p, s + 1));
                     }
+                    part_idx += 1;
                 }
-                _ => {}
             }
         }
-        part
-    }
-
-    fn write_part_gjn(&self, file: &mut File, part_idx: &mut usize) -> std::io::Result<()> {
-        for part in self.measures.iter() {
-            if *part_idx < MAX_PART_COUNT {
-                let line = format!("{}[{}] = {{\n", indent(1), part_idx);
-                file.write_all(line.as_bytes())?;
+        if !dropped.is_empty() {
+            warning!("Only {} tracks are written, dropping {}", get_max_tracks(options), dropped.join(", "));
+        }
 
-                let (keys, clefs, volumes) = calc_measure_maps(part);
+        file.write_all(b"}")?;
+        Ok(())
+    }
 
-                // Key Signature Map
-                let line = format!("{}MeasureKeySignatureMap = {{\n", indent(2));
-                file.write_all(line.as_bytes())?;
-                for (i, key) in keys {
-                    let line = format!("{}{{ {}, {} }},\n", indent(3), i, key);
-                    file.write_all(line.as_bytes())?;
+    /// Writes the words sung in the score as text, since GJM has no lyrics. Each verse of each track
+    /// starts with a comment naming the track and verse, followed by a line for every measure with
+    /// words in it that starts with the GJM index of the measure.
+    ///
+    /// # Arguments
+    ///
+    /// * 'file'    - The output to write to
+    /// * 'options' - The conversion options in use
+    ///
+    pub fn write_lyrics<W: OtherWrite>(&self, file: &mut W, options: &Options) -> std::io::Result<()> {
+        for (p, s) in self.get_track_order(options) {
+            let part = &self.parts[p];
+            let measures = &part.measures[s];
+            // The verses in the order they first appear
+            let mut verses = Vec::<&str>::new();
+            for chord in measures.iter().flat_map(|measure| measure.chords.iter()) {
+                for lyric in chord.get_lyrics() {
+                    if !verses.contains(&lyric.verse.as_str()) {
+                        verses.push(&lyric.verse);
+                    }
                 }
-                let line = format!("{}}},\n", indent(2));
-                file.write_all(line.as_bytes())?;
-
-                // Clef Type Map
-                let line = format!("{}MeasureClefTypeMap = {{\n", indent(2));
+            }
+            let label = part.get_track_label(s, options).unwrap_or_else(|| format!("Part {} staff {}", p, s + 1));
+            for verse in verses {
+                let line = format!("-- {}, verse {}\n", label, verse);
                 file.write_all(line.as_bytes())?;
-                for (i, clef) in clefs {
-                    let clef_str;
-                    match clef {
-                        Clef::F => {
-                            clef_str = "L4F";
-                        }
-                        Clef::G => {
-                            clef_str = "L2G";
+                for (m, measure) in measures.iter().enumerate() {
+                    let mut words = String::new();
+                    for chord in measure.chords.iter() {
+                        // Chords have one syllable each, even when several of their notes have one
+                        if let Some(lyric) = chord.get_lyrics().into_iter().find(|lyric| lyric.verse == verse) {
+                            words.push_str(&lyric.text);
+                            words.push_str(if lyric.hyphen { "-" } else { " " });
                         }
                     }
-                    let line = format!("{}{{ {}, '{}' }},\n", indent(3), i, clef_str);
-                    file.write_all(line.as_bytes())?;
-                }
-                let line = format!("{}}},\n", indent(2));
-                file.write_all(line.as_bytes())?;
-                
-                // Hardcoded Maps
-                    // Instrument
-                let line = format!("{}MeasureInstrumentTypeMap = {{\n", indent(2));
-                file.write_all(line.as_bytes())?;
-                let line = format!("{}{{ 0, 'Piano' }},\n", indent(3));
-                file.write_all(line.as_bytes())?;
-                let line = format!("{}}},\n", indent(2));
-                file.write_all(line.as_bytes())?;
-                    // Volume Curve
-                let line = format!("{}MeasureVolumeCurveMap = {{\n", indent(2));
-                file.write_all(line.as_bytes())?;
-                let line = format!("{}{{ 0, {{0.8, 0.7, 0.5, 0.5, 0.7, 0.6, 0.5, 0.4}} }},\n", indent(3));
-                file.write_all(line.as_bytes())?;
-                let line = format!("{}}},\n", indent(2));
-                file.write_all(line.as_bytes())?;
-
-                // Volume Map
-                let line = format!("{}MeasureVolumeMap = {{\n", indent(2));
-                file.write_all(line.as_bytes())?;
-                for (i, mut volume) in volumes {
-                    if volume == 0 {
-                        volume = 10
+                    if !words.is_empty() {
+                        let line = format!("[{}] {}\n", m, words.trim_end());
+                        file.write_all(line.as_bytes())?;
                     }
-                    let line = format!("{}{{ {}, {:.2} }},\n", indent(3), i, volume as f64 / 100f64);
-                    file.write_all(line.as_bytes())?;
                 }
-                let line = format!("{}}},\n", indent(2));
-                file.write_all(line.as_bytes())?;
+                file.write_all(b"\n")?;
+            }
+        }
+        Ok(())
+    }
 
-                for (i, measure) in part.iter().enumerate() {
-                    // Measure index
-                    let line = format!("{}[{}] = {{\n", indent(2), i);
-                    file.write_all(line.as_bytes())?;
+    /// Writes a track with no notes that carries the maps of the first staff along with a full
+    /// length measure for each measure of the score, so that the key and meter of every measure
+    /// are kept in one place. GJM tracks can't point at another track, so the instrument tracks
+    /// still carry their own maps, and the tempo stays in the score's own map that every track
+    /// follows. The conductor track takes up one of the tracks.
+    fn write_conductor_gjn<W: OtherWrite>(&self, file: &mut W, part_idx: &mut usize, options: &Options) -> std::io::Result<()> {
+        let measures = self.get_first_staff();
 
-                    // Duration of measure (expressed as divisions)
-                    let line = format!("{}DurationStampMax = {},\n", indent(3), measure.get_duration_max());
-                    file.write_all(line.as_bytes())?;
+        let line = format!("{}[{}] = {{\n", indent(1), part_idx);
+        file.write_all(line.as_bytes())?;
 
-                    // Number of notes (chords really)
-                    let line = format!("{}NotePackCount = {},\n", indent(3), measure.chords.len());
-                    file.write_all(line.as_bytes())?;
+        let instrument = self.parts.first().map_or(instrument::DEFAULT, |part| part.get_instrument(options));
+        write_track_maps(file, measures, instrument)?;
 
-                    let mut current_dur = 0;
-                    for (j, chord) in measure.chords.iter().enumerate() {
-                        // Chord index
-                        let line = format!("{}[{}] = {{\n", indent(3), j);
-                        file.write_all(line.as_bytes())?;
+        // Empty measures that only carry the length of each measure. GJM has no meter map of its
+        // own, it takes the meter from each measure's DurationStampMax, so these measures are all
+        // the conductor track needs to carry the meter changes
+        for (i, measure) in measures.iter().enumerate() {
+            let line = format!("{}[{}] = {{\n", indent(2), i);
+            file.write_all(line.as_bytes())?;
+            let line = format!("{}DurationStampMax = {},\n", indent(3), measure.get_full_duration_max());
+            file.write_all(line.as_bytes())?;
+            let line = format!("{}NotePackCount = 0,\n", indent(3));
+            file.write_all(line.as_bytes())?;
+            let line = format!("{}}},\n", indent(2));
+            file.write_all(line.as_bytes())?;
+        }
 
-                        // Add a line if chord is a rest and set notecount to zero for that chord
-                        let mut note_count = chord.notes.len();
-                        if chord.is_rest {
-                            let line = format!("{}IsRest = true,\n", indent(4));
-                            file.write_all(line.as_bytes())?;
-                            note_count = 0;
-                        }
+        // Close the track
+        let line = format!("{}}},\n", indent(1));
+        file.write_all(line.as_bytes())?;
 
-                        // Add ties/slurs
-                        if chord.slur_start && chord.slur_stop {
-                            let line = format!("{}TieType ='Both',\n", indent(4));
-                            file.write_all(line.as_bytes())?;
-                        } else if chord.slur_start {
-                            let line = format!("{}TieType ='Start',\n", indent(4));
-                            file.write_all(line.as_bytes())?;
-                        } else if chord.slur_stop {
-                            let line = format!("{}TieType ='End',\n", indent(4));
-                            file.write_all(line.as_bytes())?;
-                        }
+        *part_idx += 1;
+        Ok(())
+    }
 
-                        // Add a line if chord is dotted
-                        if chord.dotted {
-                            let line = format!("{}IsDotted = true,\n", indent(4));
-                            file.write_all(line.as_bytes())?;
-                        }
+    /// Checks the score for anything that GJM can't represent exactly, returning an Issue for each
+    /// problem found
+    pub fn validate(&self, options: &Options) -> Vec<Issue> {
+        let mut issues = Vec::<Issue>::new();
 
-                        // Triplet if appropriate (any tuple is a triplet for now)
-                        if chord.triplet {
-                            let line = format!("{}Triplet = true,\n", indent(4));
-                            file.write_all(line.as_bytes())?;
+        let mut track_count = 0;
+        if options.conductor_track {
+            track_count += 1;
+        }
+        for (p, s) in self.get_track_order(options) {
+            if track_count >= get_max_tracks(options) {
+                issues.push(Issue {
+                    feature: "track count",
+                    message: format!("Part {} staff {} was dropped, only {} tracks are written", p, s + 1, get_max_tracks(options)),
+                    location: Some(Location { part: p, staff: s + 1, measure: None, line: None }),
+                });
+            }
+            track_count += 1;
+        }
+        for (p, part) in self.parts.iter().enumerate() {
+            for (s, staff) in part.measures.iter().enumerate() {
+                for (m, measure) in staff.iter().enumerate() {
+                    let location = Some(Location { part: p, staff: s + 1, measure: Some(m), line: measure.line });
+                    // Sound directions apply to every staff of the part, so only the first is
+                    // reported
+                    if s == 0 {
+                        for placement in measure.placements.iter() {
+                            issues.push(Issue {
+                                feature: "stereo placement",
+                                message: format!("Part {} measure {} has {}, which GJM does not support so the part is played in the centre", p, m, placement),
+                                location,
+                            });
                         }
-
-                        // Duration type is just string version of note type
-                        let line = format!("{}DurationType = '{}',\n", indent(4), chord.gjm_note_string());
-                        file.write_all(line.as_bytes())?;
-                        
-                        // Arpeggiate if appropriate (always up for now)
-                        if chord.arpeggiate {
-                            let line = format!("{}ArpeggioMode ='Upward',\n", indent(4));
-                            file.write_all(line.as_bytes())?;
+                    }
+                    for chord in measure.chords.iter() {
+                        match chord.note_type {
+                            NoteType::TenTwentyFourth | NoteType::FiveTwelfth | NoteType::TwoFiftySixth |
+                            NoteType::OneTwentyEighth => {
+                                issues.push(Issue {
+                                    feature: "short notes",
+                                    message: format!("Part {} staff {} measure {} has a {:?} note, which is shorter than GJM supports", p, s + 1, m, chord.note_type),
+                                    location,
+                                });
+                            }
+                            NoteType::Breve | NoteType::Long | NoteType::Maxima => {
+                                issues.push(Issue {
+                                    feature: "long notes",
+                                    message: format!("Part {} staff {} measure {} has a {:?} note, which is longer than GJM supports", p, s + 1, m, chord.note_type),
+                                    location,
+                                });
+                            }
+                            _ => {}
                         }
-
-                        let line = format!("{}StampIndex = {},\n", indent(4), current_dur);
-                        file.write_all(line.as_bytes())?;
-                        let duration_ratio = measure.get_duration_ratio();
-                        current_dur += chord.gjm_duration(duration_ratio);
-
-                        // PitchSignCount is just how many notes are in the chord
-                        let line = format!("{}ClassicPitchSignCount = {},\n", indent(4), note_count);
-                        file.write_all(line.as_bytes())?;
-
-                        if note_count > 0 {
-                            let line = format!("{}ClassicPitchSign = {{\n", indent(4));
-                            file.write_all(line.as_bytes())?;
-                            for note in chord.notes.iter() {
-                                let line = format!("{}[{}] = {{ NumberedSign = {}, PlayingPitchIndex = {}, AlterantType = '{}', RawAlterantType = '{}', }},\n",
-                                    indent(5),
-                                    note.pitch_index,
-                                    note.get_numbered_sign(),
-                                    note.pitch_index as i32 + note.alter,
-                                    note.get_alterant_type(),
-                                    note.get_alterant_type(),
-                                );
-                                file.write_all(line.as_bytes())?;
+                        for grace_chord in chord.grace_chords.iter() {
+                            let kind = if grace_chord.iter().any(|note| note.grace_slash) { "an acciaccatura" } else { "an appoggiatura" };
+                            issues.push(Issue {
+                                feature: "grace notes",
+                                message: format!("Part {} staff {} measure {} has {} of {} notes, which GJM does not support", p, s + 1, m, kind, grace_chord.len()),
+                                location,
+                            });
+                        }
+                        if chord.fermata {
+                            issues.push(Issue {
+                                feature: "fermatas",
+                                message: format!("Part {} staff {} measure {} has a fermata, which GJM does not support so the notes are played in time", p, s + 1, m),
+                                location,
+                            });
+                        }
+                        if chord.slur_start {
+                            issues.push(Issue {
+                                feature: "slurs",
+                                message: format!("Part {} staff {} measure {} starts a slur, which GJM does not support so its notes are played unconnected", p, s + 1, m),
+                                location,
+                            });
+                        }
+                        for note in chord.notes.iter() {
+                            if note.alter < -1 || note.alter > 1 {
+                                issues.push(Issue {
+                                    feature: "double alterations",
+                                    message: format!("Part {} staff {} measure {} has a note altered by {} half steps", p, s + 1, m, note.alter),
+                                    location,
+                                });
+                            }
+                            for ornament in note.ornaments.iter() {
+                                let mut message = format!("Part {} staff {} measure {} has a {} ornament", p, s + 1, m, ornament.kind);
+                                if let Some(accidental) = ornament.accidental {
+                                    message = format!("{} with its auxiliary note altered by {} half steps", message, accidental);
+                                }
+                                issues.push(Issue {
+                                    feature: "ornaments",
+                                    message: format!("{}, which GJM does not support", message),
+                                    location,
+                                });
+                            }
+                            if note.triplet && note.tuplet_actual > 1 && (note.tuplet_actual, note.tuplet_normal) != (3, 2) {
+                                issues.push(Issue {
+                                    feature: "tuplets",
+                                    message: format!("Part {} staff {} measure {} has a tuplet of {} notes in the time of {}, which is marked as a triplet", p, s + 1, m, note.tuplet_actual, note.tuplet_normal),
+                                    location,
+                                });
+                            }
+                            if note.glissando_start {
+                                issues.push(Issue {
+                                    feature: "glissandi",
+                                    message: format!("Part {} staff {} measure {} has a glissando, which is played as its end notes only", p, s + 1, m),
+                                    location,
+                                });
+                            }
+                            if note.unpitched {
+                                issues.push(Issue {
+                                    feature: "unpitched percussion",
+                                    message: format!("Part {} staff {} measure {} has an unpitched note, which is written at its staff position", p, s + 1, m),
+                                    location,
+                                });
+                            }
+                            if !note.technical.is_empty() {
+                                issues.push(Issue {
+                                    feature: "technical markings",
+                                    message: format!("Part {} staff {} measure {} has a note marked with {}, which GJM does not support", p, s + 1, m, note.technical.describe()),
+                                    location,
+                                });
                             }
-                            let line = format!("{}}},\n", indent(4));
-                            file.write_all(line.as_bytes())?;
                         }
-
-                        // Close the chord
-                        let line = format!("{}}},\n", indent(3));
-                        file.write_all(line.as_bytes())?;
                     }
-                    // Close the measure
-                    let line = format!("{}}},\n", indent(2));
-                    file.write_all(line.as_bytes())?;
                 }
-
-                // Close the part
-                let line = format!("{}}},\n", indent(1));
-                file.write_all(line.as_bytes())?;
             }
-
-            *part_idx += 1;
         }
-        Ok(())
+
+        issues
     }
-}
 
-/// A collection of parts
-#[derive(Debug)]
-pub struct Score {
-    parts: Vec<Part>,
-}
+    /// Returns an error listing everything the conversion would lose if lossless conversion is
+    /// required and the score can't be converted exactly. The score is written to memory and read
+    /// back, so anything writing it changes is found exactly, along with what validating finds was
+    /// already lost while parsing, such as grace notes and ornaments.
+    pub fn check_lossless(&self, options: &Options) -> Result<(), Error> {
+        if !options.lossless {
+            return Ok(());
+        }
+        // The real output gives the same warnings, so they aren't given twice
+        let mut gjm = Vec::<u8>::new();
+        log::silence(true);
+        let written = self.write_gjm(&mut gjm, options);
+        log::silence(false);
+        written?;
 
-impl Score {
-    /// Returns a default instantiation of a Score
-    pub fn new() -> Self {
-        Self {parts: Vec::<Part>::new()}
+        let mut issues: Vec<Issue> = self.validate(options).into_iter()
+            .filter(|issue| !COMPARED_FEATURES.contains(&issue.feature))
+            .collect();
+        issues.extend(self.compare_written(&String::from_utf8_lossy(&gjm), options));
+        if !issues.is_empty() {
+            return Err(Error::Lossy(issues));
+        }
+        Ok(())
     }
 
-    /// Parses the tags and values of an entire partwise score
-    pub fn parse_score(parser: &mut EventReader<BufReader<File>>) -> Self {
-        let mut score = Score::new();
-        loop {
-            match parser.next() {
-                Ok(XmlEvent::StartElement {name, ..}) => {
-                    match name.local_name.as_str() {
-                        "part" => {
-                            score.parts.push(Part::parse_part(parser));
+    /// Compares a GJM document written from this score with the score, returning an issue for
+    /// every track that was left out and every chord written with a different rhythm, position
+    /// or pitches
+    ///
+    /// # Arguments
+    ///
+    /// * 'gjm'     - The GJM document written from this score
+    /// * 'options' - The conversion options it was written with
+    ///
+    fn compare_written(&self, gjm: &str, options: &Options) -> Vec<Issue> {
+        let mut issues = Vec::<Issue>::new();
+        let mut written_tracks = read_written_tracks(gjm).into_iter();
+        if options.conductor_track {
+            written_tracks.next();
+        }
+        for (p, s) in self.get_track_order(options) {
+            let written_track = match written_tracks.next() {
+                Some(track) => track,
+                None => {
+                    issues.push(Issue {
+                        feature: "track count",
+                        message: format!("Part {} staff {} was dropped, only {} tracks are written", p, s + 1, get_max_tracks(options)),
+                        location: Some(Location { part: p, staff: s + 1, measure: None, line: None }),
+                    });
+                    continue;
+                }
+            };
+            for (m, measure) in self.parts[p].measures[s].iter().enumerate() {
+                let location = Some(Location { part: p, staff: s + 1, measure: Some(m), line: measure.line });
+                let written_chords = written_track.get(m).map_or(&[][..], Vec::as_slice);
+                for (j, chord) in measure.chords.iter().enumerate() {
+                    let place = format!("Part {} staff {} measure {} chord {}", p, s + 1, m, j);
+                    let written = match written_chords.get(j) {
+                        Some(written) => written,
+                        None => {
+                            issues.push(Issue {
+                                feature: "dropped notes",
+                                message: format!("{} was not written", place),
+                                location,
+                            });
+                            continue;
                         }
-                        _ => {}
+                    };
+                    if NoteType::from_gjm_name(&written.duration_type) != Some(chord.note_type) {
+                        issues.push(Issue {
+                            feature: "note types",
+                            message: format!("{} is a {:?} note but was written as a {} note", place, chord.note_type, written.duration_type),
+                            location,
+                        });
                     }
-                }
-                Ok(XmlEvent::EndElement {name, ..}) => {
-                    if name.local_name.as_str() == "score-partwise" {
-                        break;
+                    if written.dotted != (chord.dots > 0) || written.triplet != chord.triplet {
+                        issues.push(Issue {
+                            feature: "rhythms",
+                            message: format!("{} has {} dots and is {}a triplet, but was written {}dotted and {}as a triplet",
+                                place, chord.dots, if chord.triplet { "" } else { "not " },
+                                if written.dotted { "" } else { "un" }, if written.triplet { "" } else { "not " }),
+                            location,
+                        });
+                    }
+                    let stamp = measure.get_stamp(chord.start_time);
+                    if (written.stamp as f64 - stamp).abs() > 1e-9 {
+                        issues.push(Issue {
+                            feature: "positions",
+                            message: format!("{} starts at stamp {} but was written at stamp {}", place, stamp, written.stamp),
+                            location,
+                        });
+                    }
+                    let pitches: Vec<i32> = if chord.is_rest {
+                        Vec::new()
+                    } else {
+                        chord.notes.iter().map(|note| note.pitch_index as i32 + note.alter).collect()
+                    };
+                    if written.pitches != pitches {
+                        issues.push(Issue {
+                            feature: "pitches",
+                            message: format!("{} plays the pitches {:?} but was written with {:?}", place, pitches, written.pitches),
+                            location,
+                        });
                     }
                 }
-                _ => {}
             }
         }
+        issues
+    }
+
+    /// Builds a report describing the conversion of this score
+    ///
+    /// # Arguments
+    ///
+    /// * 'source'   - The path of the MusicXml file the score was parsed from
+    /// * 'options'  - The conversion options in use
+    /// * 'warnings' - The warnings given while converting the score
+    ///
+    pub fn build_report(&self, source: &str, options: &Options, warnings: Vec<String>) -> Report {
+        let mut tracks = Vec::<TrackReport>::new();
+        let mut track_count = 0;
+        if options.conductor_track {
+            track_count += 1;
+        }
+        for (p, s) in self.get_track_order(options) {
+            tracks.push(TrackReport {
+                part: p,
+                staff: s + 1,
+                name: self.parts[p].get_track_label(s, options),
+                instrument: self.parts[p].get_instrument(options).to_string(),
+                dropped: track_count >= get_max_tracks(options),
+            });
+            track_count += 1;
+        }
 
-        score
+        let measures = self.get_first_staff();
+        let (key_map, _, _) = calc_measure_maps(measures);
+        let mut report = Report {
+            source: source.to_string(),
+            tracks,
+            measure_count: self.get_measure_count(),
+            key_map,
+            meter_map: calc_meter_map(measures),
+            tempo_map: calc_tempo_map(&self.parts),
+            skipped_measures: self.get_skipped_measure_count(),
+            statistics: self.statistics(),
+            warnings,
+            issues: Vec::<Issue>::new(),
+            downgraded: Vec::<String>::new(),
+        };
+        report.add_issues(self.validate(options));
+        report
     }
 
-    pub fn write_score_gjn(&self, file: &mut File) -> std::io::Result<()> {
-        file.write_all(b"Notation.RegularTracks = {\n")?;
-        
-        let mut part_idx = 0;
+    /// Counts up the notes, rests, pitches and rhythms of the whole score without writing it
+    pub fn statistics(&self) -> Statistics {
+        let mut stats = Statistics {
+            measure_count: self.get_measure_count(),
+            ..Statistics::default()
+        };
         for part in self.parts.iter() {
-            part.write_part_gjn(file, &mut part_idx)?;
+            for staff in part.measures.iter() {
+                for measure in staff.iter() {
+                    for chord in measure.chords.iter() {
+                        if chord.is_rest {
+                            stats.rest_count += 1;
+                            continue;
+                        }
+                        if chord.triplet {
+                            stats.tuplet_count += 1;
+                        }
+                        for note in chord.notes.iter() {
+                            let pitch = (note.pitch_index as i32 + note.alter).max(0) as u32;
+                            stats.note_count += 1;
+                            stats.lowest_pitch = Some(stats.lowest_pitch.map_or(pitch, |lowest| lowest.min(pitch)));
+                            stats.highest_pitch = Some(stats.highest_pitch.map_or(pitch, |highest| highest.max(pitch)));
+                            *stats.note_types.entry(format!("{:?}", note.note_type)).or_insert(0) += 1;
+                            if note.tie_start || note.tie_stop {
+                                stats.tied_count += 1;
+                            }
+                        }
+                    }
+                }
+            }
         }
+        stats
+    }
 
-        file.write_all(b"}")?;
-        Ok(())
+    /// Returns the (part, staff) of every track in the order they are written
+    ///
+    /// # Arguments
+    ///
+    /// * 'options' - The conversion options in use
+    ///
+    pub fn get_track_order(&self, options: &Options) -> Vec<(usize, usize)> {
+        let mut tracks = Vec::<(usize, usize)>::new();
+        for (p, part) in self.parts.iter().enumerate() {
+            for s in 0..part.measures.len() {
+                tracks.push((p, s));
+            }
+        }
+        match &options.track_order {
+            TrackOrder::Parsed => {}
+            TrackOrder::Permutation(order) => {
+                let mut sorted = order.clone();
+                sorted.sort_unstable();
+                if sorted.iter().copied().eq(0..tracks.len()) {
+                    tracks = order.iter().map(|i| tracks[*i]).collect();
+                } else {
+                    warning!("The track order must list each of the {} tracks once, keeping the parsed order", tracks.len());
+                }
+            }
+            TrackOrder::Name => {
+                // Unnamed parts go last, and the sort is stable so staves stay in order
+                tracks.sort_by_key(|(p, _)| match self.parts[*p].get_label(options) {
+                    Some(label) => (false, label.to_lowercase()),
+                    None => (true, String::new()),
+                });
+            }
+            TrackOrder::Pitch => {
+                // Highest first, with tracks that have no notes last
+                tracks.sort_by(|(p1, s1), (p2, s2)| {
+                    let first = self.parts[*p1].get_average_pitch(*s1).unwrap_or(f64::MIN);
+                    let second = self.parts[*p2].get_average_pitch(*s2).unwrap_or(f64::MIN);
+                    second.partial_cmp(&first).unwrap()
+                });
+            }
+        }
+        tracks
+    }
+
+    /// Returns the measures of the first staff of the first part, which hold the meter and key
+    /// of the whole score, or no measures for a score without parts
+    fn get_first_staff(&self) -> &[Measure] {
+        self.parts.first().map_or(&[], |part| &part.measures[0])
+    }
+
+    /// Returns the attributes the score starts with, or the default attributes for an empty score
+    fn get_first_attributes(&self) -> Attributes {
+        self.get_first_staff().first().map_or_else(Attributes::new, |measure| measure.attributes.clone())
     }
 
     pub fn get_beats_per_measure(&self) -> u8 {
-        self.parts[0].measures[0][0].attributes.beats
+        self.get_first_attributes().beats
     }
 
     pub fn get_beat_duration_type(&self) -> u8 {
-        self.parts[0].measures[0][0].attributes.beat_type
+        self.get_first_attributes().beat_type
+    }
+
+    pub fn get_numbered_key_signature(&self) -> &'static str {
+        numbered_key_name(self.get_first_attributes().key)
     }
 
     pub fn get_bpm_map(&self) -> String {
         let mut map = String::new();
 
-        let mut tempo = 0;
-        for (i, measure) in self.parts[0].measures[0].iter().enumerate() {
-            if measure.attributes.tempo != tempo {
-                write!(&mut map, "\t\t{{ {}, {} }},\n", i, measure.attributes.tempo).unwrap();
-                tempo = measure.attributes.tempo;
-            }
+        for (i, tempo) in calc_tempo_map(&self.parts) {
+            writeln!(&mut map, "\t\t{{ {}, {} }},", i, tempo).unwrap();
         }
         map
     }
 
     pub fn get_measure_count(&self) -> usize {
-        self.parts[0].measures[0].len()
+        self.get_first_staff().len()
     }
-}
 
+    /// Returns the number of parts in the score, before they are split into a track for each staff
+    pub fn get_part_count(&self) -> usize {
+        self.parts.len()
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Write;
+
+use serde::Serialize;
+
+/// Where in the score an issue was found
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Location {
+    /// The index of the part
+    pub part: usize,
+    /// The staff of the part, starting at one
+    pub staff: usize,
+    /// The index of the measure in the part, if the issue is in a single measure
+    pub measure: Option<usize>,
+    /// The line of the MusicXml file the measure starts on, if known
+    pub line: Option<u64>,
+}
+
+/// Something in the score that GJM can't represent exactly
+#[derive(Clone, Debug, Serialize)]
+pub struct Issue {
+    /// A short name for the feature that had to be downgraded
+    pub feature: &'static str,
+    /// A description of where the problem is and what was done about it
+    pub message: String,
+    /// Where the problem is, if it is in one place
+    pub location: Option<Location>,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.feature, self.message)?;
+        if let Some(line) = self.location.and_then(|location| location.line) {
+            write!(f, " (line {})", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// A summary of a single GJM track
+#[derive(Clone, Debug, Serialize)]
+pub struct TrackReport {
+    /// The index of the MusicXml part the track came from
+    pub part: usize,
+    /// The staff of the part the track came from, starting at one
+    pub staff: usize,
+    /// The label of the part the track came from, if it has one
+    pub name: Option<String>,
+    /// The instrument the track is written with
+    pub instrument: String,
+    /// Whether the track was left out of the output
+    pub dropped: bool,
+}
+
+/// Totals describing the notes of a score, for sorting through a collection of scores
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Statistics {
+    /// The number of notes that are played, counting each note of a chord
+    pub note_count: usize,
+    /// The number of rests
+    pub rest_count: usize,
+    /// The lowest pitch index played, if any notes are
+    pub lowest_pitch: Option<u32>,
+    /// The highest pitch index played, if any notes are
+    pub highest_pitch: Option<u32>,
+    /// The number of notes of each note type
+    pub note_types: BTreeMap<String, usize>,
+    /// The number of notes that start or end a tie, leaving out slurs
+    pub tied_count: usize,
+    /// The number of tuplets, counted at the chord each one starts on as that is the chord marked
+    /// with the start of the tuplet
+    pub tuplet_count: usize,
+    /// The number of measures in the score
+    pub measure_count: usize,
+}
+
+/// An auditable record of a single conversion, written next to the .gjm file
+#[derive(Clone, Debug, Serialize)]
+pub struct Report {
+    /// The MusicXml file that was converted
+    pub source: String,
+    /// Every track found in the score
+    pub tracks: Vec<TrackReport>,
+    /// The number of measures in the score
+    pub measure_count: usize,
+    /// The key signature changes as (measure, fifths)
+    pub key_map: Vec<(usize, i32)>,
+    /// The time signature changes as (measure, beats, beat type)
+    pub meter_map: Vec<(usize, u8, u8)>,
+    /// The tempo changes as (measure, beats per minute)
+    pub tempo_map: Vec<(usize, u32)>,
+    /// The number of measures that could not be parsed and were replaced with rests
+    pub skipped_measures: usize,
+    /// Totals describing the notes of the score
+    pub statistics: Statistics,
+    /// The warnings given while converting, such as values that could not be read, along with
+    /// the part and measure they were given in
+    pub warnings: Vec<String>,
+    /// Every problem found along with where it was found
+    pub issues: Vec<Issue>,
+    /// The name of each feature that was downgraded, listed once
+    pub downgraded: Vec<String>,
+}
+
+impl Report {
+    /// Fills in the issues and downgraded features from a list of issues
+    ///
+    /// # Arguments
+    ///
+    /// * 'issues' - The issues found while validating the score
+    ///
+    pub fn add_issues(&mut self, issues: Vec<Issue>) {
+        for issue in issues {
+            if !self.downgraded.iter().any(|feature| feature == issue.feature) {
+                self.downgraded.push(issue.feature.to_string());
+            }
+            self.issues.push(issue);
+        }
+    }
+
+    /// Writes the report as pretty printed JSON
+    pub fn write_json<W: Write>(&self, file: &mut W) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        file.write_all(json.as_bytes())?;
+        file.write_all(b"\n")?;
+        Ok(())
+    }
+}
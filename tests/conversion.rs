@@ -0,0 +1,2046 @@
+use mxl_2_solo::options::{ChordDuration, Glissando, NumberedNotation, Rounding, Swing, TrackLabel, TrackOrder, UnsupportedClef, Verbosity};
+use mxl_2_solo::partwise::Clef;
+use mxl_2_solo::{convert_reader, Options};
+
+/// Wraps the contents of one measure in a score with a single piano part
+fn score(attributes: &str, notes: &str) -> String {
+    format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<score-partwise version="3.1">
+  <part-list>
+    <score-part id="P1"><part-name>Piano</part-name></score-part>
+  </part-list>
+  <part id="P1">
+    <measure number="1">
+      <attributes>
+        <divisions>2</divisions>
+        {}
+      </attributes>
+      {}
+    </measure>
+  </part>
+</score-partwise>"#, attributes, notes)
+}
+
+/// Converts a MusicXml document held in memory with the default options
+fn convert(xml: &str) -> String {
+    convert_with(xml, &Options::default())
+}
+
+/// Converts a MusicXml document held in memory with the given options
+fn convert_with(xml: &str, options: &Options) -> String {
+    let mut output = Vec::new();
+    convert_reader(xml.as_bytes(), &mut output, options).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn single_quarter_note() {
+    let gjm = convert(&score(
+        "<time><beats>1</beats><beat-type>4</beat-type></time><clef><sign>G</sign><line>2</line></clef>",
+        "<note><pitch><step>C</step><octave>4</octave></pitch><duration>2</duration><type>quarter</type></note>",
+    ));
+    assert_eq!(gjm, "Version ='1.1.0.0'
+Notation = {
+	Version ='1.1.0.0',
+	NotationName = 'Unnamed',
+	NotationAuther = 'UnknownAuthor',
+	NotationTranslater = 'UnknownTranslator',
+	NotationCreator = 'Dwarfed',
+	Volume = 1,
+	BeatsPerMeasure = 1,
+	BeatDurationType = '4',
+	NumberedKeySignature = 'C',
+	MeasureBeatsPerMinuteMap = {
+		{ 0, 108 },
+	},
+	MeasureAlignedCount = 1,
+}
+Notation.RegularTracks = {
+	[0] = {
+		-- Piano
+		MeasureKeySignatureMap = {
+			{ 0, 0 },
+		},
+		MeasureClefTypeMap = {
+			{ 0, 'L2G' },
+		},
+		MeasureInstrumentTypeMap = {
+			{ 0, 'Piano' },
+		},
+		MeasureVolumeCurveMap = {
+			{ 0, {0.8, 0.7, 0.5, 0.5, 0.7, 0.6, 0.5, 0.4} },
+		},
+		MeasureVolumeMap = {
+			{ 0, 0.80 },
+		},
+		[0] = {
+			DurationStampMax = 15,
+			NotePackCount = 1,
+			[0] = {
+				DurationType = 'Quarter',
+				StampIndex = 0,
+				ClassicPitchSignCount = 1,
+				ClassicPitchSign = {
+					[40] = { NumberedSign = 1, PlayingPitchIndex = 40, AlterantType = 'Natural', RawAlterantType = 'Natural', },
+				},
+			},
+		},
+	},
+}");
+}
+
+#[test]
+fn c_major_triad() {
+    let gjm = convert(&score(
+        "<time><beats>4</beats><beat-type>4</beat-type></time>",
+        "<note><pitch><step>C</step><octave>4</octave></pitch><duration>8</duration><type>whole</type></note>
+        <note><chord/><pitch><step>E</step><octave>4</octave></pitch><duration>8</duration><type>whole</type></note>
+        <note><chord/><pitch><step>G</step><octave>4</octave></pitch><duration>8</duration><type>whole</type></note>",
+    ));
+    assert!(gjm.contains("
+		[0] = {
+			DurationStampMax = 63,
+			NotePackCount = 1,
+			[0] = {
+				DurationType = 'Whole',
+				StampIndex = 0,
+				ClassicPitchSignCount = 3,
+				ClassicPitchSign = {
+					[40] = { NumberedSign = 1, PlayingPitchIndex = 40, AlterantType = 'Natural', RawAlterantType = 'Natural', },
+					[44] = { NumberedSign = 3, PlayingPitchIndex = 44, AlterantType = 'Natural', RawAlterantType = 'Natural', },
+					[47] = { NumberedSign = 5, PlayingPitchIndex = 47, AlterantType = 'Natural', RawAlterantType = 'Natural', },
+				},
+			},
+		},
+"), "{}", gjm);
+}
+
+#[test]
+fn dotted_half() {
+    let gjm = convert(&score(
+        "<time><beats>3</beats><beat-type>4</beat-type></time>",
+        "<note><pitch><step>D</step><octave>4</octave></pitch><duration>6</duration><type>half</type><dot/></note>",
+    ));
+    assert!(gjm.contains("
+		[0] = {
+			DurationStampMax = 47,
+			NotePackCount = 1,
+			[0] = {
+				IsDotted = true,
+				DurationType = 'Half',
+				StampIndex = 0,
+				ClassicPitchSignCount = 1,
+				ClassicPitchSign = {
+					[42] = { NumberedSign = 2, PlayingPitchIndex = 42, AlterantType = 'Natural', RawAlterantType = 'Natural', },
+				},
+			},
+		},
+"), "{}", gjm);
+}
+
+#[test]
+fn two_staff_measure() {
+    let gjm = convert(&score(
+        "<time><beats>2</beats><beat-type>4</beat-type></time>
+        <staves>2</staves>
+        <clef number=\"1\"><sign>G</sign><line>2</line></clef>
+        <clef number=\"2\"><sign>F</sign><line>4</line></clef>",
+        "<note><pitch><step>E</step><octave>5</octave></pitch><duration>4</duration><type>half</type><staff>1</staff></note>
+        <backup><duration>4</duration></backup>
+        <note><pitch><step>C</step><octave>3</octave></pitch><duration>2</duration><type>quarter</type><staff>2</staff></note>
+        <note><rest/><duration>2</duration><type>quarter</type><staff>2</staff></note>",
+    ));
+    // Each staff becomes its own track with its own clef
+    let (upper, lower) = gjm.split_at(gjm.find("\t[1] = {").unwrap());
+    assert!(upper.contains("\t\t-- Piano (staff 1)\n"));
+    assert!(lower.contains("\t\t-- Piano (staff 2)\n"));
+    assert!(upper.contains("{ 0, 'L2G' },"));
+    assert!(upper.contains("
+		[0] = {
+			DurationStampMax = 31,
+			NotePackCount = 1,
+			[0] = {
+				DurationType = 'Half',
+				StampIndex = 0,
+				ClassicPitchSignCount = 1,
+				ClassicPitchSign = {
+					[56] = { NumberedSign = 3, PlayingPitchIndex = 56, AlterantType = 'Natural', RawAlterantType = 'Natural', },
+				},
+			},
+		},
+"), "{}", gjm);
+    assert!(lower.contains("{ 0, 'L4F' },"));
+    assert!(lower.contains("
+		[0] = {
+			DurationStampMax = 31,
+			NotePackCount = 2,
+			[0] = {
+				DurationType = 'Quarter',
+				StampIndex = 0,
+				ClassicPitchSignCount = 1,
+				ClassicPitchSign = {
+					[28] = { NumberedSign = 1, PlayingPitchIndex = 28, AlterantType = 'Natural', RawAlterantType = 'Natural', },
+				},
+			},
+			[1] = {
+				IsRest = true,
+				DurationType = 'Quarter',
+				StampIndex = 16,
+				ClassicPitchSignCount = 0,
+			},
+		},
+"), "{}", gjm);
+}
+
+#[test]
+fn guitar_sounds_an_octave_lower() {
+    let xml = score(
+        "<clef><sign>G</sign><line>2</line></clef><transpose><diatonic>-7</diatonic><chromatic>-12</chromatic></transpose>",
+        "<note><pitch><step>E</step><octave>4</octave></pitch><duration>8</duration><type>whole</type></note>",
+    );
+    assert!(convert(&xml).contains("[32] = { NumberedSign = 3, PlayingPitchIndex = 32, AlterantType = 'Natural',"));
+    let written = convert_with(&xml, &Options { written_pitch: true, ..Default::default() });
+    assert!(written.contains("[44] = { NumberedSign = 3, PlayingPitchIndex = 44, AlterantType = 'Natural',"));
+}
+
+#[test]
+fn tempo_from_second_part() {
+    let part = |id: &str, measures: &[&str]| {
+        let measures: Vec<String> = measures.iter().enumerate()
+            .map(|(i, notes)| format!(r#"<measure number="{}">{}{}</measure>"#, i + 1,
+                if i == 0 { "<attributes><divisions>1</divisions><time><beats>1</beats><beat-type>4</beat-type></time></attributes>" } else { "" },
+                notes))
+            .collect();
+        format!(r#"<part id="{}">{}</part>"#, id, measures.concat())
+    };
+    let rest = "<note><rest/><duration>1</duration><type>quarter</type></note>";
+    let note = "<note><pitch><step>C</step><octave>4</octave></pitch><duration>1</duration><type>quarter</type></note>";
+    let direction = |tempo: u32| format!(r#"<direction><direction-type><words>Tempo</words></direction-type><sound tempo="{}"/></direction>"#, tempo);
+    let xml = format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<score-partwise version="3.1">
+  <part-list>
+    <score-part id="P1"><part-name>Intro</part-name></score-part>
+    <score-part id="P2"><part-name>Melody</part-name></score-part>
+  </part-list>
+  {}
+  {}
+</score-partwise>"#,
+        part("P1", &[rest, rest, rest]),
+        part("P2", &[&(direction(72) + note), note, &(direction(96) + note)]));
+    let gjm = convert(&xml);
+    assert!(gjm.contains("\tMeasureBeatsPerMinuteMap = {\n\t\t{ 0, 72 },\n\t\t{ 2, 96 },\n\t},\n"));
+}
+
+#[test]
+fn score_without_parts() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<score-partwise version="3.1"><part-list></part-list></score-partwise>"#;
+    let mut output = Vec::new();
+    let error = convert_reader(xml.as_bytes(), &mut output, &Options::default()).unwrap_err();
+    assert_eq!(error.to_string(), "No parts found");
+    assert!(output.is_empty());
+}
+
+#[test]
+fn timewise_matches_partwise() {
+    let part_list = r#"<part-list>
+    <score-part id="P1"><part-name>Violin</part-name></score-part>
+    <score-part id="P2"><part-name>Cello</part-name></score-part>
+  </part-list>"#;
+    let attributes = r#"<attributes><divisions>1</divisions><time><beats>2</beats><beat-type>4</beat-type></time></attributes>"#;
+    let note = |step: &str, octave: u32| format!(
+        "<note><pitch><step>{}</step><octave>{}</octave></pitch><duration>2</duration><type>half</type></note>", step, octave);
+    // The contents of each measure of each part
+    let music = [
+        [format!("{}{}", attributes, note("E", 5)), note("F", 5)],
+        [format!("{}{}", attributes, note("C", 3)), note("D", 3)],
+    ];
+    let partwise = format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<score-partwise version="3.1">
+  {}
+  <part id="P1"><measure number="1">{}</measure><measure number="2">{}</measure></part>
+  <part id="P2"><measure number="1">{}</measure><measure number="2">{}</measure></part>
+</score-partwise>"#, part_list, music[0][0], music[0][1], music[1][0], music[1][1]);
+    let timewise = format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<score-timewise version="3.1">
+  {}
+  <measure number="1"><part id="P1">{}</part><part id="P2">{}</part></measure>
+  <measure number="2"><part id="P1">{}</part><part id="P2">{}</part></measure>
+</score-timewise>"#, part_list, music[0][0], music[1][0], music[0][1], music[1][1]);
+    assert_eq!(convert(&timewise), convert(&partwise));
+}
+
+/// Returns the playing pitches of a GJM document in the order they are written
+fn pitches(gjm: &str) -> Vec<u32> {
+    gjm.split("PlayingPitchIndex = ").skip(1)
+        .map(|rest| rest[..rest.find(',').unwrap()].parse().unwrap())
+        .collect()
+}
+
+#[test]
+fn repeats_with_endings() {
+    let note = |step: &str| format!(
+        "<note><pitch><step>{}</step><octave>4</octave></pitch><duration>8</duration><type>whole</type></note>", step);
+    let xml = score("<time><beats>4</beats><beat-type>4</beat-type></time>", &format!(r#"{}
+    </measure>
+    <measure number="2">
+      <barline location="left"><repeat direction="forward"/></barline>
+      {}
+    </measure>
+    <measure number="3">
+      <barline location="left"><ending number="1" type="start"/></barline>
+      {}
+      <barline location="right"><ending number="1" type="stop"/><repeat direction="backward"/></barline>
+    </measure>
+    <measure number="4">
+      <barline location="left"><ending number="2" type="start"/></barline>
+      {}
+      <barline location="right"><ending number="2" type="discontinue"/></barline>"#,
+        note("C"), note("D"), note("E"), note("F")));
+    assert_eq!(pitches(&convert(&xml)), [40, 42, 44, 45]);
+    let unfolded = convert_with(&xml, &Options { unfold: true, ..Default::default() });
+    assert_eq!(pitches(&unfolded), [40, 42, 44, 42, 45]);
+    assert!(unfolded.contains("\tMeasureAlignedCount = 5,\n"));
+}
+
+#[test]
+fn double_dotted_quarter() {
+    let gjm = convert(&score(
+        "<divisions>4</divisions><time><beats>2</beats><beat-type>4</beat-type></time>",
+        "<note><pitch><step>G</step><octave>4</octave></pitch><duration>7</duration><type>quarter</type><dot/><dot/></note>
+        <note><pitch><step>A</step><octave>4</octave></pitch><duration>1</duration><type>16th</type></note>",
+    ));
+    // GJM has no double dot, so the note is written as a dotted quarter tied to a sixteenth
+    let stamps: Vec<&str> = gjm.lines().filter(|line| line.contains("StampIndex")).map(str::trim).collect();
+    assert_eq!(stamps, ["StampIndex = 0,", "StampIndex = 24,", "StampIndex = 28,"]);
+    assert!(gjm.contains("TieType ='Start',\n\t\t\t\tIsDotted = true,\n\t\t\t\tDurationType = 'Quarter',"), "{}", gjm);
+    assert!(gjm.contains("TieType ='End',\n\t\t\t\tDurationType = 'The16th',"), "{}", gjm);
+}
+
+#[test]
+fn numbered_signs_follow_spelling() {
+    let note = |step: &str, alter: i32| format!(
+        "<note><pitch><step>{}</step><alter>{}</alter><octave>4</octave></pitch><duration>2</duration><type>quarter</type></note>", step, alter);
+    let gjm = convert(&score(
+        "<time><beats>3</beats><beat-type>4</beat-type></time>",
+        &format!("{}{}{}", note("C", 1), note("D", -1), note("E", 1)),
+    ));
+    // C sharp and D flat sound the same but are written on different letters, and E sharp sounds
+    // as F but is still written on E
+    assert!(gjm.contains("[40] = { NumberedSign = 1, PlayingPitchIndex = 41, AlterantType = 'Sharp',"), "{}", gjm);
+    assert!(gjm.contains("[42] = { NumberedSign = 2, PlayingPitchIndex = 41, AlterantType = 'Flat',"), "{}", gjm);
+    assert!(gjm.contains("[44] = { NumberedSign = 3, PlayingPitchIndex = 45, AlterantType = 'Sharp',"), "{}", gjm);
+}
+
+#[test]
+fn instruments_from_part_list() {
+    let score_part = |id: &str, name: &str, program: Option<u32>| format!(
+        r#"<score-part id="{0}"><part-name>{1}</part-name><score-instrument id="{0}-I1"><instrument-name>{1}</instrument-name></score-instrument>{2}</score-part>"#,
+        id, name, program.map_or(String::new(), |program| format!(r#"<midi-instrument id="{}-I1"><midi-program>{}</midi-program></midi-instrument>"#, id, program)));
+    let part = |id: &str| format!(
+        r#"<part id="{}"><measure number="1"><attributes><divisions>1</divisions></attributes><note><rest/><duration>4</duration></note></measure></part>"#, id);
+    let xml = format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<score-partwise version="3.1">
+  <part-list>{}{}{}</part-list>
+  {}{}{}
+</score-partwise>"#,
+        score_part("P1", "Violin", Some(41)), score_part("P2", "Flute", None), score_part("P3", "Theremin", None),
+        part("P1"), part("P2"), part("P3"));
+    let instruments = |gjm: &str| -> Vec<String> {
+        gjm.split("MeasureInstrumentTypeMap = {\n\t\t\t{ 0, '").skip(1)
+            .map(|rest| rest[..rest.find('\'').unwrap()].to_string())
+            .collect()
+    };
+    assert_eq!(instruments(&convert(&xml)), ["Erhu", "Dizi", "Piano"]);
+    let chosen = convert_with(&xml, &Options { instrument: Some("Guzheng".to_string()), ..Default::default() });
+    assert_eq!(instruments(&chosen), ["Guzheng", "Guzheng", "Guzheng"]);
+}
+
+#[test]
+fn forward_offsets_second_voice() {
+    let gjm = convert(&score(
+        "<time><beats>4</beats><beat-type>4</beat-type></time>",
+        "<note><pitch><step>C</step><octave>5</octave></pitch><duration>4</duration><voice>1</voice><type>half</type></note>
+        <note><pitch><step>D</step><octave>5</octave></pitch><duration>4</duration><voice>1</voice><type>half</type></note>
+        <backup><duration>8</duration></backup>
+        <forward><duration>4</duration><voice>2</voice></forward>
+        <note><pitch><step>G</step><octave>4</octave></pitch><duration>4</duration><voice>2</voice><type>half</type></note>",
+    ));
+    // The second voice starts on the third beat, alongside the second note of the first voice
+    let stamps: Vec<&str> = gjm.lines().filter(|line| line.contains("StampIndex")).map(str::trim).collect();
+    assert_eq!(stamps, ["StampIndex = 0,", "StampIndex = 32,", "StampIndex = 32,"]);
+    assert_eq!(pitches(&gjm), [52, 54, 47]);
+}
+
+#[test]
+fn drum_kit() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<score-partwise version="3.1">
+  <part-list>
+    <score-part id="P1">
+      <part-name>Drums</part-name>
+      <score-instrument id="P1-I36"><instrument-name>Bass Drum</instrument-name></score-instrument>
+      <score-instrument id="P1-I39"><instrument-name>Snare</instrument-name></score-instrument>
+      <midi-instrument id="P1-I36"><midi-channel>10</midi-channel><midi-unpitched>36</midi-unpitched></midi-instrument>
+      <midi-instrument id="P1-I39"><midi-channel>10</midi-channel><midi-unpitched>39</midi-unpitched></midi-instrument>
+    </score-part>
+  </part-list>
+  <part id="P1">
+    <measure number="1">
+      <attributes>
+        <divisions>1</divisions>
+        <time><beats>2</beats><beat-type>4</beat-type></time>
+        <clef><sign>percussion</sign></clef>
+      </attributes>
+      <note><unpitched><display-step>F</display-step><display-octave>4</display-octave></unpitched><duration>1</duration><instrument id="P1-I36"/><type>quarter</type></note>
+      <note><unpitched/><duration>1</duration><instrument id="P1-I39"/><type>quarter</type></note>
+    </measure>
+  </part>
+</score-partwise>"#;
+    // Percussion clefs are fine even when unsupported clefs are errors, since GJM has no drum
+    // tracks and drums are written on a treble staff
+    let gjm = convert_with(xml, &Options { unsupported_clef: UnsupportedClef::Error, ..Default::default() });
+    assert!(gjm.contains("{ 0, 'L2G' },"));
+    // The first hit is placed by its staff position and the second by the MIDI key of its drum
+    assert_eq!(pitches(&gjm), [45, 19]);
+}
+
+#[test]
+fn streamed_matches_buffered() {
+    let part = |id: &str, step: &str| format!(
+        r#"<part id="{}"><measure number="1"><attributes><divisions>1</divisions><staves>2</staves></attributes><note><pitch><step>{}</step><octave>4</octave></pitch><duration>4</duration><type>whole</type><staff>2</staff></note></measure></part>"#,
+        id, step);
+    let xml = format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<score-partwise version="3.1">
+  <part-list><score-part id="P1"><part-name>Harp</part-name></score-part><score-part id="P2"><part-name>Cello</part-name></score-part></part-list>
+  {}{}
+</score-partwise>"#, part("P1", "C"), part("P2", "D"));
+    let options = Options { conductor_track: true, max_tracks: Some(4), humanize: 2, stream: true, ..Default::default() };
+    // Parsing on its own keeps every note, while converting writes each part as it is parsed
+    let score = mxl_2_solo::partwise::parse(xml.as_bytes(), &options).unwrap();
+    let mut buffered = Vec::new();
+    score.write_gjm(&mut buffered, &options).unwrap();
+    assert_eq!(convert_with(&xml, &options), String::from_utf8(buffered).unwrap());
+}
+
+#[test]
+fn streamed_matches_buffered_when_unfolding_a_pickup() {
+    let part = |id: &str, step: &str| format!(
+        r#"<part id="{0}">
+    <measure number="0" implicit="yes"><attributes><divisions>1</divisions><time><beats>2</beats><beat-type>4</beat-type></time></attributes><note><pitch><step>{1}</step><octave>4</octave></pitch><duration>1</duration><type>quarter</type></note></measure>
+    <measure number="1"><barline location="left"><repeat direction="forward"/></barline><note><pitch><step>{1}</step><octave>5</octave></pitch><duration>2</duration><type>half</type></note></measure>
+    <measure number="2"><note><pitch><step>{1}</step><octave>3</octave></pitch><duration>2</duration><type>half</type></note><barline location="right"><repeat direction="backward"/></barline></measure>
+  </part>"#,
+        id, step);
+    let xml = format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<score-partwise version="3.1">
+  <part-list><score-part id="P1"><part-name>Flute</part-name></score-part><score-part id="P2"><part-name>Cello</part-name></score-part></part-list>
+  {}{}
+</score-partwise>"#, part("P1", "E"), part("P2", "C"));
+    let buffered = Options { conductor_track: true, unfold: true, ..Default::default() };
+    let streamed = Options { stream: true, ..buffered.clone() };
+    let gjm = convert_with(&xml, &streamed);
+    assert_eq!(gjm, convert_with(&xml, &buffered));
+    // The pickup is played once and the repeated measures twice, on every track
+    let lengths: Vec<&str> = gjm.lines().filter(|line| line.contains("DurationStampMax")).map(str::trim).collect();
+    let track = ["DurationStampMax = 15,", "DurationStampMax = 31,", "DurationStampMax = 31,", "DurationStampMax = 31,", "DurationStampMax = 31,"];
+    assert_eq!(lengths, track.repeat(3));
+    assert_eq!(pitches(&gjm), [44, 56, 32, 56, 32, 40, 52, 28, 52, 28]);
+}
+
+#[test]
+fn title_and_composer() {
+    let xml = score("", "<note><rest/><duration>8</duration></note>").replace("<part-list>", r#"<work><work-title>Jasmine Flower</work-title></work>
+  <movement-title>Movement</movement-title>
+  <identification>
+    <creator type="lyricist">Anon</creator>
+    <creator type="composer">Traditional</creator>
+    <creator type="composer">Arr. O'Brien</creator>
+  </identification>
+  <part-list>"#);
+    let gjm = convert(&xml);
+    assert!(gjm.contains("\tNotationName = 'Jasmine Flower',\n\tNotationAuther = 'Traditional, Arr. O\\'Brien',\n"), "{}", gjm);
+    // Scores without a title or composer keep the defaults
+    let untitled = convert(&score("", "<note><rest/><duration>8</duration></note>"));
+    assert!(untitled.contains("\tNotationName = 'Unnamed',\n\tNotationAuther = 'UnknownAuthor',\n"));
+}
+
+#[test]
+fn divisions_change_part_way() {
+    let note = |duration: u32, note_type: &str| format!(
+        "<note><pitch><step>A</step><octave>4</octave></pitch><duration>{}</duration><type>{}</type></note>", duration, note_type);
+    let quarters = |duration: u32| note(duration, "quarter").repeat(2);
+    let mut measures = vec![quarters(2); 3];
+    // The divisions double at the start of measure 5 and again part way through measure 6
+    measures.push(format!("<attributes><divisions>4</divisions></attributes>{}", quarters(4)));
+    measures.push(format!("{}<attributes><divisions>8</divisions></attributes>{}", note(4, "quarter"), note(8, "quarter")));
+    measures.push(format!("{}{}{}", note(4, "eighth"), note(4, "eighth"), note(8, "quarter")));
+    let measures: Vec<String> = measures.iter().enumerate()
+        .map(|(i, notes)| format!(r#"</measure><measure number="{}">{}"#, i + 2, notes))
+        .collect();
+    let gjm = convert(&score("<time><beats>2</beats><beat-type>4</beat-type></time>", &(quarters(2) + &measures.concat())));
+    let stamps: Vec<&str> = gjm.lines().filter(|line| line.contains("StampIndex")).map(str::trim).collect();
+    let mut expected = ["StampIndex = 0,", "StampIndex = 16,"].repeat(6);
+    expected.extend(["StampIndex = 0,", "StampIndex = 8,", "StampIndex = 16,"]);
+    assert_eq!(stamps, expected, "{}", gjm);
+    assert!(gjm.contains("\tMeasureAlignedCount = 7,\n"));
+}
+
+#[test]
+fn cue_notes_are_left_out() {
+    let gjm = convert(&score(
+        "<time><beats>3</beats><beat-type>4</beat-type></time>",
+        "<note><pitch><step>C</step><octave>4</octave></pitch><duration>2</duration><voice>1</voice><type>quarter</type></note>
+        <note><cue/><pitch><step>B</step><octave>5</octave></pitch><duration>2</duration><voice>1</voice><type>quarter</type></note>
+        <note><chord/><cue/><pitch><step>D</step><octave>6</octave></pitch><duration>2</duration><voice>1</voice><type>quarter</type></note>
+        <note><pitch><step>E</step><octave>4</octave></pitch><duration>2</duration><voice>1</voice><type>quarter</type></note>",
+    ));
+    // The cue chord isn't played but the note after it still starts on the third beat
+    let stamps: Vec<&str> = gjm.lines().filter(|line| line.contains("StampIndex")).map(str::trim).collect();
+    assert_eq!(stamps, ["StampIndex = 0,", "StampIndex = 32,"]);
+    assert_eq!(pitches(&gjm), [40, 44]);
+}
+
+#[test]
+fn dynamics_apply_from_their_position() {
+    let dynamic = |mark: &str, sound: &str| format!(
+        "<direction><direction-type><dynamics><{}/></dynamics></direction-type>{}</direction>", mark, sound);
+    let note = "<note><pitch><step>C</step><octave>4</octave></pitch><duration>4</duration><type>half</type></note>";
+    let gjm = convert(&score(
+        "<time><beats>4</beats><beat-type>4</beat-type></time>",
+        &format!(r#"{}{}{}{}</measure><measure number="2">{}"#,
+            dynamic("p", ""), note, dynamic("f", r#"<sound dynamics="88"/>"#), note, note),
+    ));
+    // The mark without a sound is played at its usual volume, and the forte marked half way
+    // through the first measure is heard from the second
+    assert!(gjm.contains("\t\tMeasureVolumeMap = {\n\t\t\t{ 0, 0.40 },\n\t\t\t{ 1, 0.88 },\n\t\t},\n"), "{}", gjm);
+}
+
+#[test]
+fn octave_treble_clef() {
+    let clef = "<clef><sign>G</sign><line>2</line><clef-octave-change>-1</clef-octave-change></clef>";
+    let note = "<note><pitch><step>E</step><octave>4</octave></pitch><duration>8</duration><type>whole</type></note>";
+    assert_eq!(pitches(&convert(&score(clef, note))), [32]);
+    // Parts that give their transposition aren't moved by the clef as well
+    let transposed = format!("{}<transpose><diatonic>-7</diatonic><chromatic>-12</chromatic></transpose>", clef);
+    assert_eq!(pitches(&convert(&score(&transposed, note))), [32]);
+    assert_eq!(pitches(&convert(&score("<clef><sign>G</sign><line>2</line></clef>", note))), [44]);
+}
+
+#[test]
+fn c_clefs() {
+    let clefs = |signs: &[(&str, u32)]| -> String {
+        let staves: Vec<String> = signs.iter().enumerate()
+            .map(|(i, (sign, line))| format!(r#"<clef number="{}"><sign>{}</sign><line>{}</line></clef>"#, i + 1, sign, line))
+            .collect();
+        format!("<staves>{}</staves>{}", signs.len(), staves.concat())
+    };
+    let gjm = convert_with(
+        &score(&clefs(&[("C", 3), ("C", 4), ("C", 1), ("TAB", 5)]), "<note><rest/><duration>8</duration></note>"),
+        &Options { unsupported_clef: UnsupportedClef::Error, max_tracks: Some(4), ..Default::default() },
+    );
+    // Alto and tenor clefs are written as they are, and the soprano and tab clefs GJM doesn't have
+    // become treble clefs
+    let written: Vec<&str> = gjm.split("MeasureClefTypeMap = {\n\t\t\t{ 0, '").skip(1).map(|rest| &rest[..3]).collect();
+    assert_eq!(written, ["L3C", "L4C", "L2G", "L2G"]);
+}
+
+#[test]
+fn bass_clef() {
+    let gjm = convert(&score("<clef><sign>F</sign><line>4</line></clef>", "<note><rest/><duration>8</duration></note>"));
+    assert!(gjm.contains("\t\tMeasureClefTypeMap = {\n\t\t\t{ 0, 'L4F' },\n\t\t},\n"), "{}", gjm);
+}
+
+#[test]
+fn check_lists_what_wont_convert() {
+    let quintuplet = |tuplet: &str| format!(
+        "<note><pitch><step>C</step><octave>4</octave></pitch><duration>1</duration><type>16th</type><time-modification><actual-notes>5</actual-notes><normal-notes>4</normal-notes></time-modification>{}</note>",
+        tuplet);
+    let xml = score(
+        "<time><beats>1</beats><beat-type>4</beat-type></time><staves>4</staves>",
+        &format!("{}{}{}{}{}", quintuplet(r#"<notations><tuplet type="start"/></notations>"#),
+            quintuplet(""), quintuplet(""), quintuplet(""), quintuplet(r#"<notations><tuplet type="stop"/></notations>"#)),
+    ).replace("<divisions>2</divisions>", "<divisions>5</divisions>");
+    let issues = mxl_2_solo::partwise::check_reader(xml.as_bytes(), &Options::default()).unwrap();
+    let features: Vec<&str> = issues.iter().map(|issue| issue.feature).collect();
+    assert_eq!(features, ["track count", "tuplets"]);
+    assert_eq!(issues[1].message, "Part 0 staff 1 measure 0 has a tuplet of 5 notes in the time of 4, which is marked as a triplet");
+}
+
+#[test]
+fn pickup_measure() {
+    let note = |duration: u32, note_type: &str, staff: u32| format!(
+        "<note><pitch><step>G</step><octave>4</octave></pitch><duration>{}</duration><type>{}</type><staff>{}</staff></note>", duration, note_type, staff);
+    let xml = score(
+        "<time><beats>4</beats><beat-type>4</beat-type></time><staves>2</staves>",
+        &format!(r#"{}</measure><measure number="1">{}<backup><duration>8</duration></backup>{}"#,
+            note(2, "quarter", 1), note(8, "whole", 1), note(8, "whole", 2)),
+    ).replacen(r#"<measure number="1">"#, r#"<measure number="0" implicit="yes">"#, 1);
+    let gjm = convert_with(&xml, &Options { conductor_track: true, ..Default::default() });
+    // The pickup only lasts a beat on every track, including the empty staff and the conductor
+    let lengths: Vec<&str> = gjm.lines().filter(|line| line.contains("DurationStampMax")).map(str::trim).collect();
+    assert_eq!(lengths, ["DurationStampMax = 15,", "DurationStampMax = 63,"].repeat(3), "{}", gjm);
+}
+
+#[test]
+fn trilled_half_note() {
+    let xml = score(
+        "<time><beats>2</beats><beat-type>4</beat-type></time>",
+        "<note><pitch><step>D</step><octave>5</octave></pitch><duration>4</duration><type>half</type><notations><ornaments><trill-mark/><accidental-mark>flat</accidental-mark></ornaments></notations></note>",
+    );
+    // GJM has no trills, so the note is played plain and the trill is reported
+    assert_eq!(pitches(&convert(&xml)), [54]);
+    let issues = mxl_2_solo::partwise::check_reader(xml.as_bytes(), &Options::default()).unwrap();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].message, "Part 0 staff 1 measure 0 has a trill-mark ornament with its auxiliary note altered by -1 half steps, which GJM does not support");
+}
+
+#[test]
+fn fermata_is_reported() {
+    let xml = score(
+        "<time><beats>4</beats><beat-type>4</beat-type></time>",
+        "<note><pitch><step>C</step><octave>5</octave></pitch><duration>8</duration><type>whole</type><notations><fermata type=\"upright\"/></notations></note>",
+    );
+    let issues = mxl_2_solo::partwise::check_reader(xml.as_bytes(), &Options::default()).unwrap();
+    let features: Vec<&str> = issues.iter().map(|issue| issue.feature).collect();
+    assert_eq!(features, ["fermatas"]);
+    // The held note keeps its written length
+    assert!(convert(&xml).contains("DurationStampMax = 63,"));
+}
+
+/// Returns the entries of a track map of a GJM document, such as "KeySignature", as they are written
+fn track_map<'a>(gjm: &'a str, name: &str) -> Vec<&'a str> {
+    let start = gjm.find(&format!("\t\tMeasure{}Map = {{\n", name)).unwrap();
+    gjm[start..].lines().skip(1).take_while(|line| line.trim() != "},").map(str::trim).collect()
+}
+
+#[test]
+fn measure_maps_hold_changes_only() {
+    let rest = "<note><rest/><duration>8</duration></note>";
+    let constant: String = (2..=7).map(|i| format!(r#"</measure><measure number="{}">{}"#, i, rest)).collect();
+    let gjm = convert(&score("<key><fifths>2</fifths></key>", &(rest.to_string() + &constant)));
+    assert_eq!(track_map(&gjm, "KeySignature"), ["{ 0, 2 },"]);
+    assert_eq!(track_map(&gjm, "ClefType"), ["{ 0, 'L2G' },"]);
+    assert_eq!(track_map(&gjm, "Volume"), ["{ 0, 0.80 },"]);
+
+    // The key, clef and volume change at the fourth measure and change back at the seventh
+    let change = r#"<attributes><key><fifths>-3</fifths></key><clef><sign>F</sign><line>4</line></clef></attributes><direction><direction-type><dynamics><p/></dynamics></direction-type></direction>"#;
+    let back = r#"<attributes><key><fifths>2</fifths></key><clef><sign>G</sign><line>2</line></clef></attributes><direction><direction-type><dynamics><f/></dynamics></direction-type></direction>"#;
+    let changing: String = (2..=7)
+        .map(|i| format!(r#"</measure><measure number="{}">{}{}"#, i, match i { 4 => change, 7 => back, _ => "" }, rest))
+        .collect();
+    let gjm = convert(&score("<key><fifths>2</fifths></key>", &(rest.to_string() + &changing)));
+    assert_eq!(track_map(&gjm, "KeySignature"), ["{ 0, 2 },", "{ 3, -3 },", "{ 6, 2 },"]);
+    assert_eq!(track_map(&gjm, "ClefType"), ["{ 0, 'L2G' },", "{ 3, 'L4F' },", "{ 6, 'L2G' },"]);
+    assert_eq!(track_map(&gjm, "Volume"), ["{ 0, 0.80 },", "{ 3, 0.40 },", "{ 6, 0.80 },"]);
+}
+
+#[test]
+fn clef_change_part_way_through() {
+    let note = |step: &str, octave: u32, beats: u32| format!(
+        "<note><pitch><step>{}</step><octave>{}</octave></pitch><duration>{}</duration>{}</note>", step, octave, beats * 2,
+        if beats == 3 { "<type>half</type><dot/>" } else { "<type>quarter</type>" });
+    let treble = "<attributes><clef><sign>G</sign><line>2</line></clef></attributes>";
+    let bass = "<attributes><clef><sign>F</sign><line>4</line></clef></attributes>";
+    // The first measure spends three beats in treble, and the second measure three beats in bass
+    let gjm = convert(&score(
+        "<time><beats>4</beats><beat-type>4</beat-type></time><clef><sign>F</sign><line>4</line></clef>",
+        &format!(r#"{}{}{}</measure><measure number="2">{}{}{}"#,
+            note("C", 3, 1), treble, note("G", 4, 3),
+            note("A", 4, 1), bass, note("D", 3, 3)),
+    ));
+    assert_eq!(track_map(&gjm, "ClefType"), ["{ 0, 'L2G' },", "{ 1, 'L4F' },"]);
+    // The notes sound the same whichever clef they are written in
+    assert_eq!(pitches(&gjm), [28, 47, 49, 30]);
+}
+
+#[test]
+fn verbosity_leaves_the_output_alone() {
+    let xml = score("", r#"<note><pitch><step>C</step><octave>4</octave></pitch><duration>8</duration><type>whole</type></note><print/>"#);
+    let quiet = Options::from_args(std::iter::once("--quiet".to_string()));
+    let verbose = Options::from_args(std::iter::once("-v".to_string()));
+    assert_eq!(quiet.verbosity, Verbosity::Quiet);
+    assert_eq!(verbose.verbosity, Verbosity::Verbose);
+    assert_eq!(convert_with(&xml, &quiet), convert(&xml));
+    assert_eq!(convert_with(&xml, &verbose), convert(&xml));
+}
+
+#[test]
+fn chord_with_mixed_durations() {
+    let xml = score("", r#"
+        <note><pitch><step>C</step><octave>4</octave></pitch><duration>4</duration><type>half</type></note>
+        <note><chord/><pitch><step>E</step><octave>4</octave></pitch><duration>2</duration><type>quarter</type></note>
+        <note><pitch><step>D</step><octave>4</octave></pitch><duration>2</duration><type>quarter</type></note>"#);
+    let fields = |gjm: &str, field: &str| -> Vec<String> {
+        gjm.lines()
+            .filter_map(|line| line.trim().strip_prefix(field))
+            .map(|value| value.trim_end_matches(',').to_string())
+            .collect()
+    };
+    // The next note starts after the shorter note of the chord, so by default the chord ends there
+    let gjm = convert(&xml);
+    assert_eq!(fields(&gjm, "DurationType = "), ["'Quarter'", "'Quarter'"]);
+    assert_eq!(fields(&gjm, "StampIndex = "), ["0", "16"]);
+    let longest = Options { chord_duration: ChordDuration::Longest, ..Options::default() };
+    let gjm = convert_with(&xml, &longest);
+    assert_eq!(fields(&gjm, "DurationType = ")[0], "'Half'");
+    assert_eq!(pitches(&gjm)[..2], [40, 44]);
+}
+
+#[test]
+fn playback_tie_without_notation() {
+    let note = |tie: &str| format!(
+        r#"<note><pitch><step>G</step><octave>4</octave></pitch><duration>4</duration><tie type="{}"/><type>half</type></note>"#, tie);
+    let gjm = convert(&score("", &format!("{}{}", note("start"), note("stop"))));
+    assert!(gjm.contains("TieType ='Start',\n\t\t\t\tDurationType = 'Half',"), "{}", gjm);
+    assert!(gjm.contains("TieType ='End',\n\t\t\t\tDurationType = 'Half',"), "{}", gjm);
+}
+
+#[test]
+fn lyrics_written_beside_the_output() {
+    let note = |step: &str, syllabic: &str, text: &str| format!(
+        r#"<note><pitch><step>{}</step><octave>4</octave></pitch><duration>2</duration><type>quarter</type><lyric number="1"><syllabic>{}</syllabic><text>{}</text></lyric></note>"#,
+        step, syllabic, text);
+    let xml = score("", &format!(r#"{}{}{}{}</measure><measure number="2">{}{}"#,
+        note("C", "begin", "Twin"), note("C", "end", "kle,"), note("G", "begin", "twin"), note("G", "end", "kle"),
+        note("A", "begin", "lit"), note("A", "end", "tle")));
+    let dir = std::env::temp_dir().join(format!("mxl_2_solo_lyrics_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let output = dir.join("song.gjm");
+    let options = Options { lyrics: true, ..Options::default() };
+    mxl_2_solo::partwise::convert_reader_to_path(xml.as_bytes(), "song", &output, &options).unwrap();
+    let lyrics = std::fs::read_to_string(dir.join("song.txt")).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(lyrics, "-- Piano, verse 1\n[0] Twin-kle, twin-kle\n[1] lit-tle\n\n");
+}
+
+#[test]
+fn cue_sized_notes_are_left_out() {
+    let xml = score(
+        "<time><beats>3</beats><beat-type>4</beat-type></time>",
+        r#"<note><pitch><step>C</step><octave>4</octave></pitch><duration>2</duration><type>quarter</type></note>
+        <note><pitch><step>B</step><octave>5</octave></pitch><duration>2</duration><type size="cue">quarter</type></note>
+        <note><grace/><pitch><step>D</step><octave>4</octave></pitch><type size="cue">eighth</type></note>
+        <note><pitch><step>E</step><octave>4</octave></pitch><duration>2</duration><type>quarter</type></note>"#,
+    );
+    // The small note isn't played but the note after it still starts on the third beat
+    let gjm = convert(&xml);
+    let stamps: Vec<&str> = gjm.lines().filter(|line| line.contains("StampIndex")).map(str::trim).collect();
+    assert_eq!(stamps, ["StampIndex = 0,", "StampIndex = 32,"]);
+    assert_eq!(pitches(&gjm), [40, 44]);
+    // The small grace note is still a grace note rather than an editorial one
+    let issues = mxl_2_solo::partwise::check_reader(xml.as_bytes(), &Options::default()).unwrap();
+    assert!(issues.iter().any(|issue| issue.feature == "grace notes"));
+}
+
+#[test]
+fn part_groups() {
+    let part = |id: &str| format!(
+        r#"<part id="{}"><measure number="1"><attributes><divisions>1</divisions></attributes><note><rest/><duration>4</duration></note></measure></part>"#, id);
+    let xml = format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<score-partwise version="3.1">
+  <part-list>
+    <score-part id="P1"><part-name>Flute</part-name></score-part>
+    <part-group type="start" number="1"><group-name>Strings</group-name><group-symbol>bracket</group-symbol></part-group>
+    <score-part id="P2"><part-name>Violin</part-name></score-part>
+    <part-group type="start" number="2"><group-symbol>none</group-symbol></part-group>
+    <score-part id="P3"><part-name>Cello</part-name></score-part>
+    <part-group type="stop" number="2"/>
+    <part-group type="stop" number="1"/>
+  </part-list>
+  {}{}{}
+</score-partwise>"#, part("P1"), part("P2"), part("P3"));
+    let score = mxl_2_solo::partwise::parse(xml.as_bytes(), &Options::default()).unwrap();
+    let groups = score.get_part_groups();
+    assert_eq!(groups.len(), 2);
+    assert_eq!((groups[0].name.as_deref(), groups[0].symbol.as_deref(), &groups[0].parts[..]), (Some("Strings"), Some("bracket"), &[1, 2][..]));
+    assert_eq!((groups[1].name.as_deref(), groups[1].symbol.as_deref(), &groups[1].parts[..]), (None, None, &[2][..]));
+}
+
+#[test]
+fn crlf_line_endings() {
+    let xml = score("", "<note><pitch><step>C</step><octave>4</octave></pitch><duration>8</duration><type>whole</type></note>")
+        .replace("<part-list>", "<work><work-title>Für Elise – Dvořák</work-title></work><part-list>");
+    let crlf = convert_with(&xml, &Options { crlf: true, ..Options::default() });
+    let lf = convert(&xml);
+    assert!(crlf.contains("NotationName = 'Für Elise – Dvořák',\r\n"), "{}", crlf);
+    assert!(!crlf.replace("\r\n", "").contains('\n'));
+    assert_eq!(crlf.replace("\r\n", "\n"), lf);
+}
+
+#[test]
+fn whole_measure_rests() {
+    let rest = |duration: &str| format!(r#"<note><rest measure="yes"/>{}<type>whole</type></note>"#, duration);
+    // An empty 4/4 measure without a duration, then a 3/4 measure whose rest is typed as a whole
+    let gjm = convert(&score(
+        "<time><beats>4</beats><beat-type>4</beat-type></time>",
+        &format!(r#"{}</measure><measure number="2"><attributes><time><beats>3</beats><beat-type>4</beat-type></time></attributes>{}"#,
+            rest(""), rest("<duration>6</duration>")),
+    ));
+    assert!(gjm.contains("IsRest = true,\n\t\t\t\tDurationType = 'Whole',\n\t\t\t\tStampIndex = 0,"), "{}", gjm);
+    assert!(gjm.contains("IsRest = true,\n\t\t\t\tIsDotted = true,\n\t\t\t\tDurationType = 'Half',"), "{}", gjm);
+    assert_eq!(gjm.matches("DurationStampMax = 63,").count(), 1);
+    assert_eq!(gjm.matches("DurationStampMax = 47,").count(), 1);
+}
+
+#[test]
+fn backup_after_shortened_chord() {
+    let note = |step: &str, octave: u32, duration: u32, note_type: &str, staff: u32, chord: &str| format!(
+        "<note>{}<pitch><step>{}</step><octave>{}</octave></pitch><duration>{}</duration><type>{}</type><staff>{}</staff></note>",
+        chord, step, octave, duration, note_type, staff);
+    // The right hand's first chord is cut to its shorter note, and the left hand only comes in on
+    // the third beat, so the backup rewinds half the measure from where the file has got to
+    let gjm = convert(&score(
+        "<time><beats>4</beats><beat-type>4</beat-type></time><staves>2</staves>",
+        &format!("{}{}{}<backup><duration>4</duration></backup>{}{}",
+            note("C", 5, 4, "half", 1, ""), note("E", 5, 2, "quarter", 1, "<chord/>"), note("G", 5, 4, "half", 1, ""),
+            note("C", 3, 2, "quarter", 2, ""), note("E", 3, 2, "quarter", 2, "")),
+    ));
+    let lower = &gjm[gjm.find("\n\t[1] = {").unwrap()..];
+    let stamps: Vec<&str> = lower.lines().filter(|line| line.contains("StampIndex")).map(str::trim).collect();
+    assert_eq!(stamps, ["StampIndex = 32,", "StampIndex = 48,"]);
+}
+
+#[test]
+fn f_double_sharp() {
+    let gjm = convert(&score(
+        "",
+        "<note><pitch><step>F</step><alter>2</alter><octave>4</octave></pitch><duration>8</duration><type>whole</type></note>",
+    ));
+    // The note keeps its F spelling but sounds as a G
+    assert!(gjm.contains("[45] = { NumberedSign = 4, PlayingPitchIndex = 47, AlterantType = 'DoubleSharp', RawAlterantType = 'DoubleSharp', },"), "{}", gjm);
+}
+
+#[test]
+fn wrong_argument_count_is_an_error() {
+    let args = |list: &[&str]| Options::from_args(list.iter().map(|arg| arg.to_string()));
+    let help = args(&["--help"]);
+    assert!(help.show_usage && !help.usage_error);
+    let paths = args(&["in.musicxml", "out.gjm"]);
+    assert!(!paths.show_usage && !paths.usage_error);
+    let too_many = args(&["in.musicxml", "out.gjm", "extra"]);
+    assert!(too_many.show_usage && too_many.usage_error);
+}
+
+/// Packs a MusicXml document into a compressed MusicXml archive held in memory
+fn zipped(xml: &str) -> Vec<u8> {
+    use std::io::Write;
+    let mut archive = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    archive.start_file("META-INF/container.xml", zip::write::FileOptions::default()).unwrap();
+    archive.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
+<container><rootfiles><rootfile full-path="score.xml"/></rootfiles></container>"#).unwrap();
+    archive.start_file("score.xml", zip::write::FileOptions::default()).unwrap();
+    archive.write_all(xml.as_bytes()).unwrap();
+    archive.finish().unwrap().into_inner()
+}
+
+/// Runs the program on a document piped through stdin and returns what it writes to stdout
+fn convert_stdin(input: &[u8], args: &[&str]) -> String {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mxl_2_solo"))
+        .args(["-", "--stdout"])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn stdin_input_formats() {
+    let xml = score(
+        "<time><beats>2</beats><beat-type>4</beat-type></time><clef><sign>G</sign><line>2</line></clef>",
+        "<note><pitch><step>E</step><octave>4</octave></pitch><duration>4</duration><type>half</type></note>",
+    );
+    let expected = convert(&xml);
+    let compressed = zipped(&xml);
+    assert_eq!(convert_stdin(xml.as_bytes(), &[]), expected);
+    assert_eq!(convert_stdin(xml.as_bytes(), &["--input-format", "xml"]), expected);
+    assert_eq!(convert_stdin(&compressed, &[]), expected);
+    assert_eq!(convert_stdin(&compressed, &["--input-format", "mxl"]), expected);
+    assert_eq!(convert_stdin(&compressed, &["--input-format=mxl"]), expected);
+}
+
+#[test]
+fn meter_change_part_way_through() {
+    let xml = score(
+        "<time><beats>2</beats><beat-type>4</beat-type></time><clef><sign>G</sign><line>2</line></clef>",
+        r#"<note><pitch><step>C</step><octave>4</octave></pitch><duration>4</duration><type>half</type></note>
+      </measure>
+      <measure number="2">
+        <attributes><time><beats>3</beats><beat-type>4</beat-type></time></attributes>
+        <note><pitch><step>D</step><octave>4</octave></pitch><duration>6</duration><type>half</type><dot/></note>"#,
+    );
+    // The meter reaches GJM through the length of each measure
+    let gjm = convert(&xml);
+    assert!(gjm.contains("\tBeatsPerMeasure = 2,\n"));
+    assert!(gjm.contains("DurationStampMax = 31,") && gjm.contains("DurationStampMax = 47,"));
+    assert!(!gjm.contains("MeasureTimeSignatureMap"));
+
+    let options = Options {meter_map: true, ..Options::default()};
+    let gjm = convert_with(&xml, &options);
+    assert!(gjm.contains("\tMeasureTimeSignatureMap = {\n\t\t{ 0, 2, '4' },\n\t\t{ 1, 3, '4' },\n\t},\n"));
+}
+
+#[test]
+fn arpeggiated_chord() {
+    let gjm = convert(&score(
+        "<time><beats>2</beats><beat-type>4</beat-type></time><clef><sign>G</sign><line>2</line></clef>",
+        r#"<note><pitch><step>C</step><octave>4</octave></pitch><duration>4</duration><type>half</type><notations><arpeggiate/></notations></note>
+      <note><chord/><pitch><step>E</step><octave>4</octave></pitch><duration>4</duration><type>half</type><notations><arpeggiate/></notations></note>"#,
+    ));
+    assert_eq!(gjm.matches("ArpeggioMode ='Upward',").count(), 1);
+    // GJM has no setting for how fast a chord rolls
+    assert!(!gjm.contains("ArpeggioSpread"));
+}
+
+#[test]
+fn panned_part_is_reported() {
+    let xml = score(
+        "<time><beats>2</beats><beat-type>4</beat-type></time><clef><sign>G</sign><line>2</line></clef>",
+        r#"<note><pitch><step>C</step><octave>4</octave></pitch><duration>4</duration><type>half</type></note>
+      </measure>
+      <measure number="2">
+        <direction><direction-type><words>left</words></direction-type><sound pan="-45"/></direction>
+        <note><pitch><step>D</step><octave>4</octave></pitch><duration>4</duration><type>half</type></note>"#,
+    );
+    // GJM has no stereo placement, so nothing about it is written
+    let gjm = convert(&xml);
+    assert!(!gjm.contains("Pan"));
+    assert_eq!(gjm, convert(&xml.replace(r#"<sound pan="-45"/>"#, "")));
+
+    let issues = mxl_2_solo::partwise::check_reader(xml.as_bytes(), &Options::default()).unwrap();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].feature, "stereo placement");
+    assert_eq!(issues[0].location.unwrap().measure, Some(1));
+    assert!(issues[0].message.contains("a pan of -45 degrees"));
+}
+
+#[test]
+fn report_structure() {
+    let xml = score(
+        "<time><beats>2</beats><beat-type>4</beat-type></time><clef><sign>G</sign><line>2</line></clef>",
+        r#"<direction><direction-type><words>left</words></direction-type><sound pan="-45"/></direction>
+      <note><pitch><step>C</step><octave>4</octave></pitch><duration>2</duration><type>quarter</type><notations><fermata/></notations></note>
+      <note><rest/><duration>2</duration><type>quarter</type></note>"#,
+    );
+    let dir = std::env::temp_dir().join(format!("mxl_2_solo_report_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let options = Options { report: true, ..Options::default() };
+    mxl_2_solo::partwise::convert_reader_to_path(xml.as_bytes(), "song.musicxml", &dir.join("song.gjm"), &options).unwrap();
+    let report = std::fs::read_to_string(dir.join("song.json")).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let report: serde_json::Value = serde_json::from_str(&report).unwrap();
+    assert_eq!(report["source"], "song.musicxml");
+    assert_eq!(report["tracks"], serde_json::json!([
+        { "part": 0, "staff": 1, "name": "Piano", "instrument": "Piano", "dropped": false },
+    ]));
+    assert_eq!(report["measure_count"], 1);
+    assert_eq!(report["key_map"], serde_json::json!([[0, 0]]));
+    assert_eq!(report["meter_map"], serde_json::json!([[0, 2, 4]]));
+    assert_eq!(report["tempo_map"], serde_json::json!([[0, 108]]));
+    assert_eq!(report["skipped_measures"], 0);
+    assert_eq!(report["statistics"]["note_count"], 1);
+    assert_eq!(report["statistics"]["rest_count"], 1);
+    // Warnings given while reading are kept apart from what validating the score finds
+    assert_eq!(report["warnings"], serde_json::json!([
+        "Part 0, measure 0: GJM has no stereo placement, so a pan of -45 degrees is dropped",
+    ]));
+    let features: Vec<&str> = report["issues"].as_array().unwrap().iter().map(|issue| issue["feature"].as_str().unwrap()).collect();
+    assert_eq!(features, ["stereo placement", "fermatas"]);
+    assert_eq!(report["issues"][1]["location"], serde_json::json!({ "part": 0, "staff": 1, "measure": 0, "line": 7 }));
+    assert_eq!(report["downgraded"], serde_json::json!(["stereo placement", "fermatas"]));
+}
+
+#[test]
+fn batch_converts_a_directory() {
+    let note = |step: &str| format!("<note><pitch><step>{}</step><octave>4</octave></pitch><duration>8</duration><type>whole</type></note>", step);
+    let plain = score("", &note("C"));
+    let panned = score("", &format!(r#"<direction><direction-type><words>left</words></direction-type><sound pan="-45"/></direction>{}"#, note("D")));
+    let dir = std::env::temp_dir().join(format!("mxl_2_solo_batch_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("a.musicxml"), &plain).unwrap();
+    std::fs::write(dir.join("b.xml"), &panned).unwrap();
+    std::fs::write(dir.join("c.xml"), "<score-partwise><part-list>").unwrap();
+    std::fs::write(dir.join("notes.txt"), "not a score").unwrap();
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_mxl_2_solo"))
+        .arg(format!("--batch={}", dir.display()))
+        .output()
+        .unwrap();
+    let a = std::fs::read_to_string(dir.join("a.gjm")).unwrap();
+    let b = std::fs::read_to_string(dir.join("b.gjm")).unwrap();
+    let c_written = dir.join("c.gjm").exists();
+    let txt_written = dir.join("notes.gjm").exists();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(a, convert(&plain));
+    assert_eq!(b, convert(&panned));
+    assert!(!c_written && !txt_written);
+    // The broken file fails the whole run
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.ends_with("Converted 2 of 3 files\n"), "{}", stdout);
+    // Every warning and error is printed under the name of the file it came from
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let lines: Vec<&str> = stderr.lines().collect();
+    assert_eq!(lines.len(), 2, "{}", stderr);
+    assert_eq!(lines[0], format!("{}: Warning! Part 0, measure 0: GJM has no stereo placement, so a pan of -45 degrees is dropped", dir.join("b.xml").display()));
+    assert!(lines[1].starts_with(&format!("{}: Error: ", dir.join("c.xml").display())), "{}", stderr);
+}
+
+#[test]
+fn lossless_reports_a_clamped_note() {
+    let note = |step: &str, duration: u32, note_type: &str| format!(
+        "<note><pitch><step>{}</step><octave>4</octave></pitch><duration>{}</duration><type>{}</type></note>", step, duration, note_type);
+    let options = Options { lossless: true, ..Options::default() };
+    let exact = score("<time><beats>1</beats><beat-type>4</beat-type></time>", &format!("{}{}", note("C", 1, "eighth"), note("D", 1, "eighth")));
+    assert!(convert_reader(exact.as_bytes(), &mut Vec::new(), &options).is_ok());
+
+    // A 128th is written as a 64th, which also moves the note after it off its stamp
+    let xml = score("<time><beats>1</beats><beat-type>4</beat-type></time>", &format!("{}{}", note("C", 1, "128th"), note("D", 31, "eighth")))
+        .replace("<divisions>2</divisions>", "<divisions>32</divisions>");
+    let issues = match convert_reader(xml.as_bytes(), &mut Vec::new(), &options) {
+        Err(mxl_2_solo::Error::Lossy(issues)) => issues,
+        result => panic!("expected a lossy conversion, got {:?}", result),
+    };
+    let messages: Vec<String> = issues.iter().map(ToString::to_string).collect();
+    assert_eq!(messages, [
+        "note types: Part 0 staff 1 measure 0 chord 0 is a OneTwentyEighth note but was written as a The64th note (line 7)",
+        "positions: Part 0 staff 1 measure 0 chord 1 starts at stamp 0.5 but was written at stamp 1 (line 7)",
+    ]);
+}
+
+/// Returns the stamp index of every chord of a GJM document, in the order they are written
+fn stamps(gjm: &str) -> Vec<u32> {
+    gjm.lines().filter_map(|line| line.trim().strip_prefix("StampIndex = ")).map(|stamp| stamp.trim_end_matches(',').parse().unwrap()).collect()
+}
+
+#[test]
+fn humanizer_is_deterministic_and_bounded() {
+    let note = |step: &str| format!("<note><pitch><step>{}</step><octave>4</octave></pitch><duration>1</duration><type>eighth</type></note>", step);
+    let measure: String = ["C", "D", "E", "F", "G", "A", "B", "C"].iter().map(|step| note(step)).collect();
+    let xml = score("<time><beats>4</beats><beat-type>4</beat-type></time>", &format!(r#"{}</measure><measure number="2">{}"#, measure, measure));
+    let grid = stamps(&convert(&xml));
+    assert_eq!(grid, [0, 8, 16, 24, 32, 40, 48, 56].repeat(2));
+
+    let options = Options { humanize: 3, seed: 42, ..Options::default() };
+    let humanized = stamps(&convert_with(&xml, &options));
+    // The same seed always gives the same output, and another seed gives another
+    assert_eq!(humanized, stamps(&convert_with(&xml, &options)));
+    assert_ne!(humanized, stamps(&convert_with(&xml, &Options { seed: 43, ..options.clone() })));
+    assert_ne!(humanized, grid);
+    // Every chord stays within the amount of its place, in order, and ends inside its measure
+    for seed in 0..20 {
+        let humanized = stamps(&convert_with(&xml, &Options { seed, ..options.clone() }));
+        for (measure, (humanized, grid)) in humanized.chunks(8).zip(grid.chunks(8)).enumerate() {
+            for (stamp, place) in humanized.iter().zip(grid.iter()) {
+                assert!((*stamp as i64 - *place as i64).abs() <= 3, "measure {}: {:?}", measure, humanized);
+                assert!(*stamp + 8 <= 64, "measure {}: {:?}", measure, humanized);
+            }
+            assert!(humanized.windows(2).all(|pair| pair[0] < pair[1]), "measure {}: {:?}", measure, humanized);
+        }
+    }
+
+    // The amount can be given after an = or as the next argument
+    let args = |list: &[&str]| Options::from_args(list.iter().map(|arg| arg.to_string()));
+    let spaced = args(&["--humanize", "3", "in.musicxml", "out.gjm"]);
+    assert_eq!(spaced.humanize, 3);
+    assert!(!spaced.usage_error);
+    assert_eq!(args(&["--humanize=3"]).humanize, 3);
+}
+
+/// Returns whether each chord of a GJM document is marked as a triplet, in the order they are written
+fn triplets(gjm: &str) -> Vec<bool> {
+    let mut triplets = Vec::new();
+    let mut triplet = false;
+    for line in gjm.lines().map(str::trim) {
+        if line == "Triplet = true," {
+            triplet = true;
+        } else if line.starts_with("StampIndex = ") {
+            triplets.push(triplet);
+            triplet = false;
+        }
+    }
+    triplets
+}
+
+#[test]
+fn beamed_triplets_are_inferred() {
+    let note = |step: &str, beam: &str| format!(
+        r#"<note><pitch><step>{}</step><octave>4</octave></pitch><duration>2</duration><type>eighth</type><beam number="1">{}</beam></note>"#, step, beam);
+    let three: String = [("C", "begin"), ("D", "continue"), ("E", "end")].iter().map(|(step, beam)| note(step, beam)).collect();
+    let six: String = [("F", "begin"), ("G", "continue"), ("A", "continue"), ("B", "continue"), ("C", "continue"), ("D", "end")]
+        .iter().map(|(step, beam)| note(step, beam)).collect();
+    let xml = score("<time><beats>3</beats><beat-type>4</beat-type></time>", &format!("{}{}", three, six))
+        .replace("<divisions>2</divisions>", "<divisions>6</divisions>");
+    assert_eq!(triplets(&convert(&xml)), [false; 9]);
+    // The group of three is one triplet and the group of six is two
+    let gjm = convert_with(&xml, &Options { infer_beamed_tuplets: true, ..Options::default() });
+    assert_eq!(triplets(&gjm), [true, false, false, true, false, false, true, false, false]);
+    assert_eq!(stamps(&gjm), [0, 5, 11, 16, 21, 27, 32, 37, 43]);
+}
+
+/// Returns the DurationType of every chord of a GJM document, in the order they are written
+fn duration_types(gjm: &str) -> Vec<&str> {
+    gjm.lines().filter_map(|line| line.trim().strip_prefix("DurationType = '")).map(|name| name.trim_end_matches("',")).collect()
+}
+
+#[test]
+fn overlap_only_cuts_the_repeated_note() {
+    let xml = score(
+        "<time><beats>2</beats><beat-type>4</beat-type></time>",
+        "<note><pitch><step>C</step><octave>4</octave></pitch><duration>4</duration><voice>1</voice><type>half</type></note>
+        <note><chord/><pitch><step>E</step><octave>4</octave></pitch><duration>4</duration><voice>1</voice><type>half</type></note>
+        <backup><duration>4</duration></backup>
+        <note><rest/><duration>2</duration><voice>2</voice><type>quarter</type></note>
+        <note><pitch><step>C</step><octave>4</octave></pitch><duration>2</duration><voice>2</voice><type>quarter</type></note>",
+    );
+    // The held C is cut where it is struck again, while the E it was played with keeps sounding
+    let gjm = convert(&xml);
+    assert_eq!(pitches(&gjm), [44, 40, 40]);
+    assert_eq!(duration_types(&gjm), ["Half", "Quarter", "Quarter", "Quarter"]);
+    assert_eq!(stamps(&gjm), [0, 0, 0, 16]);
+    // Strict mode leaves the chord as it is
+    let strict = convert_with(&xml, &Options { strict: true, ..Options::default() });
+    assert_eq!(pitches(&strict), [40, 44, 40]);
+    assert_eq!(duration_types(&strict), ["Half", "Quarter", "Quarter"]);
+}
+
+#[test]
+fn statistics_of_a_score() {
+    let note = |step: &str, duration: u32, note_type: &str, extra: &str| format!(
+        "<note><pitch><step>{}</step><octave>4</octave></pitch><duration>{}</duration><type>{}</type>{}</note>", step, duration, note_type, extra);
+    let triplet = |notations: &str| format!("<time-modification><actual-notes>3</actual-notes><normal-notes>2</normal-notes></time-modification><notations>{}</notations>", notations);
+    let xml = score("<time><beats>4</beats><beat-type>4</beat-type></time>", &[
+        note("C", 6, "quarter", r#"<tie type="start"/><notations><tied type="start"/></notations>"#),
+        note("C", 6, "quarter", r#"<tie type="stop"/><notations><tied type="stop"/></notations>"#),
+        note("D", 4, "eighth", &triplet(r#"<tuplet type="start"/><slur type="start"/>"#)),
+        note("E", 4, "eighth", &triplet("")),
+        note("F", 4, "eighth", &triplet(r#"<tuplet type="stop"/><slur type="stop"/>"#)),
+        r#"</measure><measure number="2"><note><rest/><duration>6</duration><type>quarter</type></note>"#.to_string(),
+        note("G", 12, "half", ""),
+        note("B", 12, "half", "").replace("<note>", "<note><chord/>"),
+    ].concat()).replace("<divisions>2</divisions>", "<divisions>6</divisions>");
+    let stats = mxl_2_solo::partwise::parse(xml.as_bytes(), &Options::default()).unwrap().statistics();
+    let mut note_types = std::collections::BTreeMap::new();
+    note_types.insert("Eighth".to_string(), 3);
+    note_types.insert("Half".to_string(), 2);
+    note_types.insert("Quarter".to_string(), 2);
+    // The slur doesn't count as a tie, and the triplet counts once
+    assert_eq!(stats, mxl_2_solo::report::Statistics {
+        note_count: 7,
+        rest_count: 1,
+        lowest_pitch: Some(40),
+        highest_pitch: Some(51),
+        note_types,
+        tied_count: 2,
+        tuplet_count: 1,
+        measure_count: 2,
+    });
+}
+
+#[test]
+fn note_pack_count_includes_rests() {
+    let note = |step: &str| format!("<note><pitch><step>{}</step><octave>4</octave></pitch><duration>2</duration><type>quarter</type></note>", step);
+    let rest = "<note><rest/><duration>2</duration><type>quarter</type></note>";
+    let chord = format!("{}{}", note("E"), note("G").replace("<note>", "<note><chord/>"));
+    let gjm = convert(&score("<time><beats>4</beats><beat-type>4</beat-type></time>", &[&note("C"), rest, &chord, rest].concat()));
+    assert!(gjm.contains("NotePackCount = 4,\n"));
+    // The packs are numbered from zero up to one less than the count
+    let packs: Vec<&str> = gjm.lines().filter(|line| line.starts_with("\t\t\t[")).map(str::trim).collect();
+    assert_eq!(packs, ["[0] = {", "[1] = {", "[2] = {", "[3] = {"]);
+    assert_eq!(gjm.matches("IsRest = true").count(), 2);
+}
+
+#[test]
+fn chord_duration_policies() {
+    let xml = score(
+        "<time><beats>2</beats><beat-type>4</beat-type></time>",
+        "<note><pitch><step>C</step><octave>4</octave></pitch><duration>4</duration><type>half</type></note>
+        <note><chord/><pitch><step>E</step><octave>4</octave></pitch><duration>2</duration><type>quarter</type></note>",
+    );
+    let convert_policy = |policy: ChordDuration| convert_with(&xml, &Options { chord_duration: policy, ..Options::default() });
+    let ties = |gjm: &str| -> Vec<String> {
+        gjm.lines().filter_map(|line| line.trim().strip_prefix("TieType =")).map(str::to_string).collect()
+    };
+
+    let shortest = convert_policy(ChordDuration::Shortest);
+    assert_eq!(duration_types(&shortest), ["Quarter"]);
+    assert_eq!(pitches(&shortest), [40, 44]);
+    assert!(ties(&shortest).is_empty());
+
+    let longest = convert_policy(ChordDuration::Longest);
+    assert_eq!(duration_types(&longest), ["Half"]);
+    assert_eq!(pitches(&longest), [40, 44]);
+    assert!(ties(&longest).is_empty());
+
+    // The longer note carries on past the chord as a tie, not a slur
+    let split = convert_policy(ChordDuration::Split);
+    assert_eq!(duration_types(&split), ["Quarter", "Quarter"]);
+    assert_eq!(stamps(&split), [0, 16]);
+    assert_eq!(pitches(&split), [40, 44, 40]);
+    assert_eq!(ties(&split), ["'Start',", "'End',"]);
+    assert!(mxl_2_solo::partwise::check_reader(xml.as_bytes(), &Options { chord_duration: ChordDuration::Split, ..Options::default() })
+        .unwrap().iter().all(|issue| issue.feature != "slurs"));
+}
+
+#[test]
+fn octave_clef_only_moves_its_staff() {
+    let xml = score(
+        "<time><beats>4</beats><beat-type>4</beat-type></time>
+        <staves>2</staves>
+        <clef number=\"1\"><sign>G</sign><line>2</line><clef-octave-change>-1</clef-octave-change></clef>
+        <clef number=\"2\"><sign>F</sign><line>4</line></clef>",
+        "<note><pitch><step>E</step><octave>4</octave></pitch><duration>8</duration><type>whole</type><staff>1</staff></note>
+        <backup><duration>8</duration></backup>
+        <note><pitch><step>C</step><octave>3</octave></pitch><duration>8</duration><type>whole</type><staff>2</staff></note>",
+    );
+    let gjm = convert(&xml);
+    let (upper, lower) = gjm.split_at(gjm.find("\n\t[1] = {").unwrap());
+    // The treble staff sounds an octave down while the bass staff stays where it is written
+    assert_eq!(pitches(upper), [32]);
+    assert_eq!(pitches(lower), [28]);
+    assert_eq!(pitches(&convert(&xml.replace("<clef-octave-change>-1</clef-octave-change>", ""))), [44, 28]);
+}
+
+#[test]
+fn invalid_clef_number_falls_back_on_the_first_staff() {
+    let note = "<note><pitch><step>C</step><octave>3</octave></pitch><duration>8</duration><type>whole</type></note>";
+    // Neither a number that isn't one nor a staff the part doesn't have stops the conversion
+    for number in ["x", "", "3"] {
+        let clef = format!(r#"<clef number="{}"><sign>F</sign><line>4</line></clef>"#, number);
+        let gjm = convert(&score(&format!("<time><beats>4</beats><beat-type>4</beat-type></time>{}", clef), note));
+        assert_eq!(track_map(&gjm, "ClefType"), ["{ 0, 'L4F' },"], "number {:?}", number);
+        assert_eq!(pitches(&gjm), [28]);
+    }
+}
+
+#[test]
+fn conductor_track_carries_the_maps() {
+    let xml = score(
+        r#"<time><beats>4</beats><beat-type>4</beat-type></time></attributes><direction><sound tempo="90"/></direction><attributes>"#,
+        r#"<note><pitch><step>C</step><octave>4</octave></pitch><duration>8</duration><type>whole</type></note>
+        </measure><measure number="2">
+        <attributes><key><fifths>-1</fifths></key><time><beats>3</beats><beat-type>4</beat-type></time></attributes>
+        <direction><sound tempo="120"/></direction>
+        <note><pitch><step>F</step><octave>4</octave></pitch><duration>6</duration><type>half</type><dot/></note>"#,
+    );
+    let gjm = convert_with(&xml, &Options { conductor_track: true, ..Options::default() });
+    let (conductor, piano) = gjm.split_at(gjm.find("\n\t[1] = {").unwrap());
+    let measure_lengths = |track: &str| -> Vec<String> {
+        track.lines().filter_map(|line| line.trim().strip_prefix("DurationStampMax = ")).map(str::to_string).collect()
+    };
+    // The conductor track holds the whole key and meter timeline and the maps every track has,
+    // but no notes. The tempo stays in the score's own map.
+    assert_eq!(track_map(conductor, "KeySignature"), ["{ 0, 0 },", "{ 1, -1 },"]);
+    assert_eq!(track_map(conductor, "ClefType"), ["{ 0, 'L2G' },"]);
+    assert_eq!(track_map(conductor, "InstrumentType"), ["{ 0, 'Piano' },"]);
+    assert_eq!(track_map(conductor, "VolumeCurve").len(), 1);
+    assert_eq!(track_map(conductor, "Volume").len(), 1);
+    assert_eq!(measure_lengths(conductor), ["63,", "47,"]);
+    assert!(stamps(conductor).is_empty());
+    assert!(!conductor.contains("\t\tMeasureBeatsPerMinuteMap"));
+    assert!(conductor.contains("\tMeasureBeatsPerMinuteMap = {\n\t\t{ 0, 90 },\n\t\t{ 1, 120 },\n\t},\n"));
+    // The instrument track comes after it and agrees with it measure for measure
+    assert!(piano.starts_with("\n\t[1] = {\n\t\t-- Piano\n"));
+    assert_eq!(track_map(piano, "KeySignature"), ["{ 0, 0 },", "{ 1, -1 },"]);
+    assert_eq!(measure_lengths(piano), measure_lengths(conductor));
+    assert_eq!(pitches(piano), [40, 45]);
+}
+
+#[test]
+fn tied_odd_durations_fill_the_bar() {
+    // Seven notes of four sevenths of a beat each, tied into one whole note, each lasting 9.14 stamps
+    let note = |tie: &str| format!(
+        "<note><pitch><step>G</step><octave>4</octave></pitch><duration>4</duration><type>eighth</type>{}</note>", tie);
+    let notes: String = (0..7).map(|i| note(match i {
+        0 => r#"<tie type="start"/>"#,
+        6 => r#"<tie type="stop"/>"#,
+        _ => r#"<tie type="stop"/><tie type="start"/>"#,
+    })).collect();
+    let xml = score("<time><beats>4</beats><beat-type>4</beat-type></time>", &notes)
+        .replace("<divisions>2</divisions>", "<divisions>7</divisions>");
+    let gjm = convert(&xml);
+    assert!(gjm.contains("DurationStampMax = 63,"));
+    // Rounding each note on its own would leave the bar a stamp short, rounding where each starts doesn't
+    assert_eq!(stamps(&gjm), [0, 9, 18, 27, 37, 46, 55]);
+}
+
+#[test]
+fn unsupported_clef_modes() {
+    let rest = "<note><rest/><duration>8</duration></note>";
+    // The staff starts with the given clef and changes to a jianpu clef, which GJM has no clef for
+    let xml = |sign: &str, line: u32| score(
+        &format!("<time><beats>4</beats><beat-type>4</beat-type></time><clef><sign>{}</sign><line>{}</line></clef>", sign, line),
+        &format!(r#"{}</measure><measure number="2"><attributes><clef><sign>jianpu</sign></clef></attributes>{}"#, rest, rest));
+    let clefs = |xml: &str, mode: UnsupportedClef| -> Vec<String> {
+        let gjm = convert_with(xml, &Options { unsupported_clef: mode, ..Options::default() });
+        track_map(&gjm, "ClefType").into_iter().map(str::to_string).collect()
+    };
+
+    // Warning keeps the clef the staff already had
+    assert_eq!(clefs(&xml("F", 4), UnsupportedClef::Warn), ["{ 0, 'L4F' },"]);
+    assert_eq!(clefs(&xml("G", 2), UnsupportedClef::Warn), ["{ 0, 'L2G' },"]);
+    // A fallback replaces it
+    assert_eq!(clefs(&xml("F", 4), UnsupportedClef::Fallback(Clef::G)), ["{ 0, 'L4F' },", "{ 1, 'L2G' },"]);
+    assert_eq!(clefs(&xml("G", 2), UnsupportedClef::Fallback(Clef::F)), ["{ 0, 'L2G' },", "{ 1, 'L4F' },"]);
+    // An error stops the conversion
+    let options = Options { unsupported_clef: UnsupportedClef::Error, ..Options::default() };
+    match convert_reader(xml("G", 2).as_bytes(), &mut Vec::new(), &options) {
+        Err(mxl_2_solo::Error::UnsupportedClef(sign)) => assert_eq!(sign, "jianpu"),
+        result => panic!("expected an unsupported clef, got {:?}", result),
+    }
+}
+
+#[test]
+fn typeless_triplet_gets_eighth_types() {
+    let note = |step: &str| format!(
+        "<note><pitch><step>{}</step><octave>4</octave></pitch><duration>2</duration>\
+        <time-modification><actual-notes>3</actual-notes><normal-notes>2</normal-notes></time-modification></note>", step);
+    let notes: String = ["C", "D", "E"].iter().map(|step| note(step)).collect();
+    // Three notes in the time of a quarter note, with no types to say they are eighths
+    let xml = score("<time><beats>1</beats><beat-type>4</beat-type></time>", &notes)
+        .replace("<divisions>2</divisions>", "<divisions>6</divisions>");
+    let gjm = convert(&xml);
+    assert_eq!(duration_types(&gjm), ["Eighth"; 3]);
+    assert_eq!(pitches(&gjm), [40, 42, 44]);
+}
+
+#[test]
+fn whole_rest_before_a_note() {
+    let note = "<note><pitch><step>A</step><octave>4</octave></pitch><duration>4</duration><type>half</type></note>";
+    // The rest takes up its whole length whether or not it says what type it is
+    for rest in ["<note><rest/><duration>8</duration><type>whole</type></note>", "<note><rest/><duration>8</duration></note>"] {
+        let gjm = convert(&score("<time><beats>6</beats><beat-type>4</beat-type></time>", &format!("{}{}", rest, note)));
+        assert!(gjm.contains("DurationStampMax = 95,"));
+        assert_eq!(duration_types(&gjm), ["Whole", "Half"]);
+        assert_eq!(stamps(&gjm), [0, 64]);
+        assert_eq!(pitches(&gjm), [49]);
+    }
+}
+
+#[test]
+fn abbreviated_track_labels() {
+    let part = |id: &str| format!(
+        r#"<part id="{}"><measure number="1"><attributes><divisions>1</divisions></attributes><note><rest/><duration>4</duration></note></measure></part>"#, id);
+    let xml = format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<score-partwise version="3.1">
+  <part-list>
+    <score-part id="P1"><part-name>Violin</part-name><part-abbreviation>Vln.</part-abbreviation></score-part>
+    <score-part id="P2"><part-name>Flute</part-name></score-part>
+  </part-list>
+  {}{}
+</score-partwise>"#, part("P1"), part("P2"));
+    let labels = |gjm: &str| -> Vec<String> {
+        gjm.lines().filter_map(|line| line.strip_prefix("\t\t-- ")).map(str::to_string).collect()
+    };
+    assert_eq!(labels(&convert(&xml)), ["Violin", "Flute"]);
+    // A part without an abbreviation keeps its full name
+    let abbreviated = convert_with(&xml, &Options { track_label: TrackLabel::Abbreviation, ..Options::default() });
+    assert_eq!(labels(&abbreviated), ["Vln.", "Flute"]);
+}
+
+#[test]
+fn scores_converted_back_to_back_are_independent() {
+    let treble = score(
+        r#"<key><fifths>2</fifths></key><time><beats>2</beats><beat-type>4</beat-type></time></attributes><direction><sound tempo="140"/></direction><attributes>"#,
+        "<note><pitch><step>F</step><alter>1</alter><octave>4</octave></pitch><duration>4</duration><type>half</type></note>",
+    );
+    let bass = score(
+        "<time><beats>3</beats><beat-type>4</beat-type></time><clef><sign>F</sign><line>4</line></clef>",
+        "<note><pitch><step>C</step><octave>3</octave></pitch><duration>6</duration><type>half</type><dot/></note>",
+    ).replace("Piano", "Cello");
+    let first = convert(&treble);
+    let second = convert(&bass);
+    // Nothing read from one score carries over into the next
+    assert!(first.contains("\t\t{ 0, 140 },\n") && first.contains("DurationStampMax = 31,"));
+    assert_eq!(track_map(&first, "KeySignature"), ["{ 0, 2 },"]);
+    assert_eq!(pitches(&first), [46]);
+    assert!(second.contains("\t\t{ 0, 108 },\n") && second.contains("DurationStampMax = 47,"));
+    assert_eq!(track_map(&second, "KeySignature"), ["{ 0, 0 },"]);
+    assert_eq!(track_map(&second, "ClefType"), ["{ 0, 'L4F' },"]);
+    assert!(second.contains("\t\t-- Cello\n") && !second.contains("Piano"));
+    assert_eq!(pitches(&second), [28]);
+    // Nor do the options of one conversion
+    convert_with(&treble, &Options { conductor_track: true, humanize: 3, tuning_offset: 100, ..Options::default() });
+    assert_eq!(convert(&treble), first);
+    assert_eq!(convert(&bass), second);
+}
+
+#[test]
+fn tuning_offset_moves_every_pitch() {
+    let note = |step: &str, alter: i32, octave: u32| format!(
+        "<note><pitch><step>{}</step><alter>{}</alter><octave>{}</octave></pitch><duration>2</duration><type>quarter</type></note>", step, alter, octave);
+    let notes = [note("C", 0, 4), note("E", -1, 4), note("G", 1, 4).replace("<note>", "<note><chord/>"), note("B", 0, 2), note("A", 0, 5)].concat();
+    let xml = score("<time><beats>3</beats><beat-type>4</beat-type></time>", &notes);
+    let tuned = |cents: i32| pitches(&convert_with(&xml, &Options { tuning_offset: cents, ..Options::default() }));
+    assert_eq!(tuned(0), [40, 43, 48, 27, 61]);
+    assert_eq!(tuned(100), [41, 44, 49, 28, 62]);
+    // Offsets are rounded to the nearest half step
+    assert_eq!(tuned(60), tuned(100));
+    assert_eq!(tuned(40), tuned(0));
+    assert_eq!(tuned(-100), [39, 42, 47, 26, 60]);
+}
+
+#[test]
+fn voices_interleaved_by_backups() {
+    let note = |step: &str, octave: u32, duration: u32, note_type: &str, voice: u32| format!(
+        "<note><pitch><step>{}</step><octave>{}</octave></pitch><duration>{}</duration><voice>{}</voice><type>{}</type></note>",
+        step, octave, duration, voice, note_type);
+    let shift = |tag: &str, duration: u32| format!("<{0}><duration>{1}</duration></{0}>", tag, duration);
+    // The notes are written jumping back and forth through the measure, last beat first
+    let notes = [
+        shift("forward", 6), note("G", 5, 2, "quarter", 1),
+        shift("backup", 8), note("E", 5, 2, "quarter", 1),
+        shift("forward", 1), note("D", 4, 1, "eighth", 2),
+        shift("backup", 3), note("C", 4, 1, "eighth", 2),
+        shift("forward", 2), note("F", 5, 2, "quarter", 1),
+        shift("backup", 2), note("B", 3, 2, "quarter", 2),
+    ].concat();
+    let gjm = convert(&score("<time><beats>4</beats><beat-type>4</beat-type></time>", &notes));
+    // The chords come out in time order, and the two voices on the third beat in voice order
+    assert_eq!(stamps(&gjm), [0, 8, 24, 32, 32, 48]);
+    assert_eq!(duration_types(&gjm), ["Quarter", "Eighth", "Eighth", "Quarter", "Quarter", "Quarter"]);
+    assert_eq!(pitches(&gjm), [56, 40, 42, 57, 39, 59]);
+}
+
+#[test]
+fn keep_going_past_a_malformed_measure() {
+    let note = |step: &str, duration: &str| format!(
+        "<note><pitch><step>{}</step><octave>4</octave></pitch><duration>{}</duration><type>whole</type></note>", step, duration);
+    let xml = score("<time><beats>4</beats><beat-type>4</beat-type></time>", &format!(
+        r#"{}</measure><measure number="2">{}</measure><measure number="3">{}"#, note("C", "8"), note("D", "eight"), note("E", "8")));
+    match convert_reader(xml.as_bytes(), &mut Vec::new(), &Options::default()) {
+        Err(mxl_2_solo::Error::InvalidValue { tag, measure, .. }) => assert_eq!((tag.as_str(), measure), ("duration", Some(2))),
+        result => panic!("expected an invalid duration, got {:?}", result),
+    }
+    // The measure is given by its number rather than where it falls in the part
+    let renumbered = xml.replace(r#"measure number="1""#, r#"measure number="7""#).replace(r#"measure number="2""#, r#"measure number="8""#);
+    let error = convert_reader(renumbered.as_bytes(), &mut Vec::new(), &Options::default()).unwrap_err();
+    assert!(error.to_string().ends_with(" in measure 8"), "{}", error);
+
+    // The broken measure becomes a full measure rest and the ones around it are kept
+    let options = Options { keep_going: true, ..Options::default() };
+    let gjm = convert_with(&xml, &options);
+    assert!(gjm.contains("MeasureAlignedCount = 3,"));
+    assert_eq!(pitches(&gjm), [40, 44]);
+    assert_eq!(duration_types(&gjm), ["Whole"; 3]);
+    assert_eq!(gjm.matches("IsRest = true").count(), 1);
+    assert_eq!(mxl_2_solo::partwise::parse(xml.as_bytes(), &options).unwrap().get_skipped_measure_count(), 1);
+}
+
+#[test]
+fn fixed_and_movable_numbered_signs() {
+    let note = |step: &str, alter: i32, octave: u32| format!(
+        "<note><pitch><step>{}</step><alter>{}</alter><octave>{}</octave></pitch><duration>1</duration><type>eighth</type></note>", step, alter, octave);
+    let numbered_signs = |gjm: &str| -> Vec<u32> {
+        gjm.split("NumberedSign = ").skip(1).map(|rest| rest[..rest.find(',').unwrap()].parse().unwrap()).collect()
+    };
+    let convert_notation = |xml: &str, notation: NumberedNotation| convert_with(xml, &Options { numbered_notation: notation, ..Options::default() });
+
+    // A G major scale
+    let scale = [note("G", 0, 4), note("A", 0, 4), note("B", 0, 4), note("C", 0, 5),
+        note("D", 0, 5), note("E", 0, 5), note("F", 1, 5), note("G", 0, 5)].concat();
+    let xml = score("<key><fifths>1</fifths></key><time><beats>4</beats><beat-type>4</beat-type></time>", &scale);
+    let fixed = convert_notation(&xml, NumberedNotation::Fixed);
+    let movable = convert_notation(&xml, NumberedNotation::Movable);
+    assert_eq!(fixed, convert(&xml));
+    assert_eq!(numbered_signs(&fixed), [5, 6, 7, 1, 2, 3, 4, 5]);
+    assert_eq!(numbered_signs(&movable), [1, 2, 3, 4, 5, 6, 7, 1]);
+    // Only the numbers change, not what is played
+    assert_eq!(pitches(&movable), pitches(&fixed));
+
+    // In E flat major the flattened tonic is still 1
+    let opening = [note("E", -1, 4), note("F", 0, 4), note("G", 0, 4), note("A", -1, 4)].concat();
+    let xml = score("<key><fifths>-3</fifths></key><time><beats>2</beats><beat-type>4</beat-type></time>", &opening);
+    assert_eq!(numbered_signs(&convert_notation(&xml, NumberedNotation::Fixed)), [3, 4, 5, 6]);
+    assert_eq!(numbered_signs(&convert_notation(&xml, NumberedNotation::Movable)), [1, 2, 3, 4]);
+}
+
+#[test]
+fn every_note_type_token() {
+    // Every MusicXml type token, with the GJM duration type it is written as. Anything shorter
+    // than a 64th or longer than a whole note is written as the closest one GJM has.
+    let tokens = [
+        ("1024th", "The64th"), ("512th", "The64th"), ("256th", "The64th"), ("128th", "The64th"),
+        ("64th", "The64th"), ("32nd", "The32nd"), ("16th", "The16th"), ("eighth", "Eighth"),
+        ("quarter", "Quarter"), ("half", "Half"), ("whole", "Whole"),
+        ("breve", "Whole"), ("long", "Whole"), ("maxima", "Whole"),
+    ];
+    for (token, name) in tokens.iter() {
+        // Every note lasts the same, so only the token can give its type
+        let xml = score("", &format!("<note><pitch><step>C</step><octave>4</octave></pitch><duration>1</duration><type>{}</type></note>", token));
+        assert_eq!(duration_types(&convert(&xml)), [*name], "{}", token);
+    }
+}
+
+#[test]
+fn rounding_modes_place_a_triplet() {
+    let note = |step: &str| format!(
+        "<note><pitch><step>{}</step><octave>4</octave></pitch><duration>1</duration><type>eighth</type>\
+        <time-modification><actual-notes>3</actual-notes><normal-notes>2</normal-notes></time-modification></note>", step);
+    // The second and third notes start a third of the way between two stamps
+    let xml = score("<time><beats>1</beats><beat-type>4</beat-type></time>", &[note("C"), note("D"), note("E")].concat())
+        .replace("<divisions>2</divisions>", "<divisions>3</divisions>");
+    let rounded = |rounding: Rounding| stamps(&convert_with(&xml, &Options { rounding, ..Options::default() }));
+    assert_eq!(stamps(&convert(&xml)), [0, 5, 11]);
+    assert_eq!(rounded(Rounding::Nearest), [0, 5, 11]);
+    assert_eq!(rounded(Rounding::Floor), [0, 5, 10]);
+    assert_eq!(rounded(Rounding::Ceil), [0, 6, 11]);
+}
+
+#[test]
+fn grace_chord_before_a_note() {
+    let grace = |step: &str, chord: &str| format!(
+        r#"<note><grace slash="yes"/>{}<pitch><step>{}</step><octave>4</octave></pitch><type>eighth</type></note>"#, chord, step);
+    let principal = "<note><pitch><step>G</step><octave>4</octave></pitch><duration>8</duration><type>whole</type></note>";
+    let xml = score("<time><beats>4</beats><beat-type>4</beat-type></time>", &[&grace("C", ""), &grace("E", "<chord/>"), principal].concat());
+    // The grace chord takes no time, so the note it leads into keeps its place
+    let gjm = convert(&xml);
+    assert_eq!(stamps(&gjm), [0]);
+    assert_eq!(pitches(&gjm), [47]);
+    assert_eq!(duration_types(&gjm), ["Whole"]);
+    // Both grace notes are struck together as one ornament
+    let grace_issues = |xml: &str| -> Vec<String> {
+        mxl_2_solo::partwise::check_reader(xml.as_bytes(), &Options::default()).unwrap().into_iter()
+            .filter(|issue| issue.feature == "grace notes").map(|issue| issue.message).collect()
+    };
+    assert_eq!(grace_issues(&xml), ["Part 0 staff 1 measure 0 has an acciaccatura of 2 notes, which GJM does not support"]);
+    let separate = xml.replace("<chord/>", "");
+    assert_eq!(grace_issues(&separate).len(), 2);
+}
+
+#[test]
+fn home_and_file_url_paths() {
+    let xml = score("", "<note><pitch><step>C</step><octave>4</octave></pitch><duration>8</duration><type>whole</type></note>");
+    let dir = std::env::temp_dir().join(format!("mxl_2_solo_paths_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("foo.musicxml"), &xml).unwrap();
+    std::fs::write(dir.join("my score.musicxml"), &xml).unwrap();
+    // Runs the program with the temporary directory as the home directory
+    let run = |path: &str| std::process::Command::new(env!("CARGO_BIN_EXE_mxl_2_solo"))
+        .args([path, "--stdout"])
+        .env("HOME", &dir)
+        .env("USERPROFILE", &dir)
+        .output()
+        .unwrap();
+    let home = run("~/foo.musicxml");
+    let url = run(&format!("file://{}", dir.join("my%20score.musicxml").display()));
+    let missing = run("~/missing.musicxml");
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(String::from_utf8(home.stdout).unwrap(), convert(&xml));
+    assert_eq!(String::from_utf8(url.stdout).unwrap(), convert(&xml));
+    // A path that leads nowhere is named as it was expanded
+    assert_eq!(missing.status.code(), Some(1));
+    assert_eq!(String::from_utf8(missing.stderr).unwrap(), format!("Error: '{}' is not a file\n", dir.join("missing.musicxml").display()));
+
+    assert_eq!(mxl_2_solo::options::expand_path("file://localhost/home/me/a%23b.xml"), std::path::Path::new("/home/me/a#b.xml"));
+    assert_eq!(mxl_2_solo::options::expand_path("file:///C:/Music/a.xml"), std::path::Path::new("C:/Music/a.xml"));
+    assert_eq!(mxl_2_solo::options::expand_path("notes~/a.xml"), std::path::Path::new("notes~/a.xml"));
+}
+
+#[test]
+fn glissando_between_two_notes() {
+    let note = |step: &str, glissando: &str| format!(
+        r#"<note><pitch><step>{}</step><octave>4</octave></pitch><duration>4</duration><type>half</type><notations><glissando type="{}" line-type="wavy">gliss.</glissando></notations></note>"#,
+        step, glissando);
+    let xml = score("<time><beats>4</beats><beat-type>4</beat-type></time>", &[note("C", "start"), note("G", "stop")].concat());
+    // By default the marking is kept and both notes play as written
+    let gjm = convert(&xml);
+    assert_eq!(gjm, convert(&xml.replace(r#"<notations><glissando type="start" line-type="wavy">gliss.</glissando></notations>"#, "")));
+    assert_eq!(pitches(&gjm), [40, 47]);
+    assert_eq!(stamps(&gjm), [0, 32]);
+    let features: Vec<&str> = mxl_2_solo::partwise::check_reader(xml.as_bytes(), &Options::default()).unwrap()
+        .iter().map(|issue| issue.feature).collect();
+    assert_eq!(features, ["glissandi"]);
+    // Filling it plays a chromatic run up to the second note in the time of the first
+    let filled = convert_with(&xml, &Options { glissando: Glissando::Fill, ..Options::default() });
+    assert_eq!(pitches(&filled), [40, 42, 44, 45, 47]);
+    assert_eq!(stamps(&filled), [0, 8, 16, 24, 32]);
+    assert_eq!(duration_types(&filled), ["Eighth", "Eighth", "Eighth", "Eighth", "Half"]);
+}
+
+#[test]
+fn pitch_range_folds_by_octaves() {
+    let note = |step: &str, octave: u32| format!(
+        "<note><pitch><step>{}</step><octave>{}</octave></pitch><duration>2</duration><type>quarter</type></note>", step, octave);
+    let notes = [note("D", 5), note("C", 6), note("E", 3), note("C", 4)].concat();
+    let xml = score("<time><beats>4</beats><beat-type>4</beat-type></time>", &notes);
+    assert_eq!(pitches(&convert(&xml)), [54, 64, 32, 40]);
+    // From C4 to C5 the D above is folded down an octave, the C two octaves up down to the top of
+    // the range and the E below up an octave
+    let options = Options::from_args(std::iter::once("--pitch-range=C4,C5".to_string()));
+    assert_eq!(options.pitch_range, Some((40, 52)));
+    assert_eq!(pitches(&convert_with(&xml, &options)), [42, 52, 44, 40]);
+    // The range holds for the pitch that sounds, after the part is transposed
+    let guitar = xml.replace("<time>", "<transpose><diatonic>-7</diatonic><chromatic>-12</chromatic></transpose><time>");
+    assert_eq!(pitches(&convert_with(&guitar, &options)), [42, 52, 44, 40]);
+    assert_eq!(pitches(&convert(&guitar)), [42, 52, 20, 28]);
+}
+
+#[test]
+fn reordering_two_tracks() {
+    let xml = score(
+        "<time><beats>4</beats><beat-type>4</beat-type></time>
+        <staves>2</staves>
+        <clef number=\"1\"><sign>G</sign><line>2</line></clef>
+        <clef number=\"2\"><sign>F</sign><line>4</line></clef>",
+        "<note><pitch><step>E</step><octave>5</octave></pitch><duration>8</duration><type>whole</type><staff>1</staff></note>
+        <backup><duration>8</duration></backup>
+        <note><pitch><step>C</step><octave>3</octave></pitch><duration>8</duration><type>whole</type><staff>2</staff></note>",
+    );
+    // Each track's index along with the label under it
+    let tracks = |gjm: &str| -> Vec<String> {
+        let lines: Vec<&str> = gjm.lines().collect();
+        lines.windows(2).filter(|pair| pair[0].starts_with("\t[") && pair[1].starts_with("\t\t-- "))
+            .map(|pair| format!("{} {}", pair[0].trim().trim_end_matches(" = {"), &pair[1][5..]))
+            .collect()
+    };
+    let ordered = |order: Vec<usize>| convert_with(&xml, &Options { track_order: TrackOrder::Permutation(order), ..Options::default() });
+    assert_eq!(tracks(&convert(&xml)), ["[0] Piano (staff 1)", "[1] Piano (staff 2)"]);
+    let swapped = ordered(vec![1, 0]);
+    assert_eq!(tracks(&swapped), ["[0] Piano (staff 2)", "[1] Piano (staff 1)"]);
+    assert_eq!(pitches(&swapped), [28, 56]);
+    assert_eq!(track_map(&swapped, "ClefType"), ["{ 0, 'L4F' },"]);
+    // An order that doesn't list every track once is ignored
+    assert_eq!(ordered(vec![1]), convert(&xml));
+    assert_eq!(ordered(vec![1, 1]), convert(&xml));
+}
+
+#[test]
+fn muted_note_keeps_its_time() {
+    let note = |step: &str, play: &str| format!(
+        "<note><pitch><step>{}</step><octave>4</octave></pitch><duration>2</duration><type>quarter</type>{}</note>", step, play);
+    let muted = "<play><ipa>a</ipa><mute>on</mute><other-play type=\"breath\">soft</other-play></play>";
+    let notes = [note("C", ""), note("D", muted), note("E", ""), note("G", muted).replace("<note>", "<note><chord/>"), note("F", "<play><mute>off</mute></play>")];
+    let gjm = convert(&score("<time><beats>4</beats><beat-type>4</beat-type></time>", &notes.concat()));
+    // The muted note becomes a rest in its place, and the muted note of the chord is left out of it
+    assert_eq!(stamps(&gjm), [0, 16, 32, 48]);
+    assert_eq!(duration_types(&gjm), ["Quarter"; 4]);
+    assert_eq!(pitches(&gjm), [40, 44, 45]);
+    assert!(gjm.contains("\t\t\t[1] = {\n\t\t\t\tIsRest = true,\n"));
+    assert!(gjm.contains("NotePackCount = 4,"));
+}
+
+#[test]
+fn high_resolution_divisions() {
+    let note = |step: &str, duration: u32, note_type: &str, dot: &str| format!(
+        "<note><pitch><step>{}</step><octave>4</octave></pitch><duration>{}</duration><type>{}</type>{}</note>", step, duration, note_type, dot);
+    let notes = [note("C", 960, "quarter", ""), note("D", 720, "eighth", "<dot/>"), note("E", 240, "16th", ""), note("F", 1920, "half", "")];
+    let xml = score("<time><beats>4</beats><beat-type>4</beat-type></time>", &notes.concat())
+        .replace("<divisions>2</divisions>", "<divisions>960</divisions>");
+    let gjm = convert(&xml);
+    // A quarter note still spans 16 stamps, and the measure its full 64
+    assert_eq!(stamps(&gjm), [0, 16, 28, 32]);
+    assert_eq!(duration_types(&gjm), ["Quarter", "Eighth", "The16th", "Half"]);
+    assert!(gjm.contains("DurationStampMax = 63,"));
+    assert_eq!(gjm, convert(&xml.replace("<divisions>960</divisions>", "<divisions>4</divisions>")
+        .replace(">960<", ">4<").replace(">720<", ">3<").replace(">240<", ">1<").replace(">1920<", ">8<")));
+}
+
+#[test]
+fn output_file_is_complete_on_return() {
+    // Enough measures that the output is many times larger than the write buffer
+    let measure = "<note><pitch><step>C</step><octave>4</octave></pitch><duration>8</duration><type>whole</type></note>";
+    let measures: String = (2..=200).map(|i| format!(r#"</measure><measure number="{}">{}"#, i, measure)).collect();
+    let xml = score("", &(measure.to_string() + &measures));
+    let dir = std::env::temp_dir().join(format!("mxl_2_solo_sync_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("long.musicxml"), &xml).unwrap();
+    let options = Options { sync: true, report: true, ..Options::default() };
+    let result = mxl_2_solo::partwise::convert_file(&dir.join("long.musicxml"), &dir.join("long.gjm"), &options);
+    let gjm = std::fs::read_to_string(dir.join("long.gjm")).unwrap();
+    let report = std::fs::read_to_string(dir.join("long.json")).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(result.is_ok());
+    assert!(gjm.len() > 8 * 1024);
+    assert_eq!(gjm, convert(&xml));
+    assert!(report.trim_end().ends_with('}'));
+    assert!(serde_json::from_str::<serde_json::Value>(&report).is_ok());
+}
+
+#[test]
+fn warnings_carry_their_measure() {
+    let rest = "<note><rest/><duration>4</duration></note>";
+    let fermata = "<note><pitch><step>C</step><octave>4</octave></pitch><duration>4</duration><type>whole</type><notations><fermata/></notations></note>";
+    let pan = r#"<direction><direction-type><words>left</words></direction-type><sound pan="-45"/></direction>"#;
+    let part = |id: &str, measures: &[&str]| -> String {
+        let measures: Vec<String> = measures.iter().enumerate().map(|(i, notes)| format!(r#"
+    <measure number="{}">{}{}</measure>"#, i + 1,
+            if i == 0 { "<attributes><divisions>1</divisions></attributes>" } else { "" }, notes)).collect();
+        format!(r#"<part id="{}">{}
+  </part>"#, id, measures.concat())
+    };
+    let xml = format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<score-partwise version="3.1">
+  <part-list>
+    <score-part id="P1"><part-name>Flute</part-name></score-part>
+    <score-part id="P2"><part-name>Oboe</part-name></score-part>
+  </part-list>
+  {}
+  {}
+</score-partwise>"#, part("P1", &[rest, rest, rest]), part("P2", &[rest, fermata, &format!("{}{}", pan, rest)]));
+    let dir = std::env::temp_dir().join(format!("mxl_2_solo_locations_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let options = Options { report: true, ..Options::default() };
+    mxl_2_solo::partwise::convert_reader_to_path(xml.as_bytes(), "oboe.musicxml", &dir.join("oboe.gjm"), &options).unwrap();
+    let report = std::fs::read_to_string(dir.join("oboe.json")).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    // The pan is in the third measure of the second part
+    let report: serde_json::Value = serde_json::from_str(&report).unwrap();
+    assert_eq!(report["warnings"], serde_json::json!([
+        "Part 1, measure 2: GJM has no stereo placement, so a pan of -45 degrees is dropped",
+    ]));
+    let issues = mxl_2_solo::partwise::check_reader(xml.as_bytes(), &Options::default()).unwrap();
+    let located: Vec<(&str, Option<usize>, Option<usize>)> = issues.iter()
+        .map(|issue| (issue.feature, issue.location.as_ref().map(|location| location.part), issue.location.as_ref().and_then(|location| location.measure)))
+        .collect();
+    assert_eq!(located, [("fermatas", Some(1), Some(1)), ("stereo placement", Some(1), Some(2))]);
+}
+
+#[test]
+fn multiple_rest_across_a_meter_change() {
+    let rest = |duration: u32| format!(r#"<note><rest measure="yes"/><duration>{}</duration></note>"#, duration);
+    let measures = [
+        format!("<attributes><measure-style><multiple-rest>3</multiple-rest></measure-style></attributes>{}", rest(8)),
+        rest(8),
+        format!("<attributes><time><beats>3</beats><beat-type>4</beat-type></time></attributes>{}", rest(6)),
+        "<note><pitch><step>C</step><octave>4</octave></pitch><duration>6</duration><type>half</type><dot/></note>".to_string(),
+    ];
+    let measures: Vec<String> = measures.iter().enumerate()
+        .map(|(i, notes)| if i == 0 { notes.clone() } else { format!(r#"</measure><measure number="{}">{}"#, i + 1, notes) })
+        .collect();
+    let xml = score("<time><beats>4</beats><beat-type>4</beat-type></time>", &measures.concat());
+    let gjm = convert(&xml);
+    let measure_lengths: Vec<&str> = gjm.lines().filter_map(|line| line.trim().strip_prefix("DurationStampMax = ")).collect();
+    // Each measure of the rest is as long as its own meter
+    assert_eq!(measure_lengths, ["63,", "63,", "47,", "47,"]);
+    assert_eq!(gjm.matches("IsRest = true").count(), 3);
+    assert_eq!(duration_types(&gjm), ["Whole", "Whole", "Half", "Half"]);
+    assert_eq!(gjm.matches("IsDotted = true").count(), 2);
+    assert_eq!(stamps(&gjm), [0, 0, 0, 0]);
+}
+
+#[test]
+fn swung_eighths_shift_their_offbeats() {
+    let note = |step: &str| format!("<note><pitch><step>{}</step><octave>4</octave></pitch><duration>1</duration><type>eighth</type></note>", step);
+    let eighths: String = ["C", "D", "E", "F", "G", "A", "B", "C"].iter().map(|step| note(step)).collect();
+    let xml = score("<time><beats>4</beats><beat-type>4</beat-type></time>", &eighths);
+    let marked = score("<time><beats>4</beats><beat-type>4</beat-type></time>",
+        &format!("<direction><direction-type><words>Swing</words></direction-type></direction>{}", eighths));
+    let swung = |xml: &str, swing: Swing, ratio: Option<f64>| stamps(&convert_with(xml, &Options { swing, swing_ratio: ratio, ..Options::default() }));
+    let straight = [0, 8, 16, 24, 32, 40, 48, 56];
+    assert_eq!(stamps(&convert(&xml)), straight);
+    // Each offbeat moves two thirds of the way through its beat, while the beats stay put
+    assert_eq!(swung(&xml, Swing::Force, None), [0, 11, 16, 27, 32, 43, 48, 59]);
+    assert_eq!(swung(&xml, Swing::Force, Some(3.0)), [0, 12, 16, 28, 32, 44, 48, 60]);
+    // Detecting only swings measures marked to be swung
+    assert_eq!(swung(&xml, Swing::Detect, None), straight);
+    assert_eq!(swung(&marked, Swing::Detect, None), [0, 11, 16, 27, 32, 43, 48, 59]);
+    assert_eq!(swung(&marked, Swing::Off, None), straight);
+    assert!(convert_with(&xml, &Options { swing: Swing::Force, ..Options::default() }).contains("DurationStampMax = 63,"));
+}
+
+#[test]
+fn namespaced_document() {
+    let xml = score(
+        r#"<time><beats>2</beats><beat-type>4</beat-type></time><clef number="1"><sign>F</sign><line>4</line></clef>"#,
+        r#"<note><pitch><step>C</step><octave>3</octave></pitch><duration>2</duration><type>quarter</type><tie type="start"/></note>
+        <note><pitch><step>C</step><octave>3</octave></pitch><duration>2</duration><type>quarter</type><tie type="stop"/></note>"#,
+    );
+    let expected = convert(&xml);
+    assert!(expected.contains("TieType ='Start',") && expected.contains("{ 0, 'L4F' },"));
+    // A default namespace on the root element
+    let default = xml.replace("<score-partwise version=\"3.1\">", "<score-partwise xmlns=\"http://www.musicxml.org/ns\" version=\"3.1\">");
+    assert_eq!(convert(&default), expected);
+    // Every element written with a prefix
+    let body = &xml[xml.find("<score-partwise").unwrap()..];
+    let prefixed = body.replace("</", "</mx:").replace('<', "<mx:").replace("<mx:/", "</")
+        .replacen("<mx:score-partwise", "<mx:score-partwise xmlns:mx=\"http://www.musicxml.org/ns\"", 1);
+    assert_eq!(convert(&prefixed), expected);
+    // Attributes in another tool's namespace don't stand in for MusicXml's own
+    let foreign = default
+        .replace("xmlns=\"http://www.musicxml.org/ns\"", "xmlns=\"http://www.musicxml.org/ns\" xmlns:x=\"http://example.com/x\"")
+        .replace(r#"<tie type="start"/>"#, r#"<tie x:type="stop" type="start"/>"#)
+        .replace(r#"<clef number="1">"#, r#"<clef x:number="2" number="1">"#);
+    assert_eq!(convert(&foreign), expected);
+}
+
+#[test]
+fn pitch_index_of_every_step_and_octave() {
+    use mxl_2_solo::partwise::pitch_index_from_name;
+    let steps = [("C", 0), ("D", 2), ("E", 4), ("F", 5), ("G", 7), ("A", 9), ("B", 11)];
+    // Pitch indexes are piano key numbers, with A0 as 1 and every half step one more
+    for octave in 1..=8 {
+        for (step, half_steps) in steps.iter() {
+            assert_eq!(pitch_index_from_name(&format!("{}{}", step, octave)), Some(octave * 12 + half_steps - 8), "{}{}", step, octave);
+        }
+    }
+    assert_eq!(pitch_index_from_name("A0"), Some(1));
+    assert_eq!(pitch_index_from_name("B0"), Some(3));
+    // The rest of octave 0 is below the piano, so it is played an octave up, on the same key as
+    // octave 1
+    for (step, _) in steps[..5].iter() {
+        assert_eq!(pitch_index_from_name(&format!("{}0", step)), pitch_index_from_name(&format!("{}1", step)));
+    }
+    assert_eq!(pitch_index_from_name("C8"), Some(88));
+
+    // Middle C is written as 40
+    let gjm = convert(&score("", "<note><pitch><step>C</step><octave>4</octave></pitch><duration>8</duration><type>whole</type></note>"));
+    assert!(gjm.contains("[40] = { NumberedSign = 1, PlayingPitchIndex = 40,"));
+    let low = convert(&score("", "<note><pitch><step>A</step><octave>0</octave></pitch><duration>8</duration><type>whole</type></note>"));
+    assert_eq!(pitches(&low), [1]);
+}
+
+#[test]
+fn grace_notes_take_no_time() {
+    let note = |step: &str, duration: u32, note_type: &str| format!(
+        "<note><pitch><step>{}</step><octave>4</octave></pitch><duration>{}</duration><type>{}</type></note>", step, duration, note_type);
+    // Some exporters give grace notes a duration, which still mustn't move the notes after them
+    let grace = |step: &str, slash: &str| format!(
+        "<note><grace{}/><pitch><step>{}</step><octave>5</octave></pitch><duration>1</duration><type>eighth</type></note>", slash, step);
+    let plain = [note("C", 2, "quarter"), note("E", 2, "quarter"), note("G", 4, "half")];
+    let graced = [plain[0].clone(), grace("D", ""), plain[1].clone(), grace("F", r#" slash="yes""#), grace("A", ""), plain[2].clone()];
+    let time = "<time><beats>4</beats><beat-type>4</beat-type></time>";
+    let gjm = convert(&score(time, &graced.concat()));
+    assert_eq!(stamps(&gjm), [0, 16, 32]);
+    assert_eq!(pitches(&gjm), [40, 44, 47]);
+    assert_eq!(gjm, convert(&score(time, &plain.concat())));
+}
+
+#[test]
+fn zero_counts_are_invalid_values() {
+    let note = "<note><pitch><step>C</step><octave>4</octave></pitch><duration>2</duration><type>quarter</type></note>";
+    // Each of these is divided by, so a zero is reported instead of stopping the program
+    let cases = [
+        ("beats", score("<time><beats>0</beats><beat-type>4</beat-type></time>", note)),
+        ("beat-type", score("<time><beats>4</beats><beat-type>0</beat-type></time>", note)),
+        ("divisions", score("<time><beats>4</beats><beat-type>4</beat-type></time>", note).replace("<divisions>2</divisions>", "<divisions>0</divisions>")),
+    ];
+    for (expected, xml) in cases.iter() {
+        match convert_reader(xml.as_bytes(), &mut Vec::new(), &Options::default()) {
+            Err(mxl_2_solo::Error::InvalidValue { tag, value, .. }) => assert_eq!((tag.as_str(), value.as_str()), (*expected, "0")),
+            result => panic!("expected an invalid {}, got {:?}", expected, result),
+        }
+    }
+}
+
+#[test]
+fn zero_divisions_part_way_through_a_measure() {
+    let note = "<note><pitch><step>C</step><octave>4</octave></pitch><duration>2</duration><type>quarter</type></note>";
+    let notes = format!("{}<attributes><divisions>0</divisions></attributes>{}", note, note);
+    let xml = score("<time><beats>2</beats><beat-type>4</beat-type></time>", &notes);
+    // The notes before the change would otherwise be rescaled by the new divisions
+    match convert_reader(xml.as_bytes(), &mut Vec::new(), &Options::default()) {
+        Err(mxl_2_solo::Error::InvalidValue { tag, value, .. }) => assert_eq!((tag.as_str(), value.as_str()), ("divisions", "0")),
+        result => panic!("expected invalid divisions, got {:?}", result),
+    }
+}
+
+#[test]
+fn long_notes_split_into_tied_notes_gjm_has() {
+    let ties = |gjm: &str| -> Vec<String> {
+        gjm.lines().filter_map(|line| line.trim().strip_prefix("TieType =")).map(str::to_string).collect()
+    };
+    let breve = |duration: u32, dot: &str| score(
+        "<time><beats>6</beats><beat-type>2</beat-type></time>",
+        &format!("<note><pitch><step>C</step><octave>4</octave></pitch><duration>{}</duration><type>breve</type>{}</note>", duration, dot),
+    ).replace("<divisions>2</divisions>", "<divisions>4</divisions>");
+
+    // A dotted breve is three whole notes tied together
+    let gjm = convert(&breve(48, "<dot/>"));
+    assert_eq!(duration_types(&gjm), ["Whole"; 3]);
+    assert_eq!(stamps(&gjm), [0, 64, 128]);
+    assert_eq!(ties(&gjm), ["'Start',", "'Both',", "'End',"]);
+
+    // No single note lasts the five divisions left after a whole note, so they become a quarter
+    // tied to a 16th
+    let gjm = convert(&breve(21, ""));
+    assert_eq!(duration_types(&gjm), ["Whole", "Quarter", "The16th"]);
+    assert_eq!(stamps(&gjm), [0, 64, 80]);
+    assert_eq!(ties(&gjm), ["'Start',", "'Both',", "'End',"]);
+}
+
+#[test]
+fn job_count_given_either_way() {
+    let args = |list: &[&str]| Options::from_args(list.iter().map(|arg| arg.to_string()));
+    // The count after a space is taken as the count, not as the input path
+    let spaced = args(&["--jobs", "3", "in.musicxml"]);
+    assert_eq!(spaced.jobs, Some(3));
+    assert!(!spaced.usage_error);
+    assert_eq!(args(&["--jobs=3", "in.musicxml"]).jobs, Some(3));
+    assert_eq!(args(&["--jobs", "0"]).jobs, None);
+}
+
+#[test]
+fn parse_without_rendering() {
+    let measure = |number: u32| format!(
+        r#"<measure number="{}"><note><pitch><step>C</step><octave>4</octave></pitch><duration>8</duration><type>whole</type></note></measure>"#, number);
+    let part = |id: &str| format!(
+        r#"<part id="{}"><measure number="1"><attributes><divisions>2</divisions><time><beats>4</beats><beat-type>4</beat-type></time></attributes>
+        <note><pitch><step>C</step><octave>4</octave></pitch><duration>8</duration><type>whole</type></note></measure>{}{}</part>"#,
+        id, measure(2), measure(3));
+    let xml = format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<score-partwise version="3.1">
+  <part-list>
+    <score-part id="P1"><part-name>Violin</part-name></score-part>
+    <score-part id="P2"><part-name>Cello</part-name></score-part>
+  </part-list>
+  {}{}
+</score-partwise>"#, part("P1"), part("P2"));
+    // The model can be looked at before anything is written
+    let score = mxl_2_solo::partwise::parse(xml.as_bytes(), &Options::default()).unwrap();
+    assert_eq!(score.get_part_count(), 2);
+    assert_eq!(score.get_measure_count(), 3);
+    assert_eq!(score.get_skipped_measure_count(), 0);
+}
+
+/// A score of two parts where only the first declares its divisions
+fn second_part_without_divisions() -> String {
+    let notes = r#"<note><pitch><step>C</step><octave>4</octave></pitch><duration>2</duration><type>half</type></note>
+        <note><pitch><step>D</step><octave>4</octave></pitch><duration>2</duration><type>half</type></note>"#;
+    format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<score-partwise version="3.1">
+  <part-list>
+    <score-part id="P1"><part-name>Violin</part-name></score-part>
+    <score-part id="P2"><part-name>Cello</part-name></score-part>
+  </part-list>
+  <part id="P1"><measure number="1"><attributes><divisions>1</divisions><time><beats>4</beats><beat-type>4</beat-type></time></attributes>{0}</measure></part>
+  <part id="P2"><measure number="1"><attributes><time><beats>4</beats><beat-type>4</beat-type></time></attributes>{0}</measure></part>
+</score-partwise>"#, notes)
+}
+
+#[test]
+fn divisions_carry_over_to_a_later_part() {
+    // The second part counts in the divisions of the first instead of the default, so both line up
+    let gjm = convert(&second_part_without_divisions());
+    assert_eq!(stamps(&gjm), [0, 32, 0, 32]);
+    assert_eq!(duration_types(&gjm), ["Half"; 4]);
+}
+
+#[test]
+fn strict_mode_needs_divisions_in_every_part() {
+    // Strict mode refuses to guess, naming the part that has no divisions
+    let xml = second_part_without_divisions();
+    match convert_reader(xml.as_bytes(), &mut Vec::new(), &Options { strict: true, ..Options::default() }) {
+        Err(mxl_2_solo::Error::MissingDivisions(part)) => assert_eq!(part, 1),
+        result => panic!("expected missing divisions, got {:?}", result),
+    }
+}
+
+#[test]
+fn same_tempo_in_every_part() {
+    let direction = |tempo: u32| format!(r#"<direction><sound tempo="{}"/></direction>"#, tempo);
+    let note = "<note><pitch><step>C</step><octave>4</octave></pitch><duration>1</duration><type>quarter</type></note>";
+    let part = |id: &str| format!(
+        r#"<part id="{}"><measure number="1"><attributes><divisions>1</divisions><time><beats>1</beats><beat-type>4</beat-type></time></attributes>{}{}</measure>
+        <measure number="2">{}</measure><measure number="3">{}{}</measure></part>"#,
+        id, direction(90), note, note, direction(120), note);
+    let xml = format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<score-partwise version="3.1">
+  <part-list>
+    <score-part id="P1"><part-name>Flute</part-name></score-part>
+    <score-part id="P2"><part-name>Oboe</part-name></score-part>
+    <score-part id="P3"><part-name>Horn</part-name></score-part>
+  </part-list>
+  {}{}{}
+</score-partwise>"#, part("P1"), part("P2"), part("P3"));
+    // Each change is written once, however many parts repeat it
+    assert!(convert(&xml).contains("\tMeasureBeatsPerMinuteMap = {\n\t\t{ 0, 90 },\n\t\t{ 2, 120 },\n\t},\n"));
+
+    // The parts agree, so nothing is warned about
+    let dir = std::env::temp_dir().join(format!("mxl_2_solo_tempo_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let options = Options { report: true, ..Options::default() };
+    mxl_2_solo::partwise::convert_reader_to_path(xml.as_bytes(), "tempo.musicxml", &dir.join("tempo.gjm"), &options).unwrap();
+    let report = std::fs::read_to_string(dir.join("tempo.json")).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    let report: serde_json::Value = serde_json::from_str(&report).unwrap();
+    assert_eq!(report["tempo_map"], serde_json::json!([[0, 90], [2, 120]]));
+    assert_eq!(report["warnings"], serde_json::json!([]));
+}
+
+#[test]
+fn score_without_notes() {
+    let xml = score(
+        r#"<time><beats>3</beats><beat-type>4</beat-type></time></attributes><direction><sound tempo="80"/></direction><attributes>"#,
+        "",
+    );
+    let gjm = convert(&xml);
+    // The header keeps the declared meter and tempo, and the track holds one empty measure
+    assert!(gjm.contains("\tBeatsPerMeasure = 3,\n\tBeatDurationType = '4',\n"));
+    assert!(gjm.contains("\tMeasureBeatsPerMinuteMap = {\n\t\t{ 0, 80 },\n\t},\n\tMeasureAlignedCount = 1,\n"));
+    assert!(gjm.ends_with("\t\t[0] = {\n\t\t\tDurationStampMax = 47,\n\t\t\tNotePackCount = 0,\n\t\t},\n\t},\n}"), "{}", gjm);
+    assert!(stamps(&gjm).is_empty());
+}
+
+#[test]
+fn staves_found_from_the_notes() {
+    // No staves are declared, but the notes are spread over two staves across two measures
+    let gjm = convert(&score(
+        "<time><beats>2</beats><beat-type>4</beat-type></time>",
+        r#"<note><pitch><step>E</step><octave>5</octave></pitch><duration>4</duration><type>half</type><staff>1</staff></note>
+        <backup><duration>4</duration></backup>
+        <note><pitch><step>C</step><octave>3</octave></pitch><duration>4</duration><type>half</type><staff>2</staff></note>
+      </measure>
+      <measure number="2">
+        <note><pitch><step>F</step><octave>5</octave></pitch><duration>4</duration><type>half</type><staff>1</staff></note>
+        <backup><duration>4</duration></backup>
+        <note><pitch><step>D</step><octave>3</octave></pitch><duration>4</duration><type>half</type><staff>2</staff></note>"#,
+    ));
+    // Each staff gets its own track holding its own notes, measure for measure
+    let (upper, lower) = gjm.split_at(gjm.find("\n\t[1] = {").unwrap());
+    assert!(upper.contains("\t\t-- Piano (staff 1)\n"));
+    assert!(lower.contains("\t\t-- Piano (staff 2)\n"));
+    assert_eq!(pitches(upper), [56, 57]);
+    assert_eq!(pitches(lower), [28, 30]);
+    assert_eq!(stamps(lower), [0, 0]);
+}
+
+#[test]
+fn slur_over_different_pitches() {
+    let note = |step: &str, slur: &str| format!(
+        "<note><pitch><step>{}</step><octave>4</octave></pitch><duration>2</duration><type>quarter</type><notations>{}</notations></note>", step, slur);
+    let xml = score("<time><beats>3</beats><beat-type>4</beat-type></time>", &[
+        note("C", r#"<slur type="start" number="1"/>"#),
+        note("E", ""),
+        note("G", r#"<slur type="stop" number="1"/>"#),
+    ].concat());
+    // A slur is phrasing rather than a tie, so the notes are all struck
+    let gjm = convert(&xml);
+    assert_eq!(pitches(&gjm), [40, 44, 47]);
+    assert!(!gjm.contains("TieType"), "{}", gjm);
+    let issues = mxl_2_solo::partwise::check_reader(xml.as_bytes(), &Options::default()).unwrap();
+    assert!(issues.iter().any(|issue| issue.feature == "slurs"));
+}
+
+#[test]
+fn fingering_string_and_fret() {
+    let note = |step: &str, technical: &str| format!(
+        "<note><pitch><step>{}</step><octave>3</octave></pitch><duration>2</duration><type>quarter</type>\
+        <notations><technical>{}</technical></notations></note>", step, technical);
+    let xml = score("<time><beats>2</beats><beat-type>4</beat-type></time>", &[
+        note("A", "<fingering>2</fingering><string>5</string><fret>0</fret>"),
+        note("C", "<string>5</string><fret>3</fret>"),
+    ].concat());
+    // The markings are read without disturbing the notes around them
+    let gjm = convert(&xml);
+    assert_eq!(pitches(&gjm), [37, 28]);
+    assert_eq!(stamps(&gjm), [0, 16]);
+    // GJM has nowhere to keep them, so each marked note is reported
+    let issues = mxl_2_solo::partwise::check_reader(xml.as_bytes(), &Options::default()).unwrap();
+    let technical: Vec<&str> = issues.iter().filter(|issue| issue.feature == "technical markings").map(|issue| issue.message.as_str()).collect();
+    assert_eq!(technical.len(), 2);
+    assert!(technical[0].ends_with("marked with fingering 2, string 5, fret 0, which GJM does not support"), "{}", technical[0]);
+    assert!(technical[1].ends_with("marked with string 5, fret 3, which GJM does not support"), "{}", technical[1]);
+}